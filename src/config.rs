@@ -1,19 +1,125 @@
-use serde::Deserialize;
+use serde::{Deserialize, Deserializer, Serialize};
+use std::fmt;
 use std::fs;
 use std::path::Path;
 
 const DEFAULT_TIMEOUT: u64 = 300; // 5 minutes
+const DEFAULT_SHELL: &str = "sh";
+const DEFAULT_KILL_GRACE: u64 = 5;
+const DEFAULT_MAX_LOG_BYTES: u64 = 1024 * 1024;
+const DEFAULT_MAX_PARALLEL: usize = 1;
+const DEFAULT_MAX_CONCURRENT_PIPELINES: usize = 1;
+const DEFAULT_MAX_OUTPUT_BYTES: u64 = 10 * 1024 * 1024;
+const DEFAULT_TICK_INTERVAL: u64 = 60;
+const DEFAULT_AGENT_BACKOFF_BASE: u64 = 5;
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct Config {
-    #[serde(default = "default_timeout")]
+    /// Seconds, or a duration such as "30m"
+    #[serde(default = "default_timeout", deserialize_with = "deserialize_secs")]
     pub timeout: u64,
+
+    /// Upper bound on step timeouts; longer per-step overrides are cut down
+    /// to it.
+    #[serde(default, deserialize_with = "deserialize_opt_secs")]
+    pub max_timeout: Option<u64>,
+
+    /// Shell used for bash steps. Invoked as `<shell> -c <script>`.
+    #[serde(default = "default_shell")]
+    pub shell: String,
+
+    /// Seconds a timed-out step gets to exit after SIGTERM before it is killed.
+    #[serde(default = "default_kill_grace")]
+    pub kill_grace_secs: u64,
+
+    /// Reset steps left `running` by a crashed runner back to pending.
+    #[serde(default)]
+    pub reclaim_stale: bool,
+
+    /// Size at which a step's log in `logs/` is rotated. 0 disables rotation.
+    #[serde(default = "default_max_log_bytes")]
+    pub max_log_bytes: u64,
+
+    /// How many ready steps of a pipeline one tick may run at once. The
+    /// default of 1 keeps to one step per tick.
+    #[serde(default = "default_max_parallel")]
+    pub max_parallel: usize,
+
+    /// How many pipelines one `run` (or daemon tick) may tick at once, each
+    /// on its own thread. The default of 1 ticks them one after another.
+    #[serde(default = "default_max_concurrent_pipelines")]
+    pub max_concurrent_pipelines: usize,
+
+    /// How much of each of a step's stdout and stderr is kept; the rest is
+    /// dropped and marked as truncated. 0 keeps everything.
+    #[serde(default = "default_max_output_bytes")]
+    pub max_output_bytes: u64,
+
+    /// Seconds between ticks in `cronclaw daemon`.
+    #[serde(default = "default_tick_interval")]
+    pub tick_interval_secs: u64,
+
+    /// Shell command run when a pipeline completes. A pipeline's own
+    /// `on_success` takes its place.
+    #[serde(default)]
+    pub on_success: Option<String>,
+
+    /// Shell command run when a step fails for good, with CRONCLAW_STEP and
+    /// CRONCLAW_ERROR describing it. A pipeline's own `on_failure` takes its
+    /// place.
+    #[serde(default)]
+    pub on_failure: Option<String>,
+
+    /// What to do with a ready step whose declared `inputs` aren't all in the
+    /// workspace.
+    #[serde(default)]
+    pub missing_inputs: MissingInputs,
+
+    /// Bytes the workspace must have free before a step is claimed, which
+    /// also checks that it can be written to. Unset skips both checks.
+    #[serde(default)]
+    pub min_free_bytes: Option<u64>,
+
+    /// Extra tries an agent step gets, within its timeout, when openclaw
+    /// exits non-zero, before the step fails. Separate from a step's own
+    /// `retries`, which wait for the next tick.
+    #[serde(default)]
+    pub agent_retries: u32,
+
+    /// Wait before the first of those tries, doubling for each one after.
+    #[serde(default = "default_agent_backoff_base")]
+    pub agent_backoff_base_secs: u64,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MissingInputs {
+    /// Leave the step pending until they appear.
+    #[default]
+    Wait,
+    /// Fail the step.
+    Fail,
 }
 
 impl Default for Config {
     fn default() -> Self {
         Config {
             timeout: DEFAULT_TIMEOUT,
+            max_timeout: None,
+            shell: DEFAULT_SHELL.to_string(),
+            kill_grace_secs: DEFAULT_KILL_GRACE,
+            reclaim_stale: false,
+            max_log_bytes: DEFAULT_MAX_LOG_BYTES,
+            max_parallel: DEFAULT_MAX_PARALLEL,
+            max_concurrent_pipelines: DEFAULT_MAX_CONCURRENT_PIPELINES,
+            max_output_bytes: DEFAULT_MAX_OUTPUT_BYTES,
+            tick_interval_secs: DEFAULT_TICK_INTERVAL,
+            on_success: None,
+            on_failure: None,
+            missing_inputs: MissingInputs::default(),
+            min_free_bytes: None,
+            agent_retries: 0,
+            agent_backoff_base_secs: DEFAULT_AGENT_BACKOFF_BASE,
         }
     }
 }
@@ -22,16 +128,244 @@ fn default_timeout() -> u64 {
     DEFAULT_TIMEOUT
 }
 
-pub fn load(path: &Path) -> Config {
+fn default_shell() -> String {
+    DEFAULT_SHELL.to_string()
+}
+
+fn default_kill_grace() -> u64 {
+    DEFAULT_KILL_GRACE
+}
+
+fn default_max_log_bytes() -> u64 {
+    DEFAULT_MAX_LOG_BYTES
+}
+
+fn default_max_parallel() -> usize {
+    DEFAULT_MAX_PARALLEL
+}
+
+fn default_max_concurrent_pipelines() -> usize {
+    DEFAULT_MAX_CONCURRENT_PIPELINES
+}
+
+fn default_max_output_bytes() -> u64 {
+    DEFAULT_MAX_OUTPUT_BYTES
+}
+
+fn default_tick_interval() -> u64 {
+    DEFAULT_TICK_INTERVAL
+}
+
+fn default_agent_backoff_base() -> u64 {
+    DEFAULT_AGENT_BACKOFF_BASE
+}
+
+/// Parse a duration such as "90s", "30m", "2h", "7d" or "2w" into seconds.
+/// A bare number is taken as seconds.
+pub fn parse_duration(text: &str) -> Result<u64, String> {
+    let invalid = || format!("invalid duration '{}': expected e.g. 90s, 30m or 2h", text);
+    let trimmed = text.trim();
+    let (number, unit) = match trimmed.find(|c: char| !c.is_ascii_digit()) {
+        Some(i) => trimmed.split_at(i),
+        None => (trimmed, "s"),
+    };
+    let multiplier = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 3600,
+        "d" => 86400,
+        "w" => 7 * 86400,
+        _ => return Err(invalid()),
+    };
+    let number: u64 = number.parse().map_err(|_| invalid())?;
+    Ok(number.saturating_mul(multiplier))
+}
+
+/// Seconds given either as a plain number or as a duration string.
+struct Secs(u64);
+
+impl<'de> Deserialize<'de> for Secs {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct SecsVisitor;
+
+        impl serde::de::Visitor<'_> for SecsVisitor {
+            type Value = Secs;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a number of seconds or a duration such as \"30m\"")
+            }
+
+            fn visit_u64<E: serde::de::Error>(self, secs: u64) -> Result<Secs, E> {
+                Ok(Secs(secs))
+            }
+
+            fn visit_i64<E: serde::de::Error>(self, secs: i64) -> Result<Secs, E> {
+                u64::try_from(secs)
+                    .map(Secs)
+                    .map_err(|_| E::custom(format!("invalid duration {}: can't be negative", secs)))
+            }
+
+            fn visit_str<E: serde::de::Error>(self, text: &str) -> Result<Secs, E> {
+                parse_duration(text).map(Secs).map_err(E::custom)
+            }
+        }
+
+        deserializer.deserialize_any(SecsVisitor)
+    }
+}
+
+/// For a seconds field that also takes a duration such as "30m".
+pub fn deserialize_secs<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u64, D::Error> {
+    Secs::deserialize(deserializer).map(|secs| secs.0)
+}
+
+/// `deserialize_secs` for an optional field.
+pub fn deserialize_opt_secs<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<u64>, D::Error> {
+    Option::<Secs>::deserialize(deserializer).map(|secs| secs.map(|secs| secs.0))
+}
+
+pub fn load(path: &Path) -> Result<Config, String> {
     if !path.exists() {
-        return Config::default();
+        return Ok(Config::default());
     }
 
-    let content = match fs::read_to_string(path) {
-        Ok(c) => c,
-        Err(_) => return Config::default(),
+    let content = fs::read_to_string(path)
+        .map_err(|e| format!("failed to read {}: {}", path.display(), e))?;
+
+    // An empty or comment-only file is no settings at all, not an error
+    serde_yaml::from_str::<Option<Config>>(&content)
+        .map(Option::unwrap_or_default)
+        .map_err(|e| format!("failed to parse {}: {}", path.display(), e))
+}
+
+
+/// What `load` would silently ignore in the config file at `path`: settings
+/// it doesn't know, e.g. a misspelled `timout`. A file that doesn't parse
+/// is left to `load` to report.
+pub fn problems(path: &Path) -> Vec<String> {
+    let Ok(content) = fs::read_to_string(path) else {
+        return Vec::new();
+    };
+    let Ok(serde_yaml::Value::Mapping(map)) = serde_yaml::from_str(&content) else {
+        return Vec::new();
     };
 
-    serde_yaml::from_str(&content).unwrap_or_default()
+    let known = settings(&Config::default()).unwrap_or_default();
+    map.keys()
+        .filter(|key| !known.contains_key(*key))
+        .map(|key| match key.as_str() {
+            Some(key) => format!("unknown setting '{}' is ignored", key),
+            None => format!("unknown setting {:?} is ignored", key),
+        })
+        .collect()
+}
+
+/// The effective value of one setting: text as is, anything else as YAML.
+/// Errors on an unknown key.
+pub fn get(cfg: &Config, key: &str) -> Result<String, String> {
+    let value = settings(cfg)?
+        .remove(key)
+        .ok_or_else(|| unknown_key(key))?;
+    if let serde_yaml::Value::String(text) = value {
+        return Ok(text);
+    }
+    let yaml = serde_yaml::to_string(&value)
+        .map_err(|e| format!("failed to serialize '{}': {}", key, e))?;
+    Ok(yaml.trim_end().to_string())
+}
+
+/// Every setting with its effective value, as YAML.
+pub fn show(cfg: &Config) -> Result<String, String> {
+    serde_yaml::to_string(cfg).map_err(|e| format!("failed to serialize config: {}", e))
 }
 
+fn settings(cfg: &Config) -> Result<serde_yaml::Mapping, String> {
+    match serde_yaml::to_value(cfg) {
+        Ok(serde_yaml::Value::Mapping(map)) => Ok(map),
+        Ok(_) => Err("config didn't serialize to a mapping".to_string()),
+        Err(e) => Err(format!("failed to serialize config: {}", e)),
+    }
+}
+
+/// Set one setting in the config file at `path`, given as YAML (`600`,
+/// `bash`, `null`); a value that only makes sense as text, like a shell
+/// command, is taken as a string. The file is edited in place so its
+/// comments survive: an existing `key:` line is replaced, else a
+/// commented-out `# key:` line, else the setting is appended. Nothing is
+/// written unless the result is a valid config.
+pub fn set(path: &Path, key: &str, value: &str) -> Result<(), String> {
+    if settings(&Config::default())?.get(key).is_none() {
+        return Err(unknown_key(key));
+    }
+    let content = if path.exists() {
+        fs::read_to_string(path)
+            .map_err(|e| format!("failed to read {}: {}", path.display(), e))?
+    } else {
+        String::new()
+    };
+
+    let text = serde_yaml::Value::String(value.to_string());
+    let candidates = match serde_yaml::from_str(value) {
+        Ok(parsed) if parsed != text => vec![parsed, text],
+        _ => vec![text],
+    };
+    let mut error = None;
+    for candidate in candidates {
+        match with_setting(&content, key, &candidate) {
+            Ok(content) => {
+                return fs::write(path, content)
+                    .map_err(|e| format!("failed to write {}: {}", path.display(), e));
+            }
+            Err(e) => {
+                error.get_or_insert(e);
+            }
+        }
+    }
+    Err(format!("invalid value for '{}': {}", key, error.unwrap()))
+}
+
+/// `content` with `key` set to `value`, if that is still a valid config.
+fn with_setting(content: &str, key: &str, value: &serde_yaml::Value) -> Result<String, String> {
+    let rendered = serde_yaml::to_string(value).map_err(|e| e.to_string())?;
+    let rendered = rendered.trim_end();
+    if rendered.contains('\n') {
+        return Err("only single-line values can be set; edit config.yaml instead".to_string());
+    }
+    let content = replace_setting(content, key, &format!("{}: {}", key, rendered));
+    serde_yaml::from_str::<Option<Config>>(&content).map_err(|e| e.to_string())?;
+    Ok(content)
+}
+
+/// `content` with the line setting `key` replaced by `line`.
+fn replace_setting(content: &str, key: &str, line: &str) -> String {
+    let mut lines: Vec<&str> = content.lines().collect();
+    let sets = |l: &str| {
+        l.strip_prefix(key)
+            .is_some_and(|rest| rest.trim_start().starts_with(':'))
+    };
+    let set = lines.iter().position(|l| sets(l));
+    let commented = lines
+        .iter()
+        .position(|l| l.strip_prefix('#').is_some_and(|l| sets(l.trim_start())));
+
+    match set.or(commented) {
+        Some(i) => lines[i] = line,
+        None => lines.push(line),
+    }
+    let mut content = lines.join("\n");
+    content.push('\n');
+    content
+}
+
+fn unknown_key(key: &str) -> String {
+    let known: Vec<String> = settings(&Config::default())
+        .map(|map| {
+            map.keys()
+                .filter_map(|k| k.as_str().map(String::from))
+                .collect()
+        })
+        .unwrap_or_default();
+    format!("unknown setting '{}' (known: {})", key, known.join(", "))
+}
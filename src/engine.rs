@@ -0,0 +1,152 @@
+use crate::config::Config;
+use crate::error::CronclawError;
+use crate::runner::{self, RunOptions, Ticked};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+/// How one pipeline's tick went.
+#[derive(Debug, Clone)]
+pub struct PipelineResult {
+    /// The pipeline's name, i.e. its directory name.
+    pub name: String,
+    pub dir: PathBuf,
+    /// Why the tick failed, one problem per line.
+    pub error: Option<CronclawError>,
+    /// Nothing could run because a step is running in another process.
+    pub busy: bool,
+}
+
+/// The outcome of ticking a set of pipelines, in the order they ran.
+#[derive(Debug, Clone, Default)]
+pub struct RunReport {
+    pub pipelines: Vec<PipelineResult>,
+}
+
+impl RunReport {
+    /// True if every pipeline ticked without error.
+    pub fn is_ok(&self) -> bool {
+        self.pipelines.iter().all(|p| p.error.is_none())
+    }
+
+    /// The pipelines whose tick failed.
+    pub fn failures(&self) -> impl Iterator<Item = &PipelineResult> {
+        self.pipelines.iter().filter(|p| p.error.is_some())
+    }
+}
+
+/// Pipeline directories under `home` (those containing a pipeline.yaml), sorted by name.
+pub fn pipeline_dirs(home: &Path) -> Result<Vec<PathBuf>, CronclawError> {
+    let pipelines = home.join("pipelines");
+    let read_error = |e: std::io::Error| {
+        CronclawError::Io(format!("failed to read {}: {}", pipelines.display(), e))
+    };
+    let entries = fs::read_dir(&pipelines).map_err(read_error)?;
+
+    let mut dirs = Vec::new();
+    for entry in entries {
+        let path = entry.map_err(read_error)?.path();
+        if path.is_dir() && path.join("pipeline.yaml").exists() {
+            dirs.push(path);
+        }
+    }
+    dirs.sort();
+    Ok(dirs)
+}
+
+/// Whether the pipeline in `dir` carries any of `tags`. No tags matches
+/// every pipeline; a pipeline that can't be loaded matches none.
+pub fn has_any_tag(dir: &Path, tags: &[String]) -> bool {
+    if tags.is_empty() {
+        return true;
+    }
+    crate::pipeline::load(&dir.join("pipeline.yaml"))
+        .is_ok_and(|pipeline| pipeline.tags.iter().any(|tag| tags.contains(tag)))
+}
+
+/// Advance each pipeline in `dirs` by one tick, carrying on past failures.
+/// Up to `cfg.max_concurrent_pipelines` pipelines tick at once, each on its
+/// own thread; the report keeps the order of `dirs` regardless.
+pub fn run_pipelines(dirs: &[PathBuf], cfg: &Config, opts: &RunOptions) -> RunReport {
+    let tick = |dir: &PathBuf| {
+        let result = runner::run_pipeline(dir, cfg, opts);
+        PipelineResult {
+            name: dir
+                .file_name()
+                .map(|n| n.to_string_lossy().to_string())
+                .unwrap_or_default(),
+            dir: dir.clone(),
+            busy: matches!(result, Ok(Ticked::Busy)),
+            error: result.err(),
+        }
+    };
+
+    let workers = cfg.max_concurrent_pipelines.clamp(1, dirs.len().max(1));
+    if workers == 1 {
+        return RunReport {
+            pipelines: dirs.iter().map(tick).collect(),
+        };
+    }
+
+    // Each worker takes the next pipeline not yet claimed until none are left
+    let next = AtomicUsize::new(0);
+    let mut results: Vec<(usize, PipelineResult)> = std::thread::scope(|scope| {
+        let handles: Vec<_> = (0..workers)
+            .map(|_| {
+                let (next, tick) = (&next, &tick);
+                scope.spawn(move || {
+                    let mut done = Vec::new();
+                    loop {
+                        let i = next.fetch_add(1, Ordering::SeqCst);
+                        let Some(dir) = dirs.get(i) else {
+                            return done;
+                        };
+                        done.push((i, tick(dir)));
+                    }
+                })
+            })
+            .collect();
+        handles
+            .into_iter()
+            .flat_map(|h| h.join().unwrap())
+            .collect()
+    });
+    results.sort_by_key(|(i, _)| *i);
+    RunReport {
+        pipelines: results.into_iter().map(|(_, result)| result).collect(),
+    }
+}
+
+/// Advance every pipeline under `home` by one tick, as `cronclaw run` does.
+pub fn run_all(home: &Path, cfg: &Config, opts: &RunOptions) -> Result<RunReport, CronclawError> {
+    Ok(run_pipelines(&pipeline_dirs(home)?, cfg, opts))
+}
+
+/// Tick every pipeline under `home` once per `cfg.tick_interval_secs` until
+/// `stop` is set, as `cronclaw daemon` does. `stop` is checked between ticks,
+/// so a running step is always finished first. Pipelines with a `schedule`
+/// or `watch` still only run when it is due, and the state locks keep ticks
+/// from an external `cronclaw run` from clashing with the daemon's.
+pub fn daemon(
+    home: &Path,
+    cfg: &Config,
+    opts: &RunOptions,
+    stop: &AtomicBool,
+    mut on_tick: impl FnMut(Result<RunReport, CronclawError>),
+) {
+    let interval = Duration::from_secs(cfg.tick_interval_secs.max(1));
+    while !stop.load(Ordering::SeqCst) {
+        let started = Instant::now();
+        on_tick(run_all(home, cfg, opts));
+
+        // Sleep in short slices so a stop request doesn't wait out the interval
+        while !stop.load(Ordering::SeqCst) {
+            let elapsed = started.elapsed();
+            if elapsed >= interval {
+                break;
+            }
+            std::thread::sleep((interval - elapsed).min(Duration::from_millis(200)));
+        }
+    }
+}
@@ -0,0 +1,95 @@
+use std::fmt;
+
+/// What went wrong in a cronclaw operation, for library callers that need to
+/// tell problems apart. Displays as the message the CLI prints for it.
+#[derive(Debug, Clone, PartialEq)]
+pub enum CronclawError {
+    /// A pipeline.yaml, an included file or state.json that couldn't be
+    /// read, or doesn't parse or validate
+    Parse(String),
+    /// A file that couldn't be written, created, removed or locked
+    Io(String),
+    /// state.json doesn't match pipeline.yaml
+    StateMismatch(String),
+    /// A `{{ ... }}` template that couldn't be resolved
+    Template(String),
+    /// A step was stopped for running past its timeout
+    Timeout { id: String, message: String },
+    /// A step was stopped by an interruption and left to run again
+    Interrupted { id: String, message: String },
+    /// A step failed for good, with its exit code if it got as far as one
+    StepFailed {
+        id: String,
+        code: Option<i32>,
+        message: String,
+    },
+    /// Several problems at once, e.g. from steps run side by side
+    Several(Vec<CronclawError>),
+    /// Anything else
+    Other(String),
+}
+
+/// What kind of problem stopped a tick, for callers that treat them
+/// differently. See `CronclawError::kind`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorKind {
+    /// A step failed for good
+    Step,
+    /// pipeline.yaml or state.json couldn't be read or doesn't match
+    Invalid,
+    /// Anything else, e.g. a file that couldn't be written
+    Other,
+}
+
+impl CronclawError {
+    /// The broad kind of problem, which decides `cronclaw run`'s exit code.
+    /// Of several, a failed step outranks an invalid file, which outranks
+    /// anything else, whatever order they came in.
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            CronclawError::Timeout { .. } | CronclawError::StepFailed { .. } => ErrorKind::Step,
+            CronclawError::Parse(_) | CronclawError::StateMismatch(_) => ErrorKind::Invalid,
+            CronclawError::Several(errors) => {
+                let kinds: Vec<ErrorKind> = errors.iter().map(CronclawError::kind).collect();
+                if kinds.contains(&ErrorKind::Step) {
+                    ErrorKind::Step
+                } else if kinds.contains(&ErrorKind::Invalid) {
+                    ErrorKind::Invalid
+                } else {
+                    ErrorKind::Other
+                }
+            }
+            CronclawError::Io(_)
+            | CronclawError::Template(_)
+            | CronclawError::Interrupted { .. }
+            | CronclawError::Other(_) => ErrorKind::Other,
+        }
+    }
+}
+
+impl fmt::Display for CronclawError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CronclawError::Parse(message)
+            | CronclawError::Io(message)
+            | CronclawError::StateMismatch(message)
+            | CronclawError::Template(message)
+            | CronclawError::Timeout { message, .. }
+            | CronclawError::Interrupted { message, .. }
+            | CronclawError::StepFailed { message, .. }
+            | CronclawError::Other(message) => f.write_str(message),
+            CronclawError::Several(errors) => {
+                let messages: Vec<String> = errors.iter().map(ToString::to_string).collect();
+                f.write_str(&messages.join("\n"))
+            }
+        }
+    }
+}
+
+impl std::error::Error for CronclawError {}
+
+impl From<String> for CronclawError {
+    fn from(message: String) -> Self {
+        CronclawError::Other(message)
+    }
+}
@@ -0,0 +1,77 @@
+use crate::pipeline::Pipeline;
+use crate::state::{State, StepStatus};
+
+/// Fill colour for a step in the rendered graph.
+fn colour(status: Option<&StepStatus>) -> &'static str {
+    match status {
+        Some(StepStatus::Pending) | None => "#e0e0e0",
+        Some(StepStatus::Running) => "#fff2a8",
+        Some(StepStatus::Completed) => "#b7e4b0",
+        Some(StepStatus::Failed) => "#f4a6a6",
+        Some(StepStatus::Skipped) => "#f5f5f5",
+    }
+}
+
+/// Dependency edges as (dependency, dependent) step indices.
+fn edges(pipeline: &Pipeline) -> Vec<(usize, usize)> {
+    let mut edges = Vec::new();
+    for i in 0..pipeline.steps.len() {
+        for need in pipeline.needs_of(i) {
+            if let Some(j) = pipeline.steps.iter().position(|s| s.id == need) {
+                edges.push((j, i));
+            }
+        }
+    }
+    edges
+}
+
+/// Render the step graph as Graphviz DOT, e.g. for `dot -Tpng`.
+pub fn to_dot(name: &str, pipeline: &Pipeline, state: &State) -> String {
+    let escape = |s: &str| s.replace('\\', "\\\\").replace('"', "\\\"");
+    let quote = |s: &str| format!("\"{}\"", escape(s));
+
+    let mut out = format!("digraph {} {{\n", quote(name));
+    out.push_str("  rankdir=LR;\n");
+    out.push_str("  node [shape=box, style=\"rounded,filled\"];\n");
+    for step in &pipeline.steps {
+        let status = state.steps.get(&step.id).map(|s| &s.status);
+        out.push_str(&format!(
+            "  {} [label=\"{}\\n({})\", fillcolor=\"{}\"];\n",
+            quote(&step.id),
+            escape(&step.id),
+            step.step_type.name(),
+            colour(status)
+        ));
+    }
+    for (from, to) in edges(pipeline) {
+        out.push_str(&format!(
+            "  {} -> {};\n",
+            quote(&pipeline.steps[from].id),
+            quote(&pipeline.steps[to].id)
+        ));
+    }
+    out.push_str("}\n");
+    out
+}
+
+/// Render the step graph as a Mermaid flowchart.
+pub fn to_mermaid(pipeline: &Pipeline, state: &State) -> String {
+    // Step ids may hold characters Mermaid won't take as node ids
+    let node = |i: usize| format!("s{}", i);
+
+    let mut out = String::from("flowchart LR\n");
+    for (i, step) in pipeline.steps.iter().enumerate() {
+        let status = state.steps.get(&step.id).map(|s| &s.status);
+        out.push_str(&format!(
+            "  {}[\"{} ({})\"]\n",
+            node(i),
+            step.id.replace('"', "#quot;"),
+            step.step_type.name()
+        ));
+        out.push_str(&format!("  style {} fill:{}\n", node(i), colour(status)));
+    }
+    for (from, to) in edges(pipeline) {
+        out.push_str(&format!("  {} --> {}\n", node(from), node(to)));
+    }
+    out
+}
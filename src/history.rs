@@ -0,0 +1,110 @@
+use serde::{Deserialize, Serialize};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use crate::config;
+use crate::state::{self, State, StepStatus};
+
+/// Ledger of a pipeline's completed runs, one JSON record per line.
+pub fn history_path(pipeline_dir: &Path) -> PathBuf {
+    pipeline_dir.join("history.jsonl")
+}
+
+/// One completed run of a pipeline.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Run {
+    /// When the run's first step first started (RFC3339)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub started_at: Option<String>,
+    pub finished_at: String,
+    /// End to end, from `started_at` to `finished_at`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub duration_secs: Option<u64>,
+    pub steps: Vec<StepRun>,
+}
+
+/// How one step of a completed run went.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct StepRun {
+    pub id: String,
+    pub status: StepStatus,
+    /// Duration of the step's last attempt
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub duration_secs: Option<u64>,
+}
+
+impl Run {
+    /// The run that has just finished with every step of `state` done.
+    pub fn from_state(state: &State) -> Self {
+        let started_at = state
+            .steps
+            .values()
+            .filter_map(|s| s.first_started_at.as_ref().or(s.started_at.as_ref()))
+            .min()
+            .cloned();
+        let finished_at = state::now();
+        let duration_secs = started_at.as_deref().and_then(state::secs_since);
+        let steps = state
+            .ordered_steps()
+            .into_iter()
+            .map(|(id, s)| StepRun {
+                id: id.to_string(),
+                status: s.status.clone(),
+                duration_secs: s.duration_secs,
+            })
+            .collect();
+        Run {
+            started_at,
+            finished_at,
+            duration_secs,
+            steps,
+        }
+    }
+
+    /// Seconds elapsed since the run finished.
+    pub fn secs_since_finished(&self) -> Option<u64> {
+        state::secs_since(&self.finished_at)
+    }
+}
+
+/// Append `run` to the pipeline's history.
+pub fn append(pipeline_dir: &Path, run: &Run) -> Result<(), String> {
+    let path = history_path(pipeline_dir);
+    let mut line =
+        serde_json::to_string(run).map_err(|e| format!("failed to record run: {}", e))?;
+    line.push('\n');
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .and_then(|mut file| file.write_all(line.as_bytes()))
+        .map_err(|e| format!("failed to write {}: {}", path.display(), e))
+}
+
+/// Every run recorded for the pipeline, oldest first. No history file
+/// means no runs yet.
+pub fn load(pipeline_dir: &Path) -> Result<Vec<Run>, String> {
+    let path = history_path(pipeline_dir);
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let content = fs::read_to_string(&path)
+        .map_err(|e| format!("failed to read {}: {}", path.display(), e))?;
+    content
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(i, line)| {
+            serde_json::from_str(line)
+                .map_err(|e| format!("{} line {}: {}", path.display(), i + 1, e))
+        })
+        .collect()
+}
+
+/// Parse an age such as "90s", "30m", "12h", "7d" or "2w" into seconds.
+/// A bare number is taken as seconds.
+pub fn parse_age(age: &str) -> Result<u64, String> {
+    config::parse_duration(age)
+        .map_err(|_| format!("invalid age '{}': expected e.g. 30m, 12h or 7d", age))
+}
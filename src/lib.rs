@@ -1,4 +1,9 @@
 pub mod config;
+pub mod engine;
+pub mod error;
+pub mod graph;
+pub mod history;
+pub mod logs;
 pub mod openclaw;
 pub mod pipeline;
 pub mod runner;
@@ -0,0 +1,91 @@
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use crate::state;
+
+/// Directory holding a pipeline's step logs.
+pub fn log_dir(pipeline_dir: &Path) -> PathBuf {
+    pipeline_dir.join("logs")
+}
+
+/// Log file for one step.
+pub fn log_path(pipeline_dir: &Path, step_id: &str) -> PathBuf {
+    log_dir(pipeline_dir).join(format!("{}.log", step_id))
+}
+
+/// Where a step log is moved when it outgrows `max_log_bytes`.
+pub fn rotated_path(log: &Path) -> PathBuf {
+    let mut name = log.as_os_str().to_owned();
+    name.push(".1");
+    PathBuf::from(name)
+}
+
+/// Operational log for one step: every attempt's output and outcome,
+/// appended with timestamps. Independent of the step's `output`/`error`
+/// routing.
+///
+/// Writing is best-effort — a log that can't be written produces a warning
+/// but never fails the step.
+pub struct StepLog {
+    path: PathBuf,
+    max_bytes: u64,
+}
+
+impl StepLog {
+    /// A log for `step_id`, rotated once it would grow past `max_bytes`
+    /// (0 means never rotate).
+    pub fn new(pipeline_dir: &Path, step_id: &str, max_bytes: u64) -> Self {
+        StepLog {
+            path: log_path(pipeline_dir, step_id),
+            max_bytes,
+        }
+    }
+
+    /// Append a timestamped line.
+    pub fn line(&self, message: &str) {
+        self.append(format!("[{}] {}\n", state::now(), message).as_bytes());
+    }
+
+    /// Append a captured stream under a timestamped heading. Empty streams
+    /// are left out.
+    pub fn stream(&self, name: &str, bytes: &[u8]) {
+        if bytes.is_empty() {
+            return;
+        }
+        let mut entry = format!("[{}] {}:\n", state::now(), name).into_bytes();
+        entry.extend_from_slice(bytes);
+        if !bytes.ends_with(b"\n") {
+            entry.push(b'\n');
+        }
+        self.append(&entry);
+    }
+
+    fn append(&self, bytes: &[u8]) {
+        if let Err(e) = self.try_append(bytes) {
+            eprintln!(
+                "warning: failed to write step log '{}': {}",
+                self.path.display(),
+                e
+            );
+        }
+    }
+
+    fn try_append(&self, bytes: &[u8]) -> std::io::Result<()> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        // Keep one previous file so a step's recent history survives rotation
+        let len = fs::metadata(&self.path).map(|m| m.len()).unwrap_or(0);
+        if self.max_bytes > 0 && len > 0 && len + bytes.len() as u64 > self.max_bytes {
+            fs::rename(&self.path, rotated_path(&self.path))?;
+        }
+
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        file.write_all(bytes)
+    }
+}
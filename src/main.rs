@@ -1,18 +1,84 @@
-mod config;
-mod openclaw;
-mod pipeline;
-mod runner;
-mod state;
+use cronclaw::error::ErrorKind;
+use cronclaw::state::{State, StepStatus};
+use cronclaw::{config, engine, graph, history, logs, pipeline, runner, state};
 
-use clap::{Parser, Subcommand};
+use clap::{Parser, Subcommand, ValueEnum};
+use serde::Serialize;
+use std::collections::BTreeMap;
 use std::fs;
-use std::path::PathBuf;
+use std::io::{IsTerminal, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
 
-fn cronclaw_home() -> PathBuf {
+// Exit codes of `run`, so wrapper scripts can tell failures apart. Anything
+// else that goes wrong, such as an unreadable pipelines directory, exits 1.
+// A run stopped by SIGINT or SIGTERM exits 130, as a shell reports Ctrl-C.
+const EXIT_STEP_FAILED: i32 = 2;
+const EXIT_INVALID: i32 = 3;
+const EXIT_BUSY: i32 = 4;
+const EXIT_INTERRUPTED: i32 = 130;
+
+/// The cronclaw home directory: `--home`, then `$CRONCLAW_HOME`, then `~/.cronclaw`.
+fn cronclaw_home(flag: Option<PathBuf>) -> PathBuf {
+    if let Some(home) = flag {
+        return home;
+    }
+    if let Some(home) = std::env::var_os("CRONCLAW_HOME").filter(|h| !h.is_empty()) {
+        return PathBuf::from(home);
+    }
     let home = std::env::var("HOME").expect("HOME environment variable not set");
     PathBuf::from(home).join(".cronclaw")
 }
 
+/// `--step-timeout-override`: a duration such as "30s" or "5m", at least a second.
+fn parse_step_timeout(text: &str) -> Result<u64, String> {
+    match config::parse_duration(text)? {
+        0 => Err("timeout must be at least 1s".to_string()),
+        secs => Ok(secs),
+    }
+}
+
+/// `--var`: a variable's name and value, split at the first `=`.
+fn parse_var(text: &str) -> Result<(String, String), String> {
+    match text.split_once('=') {
+        Some((name, value)) if !name.is_empty() => Ok((name.to_string(), value.to_string())),
+        _ => Err(format!("expected NAME=VALUE, got '{}'", text)),
+    }
+}
+
+/// Load config.yaml, warning about anything in it that would be ignored, or
+/// exiting with `strict`. A file that doesn't parse always exits: running
+/// with the defaults in its place is never what was meant.
+fn load_config(home: &Path, strict: bool) -> config::Config {
+    let path = home.join("config.yaml");
+    let cfg = match config::load(&path) {
+        Ok(cfg) => cfg,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            std::process::exit(EXIT_INVALID);
+        }
+    };
+    let problems = config::problems(&path);
+    for problem in &problems {
+        let level = if strict { "error" } else { "warning" };
+        eprintln!("{}: {}: {}", level, path.display(), problem);
+    }
+    if strict && !problems.is_empty() {
+        std::process::exit(EXIT_INVALID);
+    }
+    cfg
+}
+
+/// Exit with a hint if `init` hasn't been run for `home`.
+fn ensure_initialised(home: &Path) {
+    if !home.exists() {
+        eprintln!("cronclaw not initialised. Run `cronclaw init` first.");
+        std::process::exit(1);
+    }
+}
+
 #[derive(Parser)]
 #[command(name = "cronclaw")]
 #[command(about = "Cron-driven pipeline orchestrator for agents and programs")]
@@ -22,6 +88,22 @@ struct Cli {
     #[arg(short, long, global = true)]
     verbose: bool,
 
+    /// Report run progress as JSON lines on stdout (status: a JSON document)
+    #[arg(long, global = true)]
+    json: bool,
+
+    /// Only report failures when running pipelines, for cron mail
+    #[arg(short, long, global = true, conflicts_with = "verbose")]
+    quiet: bool,
+
+    /// Treat unknown or invalid settings in config.yaml as errors
+    #[arg(long, global = true)]
+    strict: bool,
+
+    /// cronclaw directory to use instead of $CRONCLAW_HOME or ~/.cronclaw
+    #[arg(long, global = true, value_name = "PATH")]
+    home: Option<PathBuf>,
+
     #[command(subcommand)]
     command: Option<Commands>,
 }
@@ -29,107 +111,1434 @@ struct Cli {
 #[derive(Subcommand)]
 enum Commands {
     /// Initialise the cronclaw directory structure
-    Init,
+    Init {
+        /// Don't create the example pipeline
+        #[arg(long)]
+        no_example: bool,
+        /// Fill in missing files in an existing directory, keeping pipelines and config
+        #[arg(long)]
+        force: bool,
+    },
     /// Advance all pipelines by one tick
-    Run,
+    Run {
+        /// Only advance this pipeline
+        pipeline: Option<String>,
+        /// Show the step that would run next, without running it or touching state
+        #[arg(long)]
+        dry_run: bool,
+        /// Only pipelines with this tag (repeat for any of several)
+        #[arg(long = "tag", value_name = "TAG", conflicts_with = "pipeline")]
+        tags: Vec<String>,
+        /// Timeout for the steps this run starts, e.g. 30s, in place of their own
+        #[arg(long, value_name = "DURATION", value_parser = parse_step_timeout)]
+        step_timeout_override: Option<u64>,
+        /// Value for one of the pipeline's vars (repeat for several)
+        #[arg(long = "var", value_name = "NAME=VALUE", value_parser = parse_var, requires = "pipeline")]
+        vars: Vec<(String, String)>,
+    },
+    /// Stay resident, ticking all pipelines every tick_interval_secs
+    Daemon,
     /// Reset a pipeline by removing its state file
     Reset {
         /// Name of the pipeline to reset
+        #[arg(required_unless_present = "all", conflicts_with = "all")]
+        pipeline: Option<String>,
+        /// Reset every pipeline
+        #[arg(long)]
+        all: bool,
+        /// Only reset pipelines (or the step) that have failed
+        #[arg(long)]
+        failed_only: bool,
+        /// Put just this step back to pending, keeping the rest of the state
+        #[arg(long, requires = "pipeline")]
+        step: Option<String>,
+        /// Rewind to this step: it and every later step go back to pending
+        #[arg(long, requires = "pipeline", conflicts_with_all = ["step", "failed_only"])]
+        from: Option<String>,
+    },
+    /// Give a failed step a fresh retry budget so it runs on the next tick
+    Retry {
+        /// Name of the pipeline
+        pipeline: String,
+        /// Id of the step to retry
+        step: String,
+    },
+    /// Stop a pipeline advancing, keeping its progress, until it is resumed
+    Pause {
+        /// Name of the pipeline
+        pipeline: String,
+    },
+    /// Let a paused pipeline advance again, from where it left off
+    Resume {
+        /// Name of the pipeline
+        pipeline: String,
+    },
+    /// Compare a pipeline's steps with its state, without running it
+    Check {
+        /// Name of the pipeline
+        pipeline: String,
+    },
+    /// Check pipeline definitions for errors without running them
+    Validate {
+        /// A pipeline.yaml (or pipeline directory) to check instead of the installed pipelines
+        path: Option<PathBuf>,
+    },
+    /// Show the state of each step for every pipeline
+    Status {
+        /// Only show this pipeline
+        pipeline: Option<String>,
+        /// Only pipelines with this tag (repeat for any of several)
+        #[arg(long = "tag", value_name = "TAG", conflicts_with = "pipeline")]
+        tags: Vec<String>,
+    },
+    /// List pipelines with their step count and overall state
+    List {
+        /// Only pipelines with this tag (repeat for any of several)
+        #[arg(long = "tag", value_name = "TAG")]
+        tags: Vec<String>,
+    },
+    /// Print a pipeline's step dependency graph
+    Graph {
+        /// Name of the pipeline
+        pipeline: String,
+        /// Output format
+        #[arg(long, value_enum, default_value_t = GraphFormat::Dot)]
+        format: GraphFormat,
+    },
+    /// Describe every step of a pipeline, with templates resolved, without running it
+    Explain {
+        /// Name of the pipeline
         pipeline: String,
     },
+    /// Show everything about one step: what it runs, its state and the files it produced
+    Show {
+        /// Name of the pipeline
+        pipeline: String,
+        /// Id of the step
+        step: String,
+    },
+    /// Show or change settings in config.yaml
+    Config {
+        #[command(subcommand)]
+        action: ConfigAction,
+    },
+    /// Print the step logs of a pipeline
+    Logs {
+        /// Name of the pipeline
+        pipeline: String,
+        /// Only show this step's log
+        #[arg(long)]
+        step: Option<String>,
+        /// Keep printing new log output as it is written
+        #[arg(short, long)]
+        follow: bool,
+    },
+    /// Watch a pipeline's steps change state until it completes or fails
+    Tail {
+        /// Name of the pipeline
+        pipeline: String,
+        /// Seconds between looks at state.json
+        #[arg(long, default_value_t = 2, value_name = "SECS")]
+        interval: u64,
+    },
+    /// Summarise a pipeline's completed runs
+    History {
+        /// Name of the pipeline
+        pipeline: String,
+        /// Only runs that finished within this long, e.g. 12h or 7d
+        #[arg(long, value_name = "AGE")]
+        since: Option<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum ConfigAction {
+    /// Print a setting's effective value, or every setting
+    Get {
+        /// Name of the setting, e.g. timeout
+        key: Option<String>,
+    },
+    /// Change a setting in config.yaml, keeping its comments
+    Set {
+        /// Name of the setting, e.g. timeout
+        key: String,
+        /// New value as YAML, e.g. 600, bash or null
+        value: String,
+    },
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum GraphFormat {
+    /// Graphviz DOT, e.g. for `dot -Tpng`
+    Dot,
+    /// Mermaid flowchart
+    Mermaid,
 }
 
-fn cmd_init() {
-    let home = cronclaw_home();
+/// config.yaml written by `init`: every setting, commented out at its default.
+const CONFIG_TEMPLATE: &str = "\
+# cronclaw configuration
+# Every setting is optional; uncomment one to change it from its default.
+
+# Default step timeout in seconds (or e.g. 30m), and a cap on per-step overrides
+# timeout: 300
+# max_timeout: 86400
+
+# Shell for bash steps (must accept -c)
+# shell: sh
+
+# Time between SIGTERM and SIGKILL when a step times out
+# kill_grace_secs: 5
+
+# Reset steps left running by a crashed run back to pending
+# reclaim_stale: false
+
+# Ready steps of a pipeline that one tick may run at once
+# max_parallel: 1
+
+# Pipelines that one run (or daemon tick) may tick at once
+# max_concurrent_pipelines: 1
+
+# Bytes of each step's stdout and stderr to keep (0 keeps everything)
+# max_output_bytes: 10485760
+
+# Size at which a step's log in logs/ is rotated (0 never rotates)
+# max_log_bytes: 1048576
+
+# Seconds between ticks of `cronclaw daemon`
+# tick_interval_secs: 60
+
+# A ready step whose declared inputs are missing: wait (stay pending) or fail
+# missing_inputs: wait
+
+# Fail a step before it starts if its workspace has fewer bytes free than
+# this, or can't be written to (unset: no check)
+# min_free_bytes: 1073741824
+
+# Quick retries for an agent exiting non-zero, within the step's timeout,
+# waiting agent_backoff_base_secs before the first and doubling after
+# agent_retries: 0
+# agent_backoff_base_secs: 5
+
+# Shell commands run when a pipeline completes or a step fails for good,
+# e.g. to post an alert. See CRONCLAW_* variables in the README.
+# on_success: echo \"$CRONCLAW_PIPELINE done\"
+# on_failure: echo \"$CRONCLAW_PIPELINE: $CRONCLAW_STEP failed\" >&2
+";
+
+/// pipelines/example/pipeline.yaml written by `init`.
+const EXAMPLE_PIPELINE: &str = "\
+# An example pipeline. Each `cronclaw run` advances it by one step.
+# Try `cronclaw run example --dry-run` to see what would run next, then
+# `cronclaw run example` and `cronclaw status example`.
+# Delete this directory once you don't need it.
+version: 1
+workspace: workspace
+
+steps:
+  # Collect some data into the workspace. It is written to a .tmp file and
+  # only moved into place once the step succeeds.
+  - id: gather
+    type: bash
+    bash: |
+      date > report.txt.tmp
+      uname -a >> report.txt.tmp
+    outputs:
+      - name: report
+        path: report.txt
+        tmp: report.txt.tmp
+
+  # Hand it to an OpenClaw agent, or skip this step if openclaw isn't installed.
+  - id: summarise
+    type: agent
+    agent: main
+    when: command -v openclaw
+    prompt: |
+      Summarise this system report in one sentence:
+      {{ file:report.txt }}
+    output: summary.md
+";
+
+fn cmd_init(home: &Path, no_example: bool, force: bool) {
     let pipelines_dir = home.join("pipelines");
     let config_path = home.join("config.yaml");
 
-    if home.exists() {
-        eprintln!("cronclaw directory already exists at {}", home.display());
+    if home.exists() && !force {
+        eprintln!(
+            "cronclaw directory already exists at {} (use --force to fill in missing files)",
+            home.display()
+        );
         std::process::exit(1);
     }
 
-    fs::create_dir_all(&pipelines_dir).expect("failed to create pipelines directory");
+    // Only ever create what's missing: existing pipelines, state and config stay as they are
+    let mut created = Vec::new();
+    if !pipelines_dir.is_dir() {
+        fs::create_dir_all(&pipelines_dir).expect("failed to create pipelines directory");
+        created.push("pipelines/".to_string());
+    }
+    if !config_path.exists() {
+        fs::write(&config_path, CONFIG_TEMPLATE).expect("failed to write config.yaml");
+        created.push("config.yaml".to_string());
+    }
 
-    fs::write(
-        &config_path,
-        "# cronclaw configuration\n# timeout: 300  # default step timeout in seconds\n",
-    )
-    .expect("failed to write config.yaml");
+    let no_pipelines = fs::read_dir(&pipelines_dir)
+        .map(|mut entries| entries.next().is_none())
+        .unwrap_or(true);
+    let example = !no_example && no_pipelines;
+    if example {
+        let example_dir = pipelines_dir.join("example");
+        fs::create_dir_all(&example_dir).expect("failed to create example pipeline directory");
+        fs::write(example_dir.join("pipeline.yaml"), EXAMPLE_PIPELINE)
+            .expect("failed to write example pipeline");
+        created.push("pipelines/example/".to_string());
+    }
 
-    println!("Initialised cronclaw at {}", home.display());
+    if created.is_empty() {
+        println!("cronclaw at {} is already initialised", home.display());
+        return;
+    }
+    println!(
+        "Initialised cronclaw at {} (created {})",
+        home.display(),
+        created.join(", ")
+    );
+    if example {
+        println!("Try `cronclaw run example --dry-run` to see the example pipeline.");
+    }
 }
 
-fn cmd_run(verbose: bool) {
-    let home = cronclaw_home();
-    if !home.exists() {
-        eprintln!("cronclaw not initialised. Run `cronclaw init` first.");
+fn cmd_run(
+    home: &Path,
+    pipeline: Option<&str>,
+    tags: &[String],
+    opts: &runner::RunOptions,
+    strict: bool,
+) {
+    ensure_initialised(home);
+
+    let cfg = load_config(home, strict);
+    install_stop_handler();
+
+    let dirs = selected_pipeline_dirs(home, pipeline, tags);
+    let report = engine::run_pipelines(&dirs, &cfg, opts);
+
+    if dirs.is_empty() && opts.verbose && !opts.json {
+        println!("No pipelines found.");
+    }
+
+    if !report.is_ok() {
+        eprintln!();
+        // A tick running steps in parallel can report several failures
+        for e in report.failures().filter_map(|p| p.error.as_ref()) {
+            for line in e.to_string().lines() {
+                eprintln!("error: {}", line);
+            }
+        }
+    }
+    if STOP.load(Ordering::SeqCst) {
+        std::process::exit(EXIT_INTERRUPTED);
+    }
+    std::process::exit(run_exit_code(&report));
+}
+
+/// `run`'s exit code: a failed step outranks a pipeline that couldn't be
+/// loaded, which outranks any other error, and a pipeline left alone because
+/// another run holds it only counts if nothing went wrong.
+fn run_exit_code(report: &engine::RunReport) -> i32 {
+    let kinds: Vec<ErrorKind> = report
+        .failures()
+        .filter_map(|p| p.error.as_ref().map(|e| e.kind()))
+        .collect();
+    if kinds.contains(&ErrorKind::Step) {
+        EXIT_STEP_FAILED
+    } else if kinds.contains(&ErrorKind::Invalid) {
+        EXIT_INVALID
+    } else if !kinds.is_empty() {
+        1
+    } else if report.pipelines.iter().any(|p| p.busy) {
+        EXIT_BUSY
+    } else {
+        0
+    }
+}
+
+/// Set by SIGTERM/SIGINT: `cronclaw run` stops the steps it is running,
+/// and `cronclaw daemon` stops after the current tick.
+static STOP: AtomicBool = AtomicBool::new(false);
+
+extern "C" fn request_stop(_: libc::c_int) {
+    STOP.store(true, Ordering::SeqCst);
+}
+
+fn install_stop_handler() {
+    let handler = request_stop as extern "C" fn(libc::c_int) as libc::sighandler_t;
+    // SAFETY: `request_stop` is async-signal-safe, as it only stores to an
+    // atomic, and signal(2) has no other preconditions.
+    unsafe {
+        libc::signal(libc::SIGTERM, handler);
+        libc::signal(libc::SIGINT, handler);
+    }
+}
+
+fn cmd_daemon(home: &Path, opts: &runner::RunOptions, strict: bool) {
+    ensure_initialised(home);
+
+    let cfg = load_config(home, strict);
+    install_stop_handler();
+
+    if opts.verbose && !opts.json {
+        println!(
+            "cronclaw daemon ticking {} every {}s",
+            home.display(),
+            cfg.tick_interval_secs.max(1)
+        );
+    }
+    engine::daemon(home, &cfg, opts, &STOP, |report| {
+        // Failures are reported and the daemon carries on with the next tick
+        let errors = match report {
+            Ok(report) => report
+                .failures()
+                .filter_map(|p| p.error.as_ref())
+                .flat_map(|e| e.to_string().lines().map(String::from).collect::<Vec<_>>())
+                .collect(),
+            Err(e) => vec![e.to_string()],
+        };
+        for e in errors {
+            eprintln!("error: {}", e);
+        }
+    });
+    if opts.verbose && !opts.json {
+        println!("cronclaw daemon stopped");
+    }
+}
+
+/// Pipeline directories (those containing a pipeline.yaml), sorted by name.
+fn pipeline_dirs(home: &Path) -> Vec<PathBuf> {
+    engine::pipeline_dirs(home).unwrap_or_else(|e| {
+        eprintln!("{}", e);
         std::process::exit(1);
+    })
+}
+
+/// The named pipeline's directory, or every pipeline's with any of `tags`
+/// (all of them without tags).
+fn selected_pipeline_dirs(home: &Path, pipeline: Option<&str>, tags: &[String]) -> Vec<PathBuf> {
+    match pipeline {
+        Some(name) => vec![named_pipeline_dir(home, name)],
+        None => pipeline_dirs(home)
+            .into_iter()
+            .filter(|dir| engine::has_any_tag(dir, tags))
+            .collect(),
     }
+}
 
-    let cfg = config::load(&home.join("config.yaml"));
+/// The directory of the pipeline called `name`, exiting with the list of
+/// available pipelines if there is no such pipeline.
+fn named_pipeline_dir(home: &Path, name: &str) -> PathBuf {
+    let dir = home.join("pipelines").join(name);
+    if dir.join("pipeline.yaml").exists() {
+        return dir;
+    }
 
-    let pipelines_dir = home.join("pipelines");
-    let entries = fs::read_dir(&pipelines_dir).expect("failed to read pipelines directory");
+    let available: Vec<String> = pipeline_dirs(home)
+        .iter()
+        .map(|d| d.file_name().unwrap().to_string_lossy().to_string())
+        .collect();
+    if available.is_empty() {
+        eprintln!("No pipeline named '{}' (no pipelines found).", name);
+    } else {
+        eprintln!(
+            "No pipeline named '{}'. Available: {}",
+            name,
+            available.join(", ")
+        );
+    }
+    std::process::exit(1);
+}
 
-    let mut found = false;
-    let mut errors = Vec::new();
+/// Whether to colour status words: only on a terminal, unless `NO_COLOR`
+/// turns it off or `CLICOLOR_FORCE` turns it on regardless.
+fn use_color() -> bool {
+    static USE_COLOR: OnceLock<bool> = OnceLock::new();
+    *USE_COLOR.get_or_init(|| {
+        let var = |name: &str| std::env::var(name).unwrap_or_default();
+        if !var("NO_COLOR").is_empty() {
+            return false;
+        }
+        !matches!(var("CLICOLOR_FORCE").as_str(), "" | "0") || std::io::stdout().is_terminal()
+    })
+}
 
-    for entry in entries {
-        let entry = entry.expect("failed to read directory entry");
-        let path = entry.path();
-        if !path.is_dir() {
-            continue;
+/// `text` coloured after the status it shows: green once done, yellow while
+/// running, red when failed or stuck. Pad `text` first, as escape codes
+/// would count towards the width.
+fn paint(status: &str, text: &str) -> String {
+    let code = match status {
+        "completed" => "32",
+        "running" => "33",
+        "failed" | "blocked" | "invalid" => "31",
+        _ => return text.to_string(),
+    };
+    if use_color() {
+        format!("\x1b[{}m{}\x1b[0m", code, text)
+    } else {
+        text.to_string()
+    }
+}
+
+/// One-word summary of a pipeline's state.
+fn overall_status(state: &State) -> &'static str {
+    let statuses: Vec<&StepStatus> = state.steps.values().map(|s| &s.status).collect();
+    if statuses.contains(&&StepStatus::Failed) {
+        "failed"
+    } else if statuses.contains(&&StepStatus::Running) {
+        "running"
+    } else if statuses.iter().all(|s| s.is_done()) {
+        "completed"
+    } else if statuses.iter().all(|s| **s == StepStatus::Pending) {
+        "not started"
+    } else {
+        "in progress"
+    }
+}
+
+/// ", paused" for a paused pipeline, to follow its overall state.
+fn paused_suffix(dir: &Path) -> &'static str {
+    if runner::is_paused(dir) {
+        ", paused"
+    } else {
+        ""
+    }
+}
+
+/// Summary of one pipeline directory: its step count and overall state, plus
+/// its workspace and next step in verbose mode. Errors if it can't be loaded.
+fn describe_pipeline(dir: &Path, verbose: bool) -> Result<Vec<String>, String> {
+    let pipeline_file = dir.join("pipeline.yaml");
+    if !pipeline_file.exists() {
+        return Err("missing pipeline.yaml".to_string());
+    }
+    let pipeline = pipeline::load(&pipeline_file).map_err(|e| {
+        let prefix = format!("{}: ", pipeline_file.display());
+        let e = e.to_string();
+        e.strip_prefix(&prefix).map(str::to_string).unwrap_or(e)
+    })?;
+
+    // A state file that doesn't match the pipeline stops it until it's reset
+    let status = match state::load(&dir.join("state.json")) {
+        Ok(Some(state)) if state.drift(&pipeline).is_empty() => Ok(state),
+        Ok(Some(_)) => Err("blocked (state doesn't match pipeline.yaml)"),
+        Ok(None) => Ok(State::from_pipeline(&pipeline)),
+        Err(_) => Err("blocked (unreadable state.json)"),
+    };
+
+    let steps = format!(
+        "{} step{}",
+        pipeline.steps.len(),
+        if pipeline.steps.len() == 1 { "" } else { "s" }
+    );
+    let mut lines = vec![match &status {
+        Ok(state) => {
+            let overall = overall_status(state);
+            format!(
+                "{:<8}  {}{}",
+                steps,
+                paint(overall, overall),
+                paused_suffix(dir)
+            )
+        }
+        Err(blocked) => format!("{:<8}  {}", steps, paint("blocked", blocked)),
+    }];
+    if verbose {
+        lines.push(format!(
+            "workspace: {}",
+            runner::workspace_dir(dir, &pipeline, status.as_ref().ok()).display()
+        ));
+        if !pipeline.tags.is_empty() {
+            lines.push(format!("tags: {}", pipeline.tags.join(", ")));
+        }
+        if let Ok(state) = &status {
+            let next = runner::next_step(&pipeline, state).map_or("-", |s| s.id.as_str());
+            lines.push(format!("next: {}", next));
+        }
+    }
+    Ok(lines)
+}
+
+fn cmd_list(home: &Path, tags: &[String], verbose: bool) {
+    ensure_initialised(home);
+    let entries = fs::read_dir(home.join("pipelines")).expect("failed to read pipelines directory");
+    let mut dirs: Vec<PathBuf> = entries
+        .map(|entry| entry.expect("failed to read directory entry").path())
+        .filter(|path| path.is_dir() && engine::has_any_tag(path, tags))
+        .collect();
+    dirs.sort();
+
+    if dirs.is_empty() {
+        println!("No pipelines found.");
+        return;
+    }
+
+    let width = dirs
+        .iter()
+        .map(|d| d.file_name().unwrap().len())
+        .max()
+        .unwrap_or(0);
+    for dir in &dirs {
+        let name = dir.file_name().unwrap().to_string_lossy();
+        match describe_pipeline(dir, verbose) {
+            Ok(lines) => {
+                println!("{:<width$}  {}", name, lines[0]);
+                for line in &lines[1..] {
+                    println!("{:<width$}    {}", "", line);
+                }
+            }
+            Err(e) => println!("{:<width$}  {}: {}", name, paint("invalid", "invalid"), e),
         }
+    }
+}
 
-        let pipeline_file = path.join("pipeline.yaml");
-        if !pipeline_file.exists() {
-            continue;
+/// A number of seconds as a rough age, e.g. "45s", "3m", "2h" or "5d".
+fn format_age(secs: u64) -> String {
+    match secs {
+        0..60 => format!("{}s", secs),
+        60..3600 => format!("{}m", secs / 60),
+        3600..86400 => format!("{}h", secs / 3600),
+        _ => format!("{}d", secs / 86400),
+    }
+}
+
+/// Print a pipeline's step table. Returns true if any step has failed.
+fn print_status(pipeline_dir: &Path, verbose: bool) -> Result<bool, String> {
+    let name = pipeline_dir.file_name().unwrap().to_string_lossy();
+    let pipeline =
+        pipeline::load(&pipeline_dir.join("pipeline.yaml")).map_err(|e| e.to_string())?;
+    let state = state::load(&pipeline_dir.join("state.json"))
+        .map_err(|e| format!("[{}] {}", name, e))?
+        .unwrap_or_else(|| State::from_pipeline(&pipeline));
+
+    let completed = state
+        .steps
+        .values()
+        .filter(|s| s.status == StepStatus::Completed)
+        .count();
+    let overall = overall_status(&state);
+    println!(
+        "{}: {}{} ({}/{} steps completed)",
+        name,
+        paint(overall, overall),
+        paused_suffix(pipeline_dir),
+        completed,
+        pipeline.steps.len()
+    );
+    if verbose {
+        println!(
+            "  workspace: {}",
+            runner::workspace_dir(pipeline_dir, &pipeline, Some(&state)).display()
+        );
+        match state.secs_since_last_run() {
+            Some(secs) => println!(
+                "  ticked {} times, last {} ago",
+                state.tick_count,
+                format_age(secs)
+            ),
+            None => println!("  never ticked"),
+        }
+    }
+
+    let width = pipeline
+        .steps
+        .iter()
+        .chain(pipeline.cleanup_steps())
+        .map(|s| s.id.len())
+        .max()
+        .unwrap_or(0);
+    for step in &pipeline.steps {
+        let step_state = state.steps.get(&step.id);
+        let status = step_state.map(|s| s.status.name()).unwrap_or("unknown");
+        let status = paint(status, &format!("{:<9}", status));
+        let mut details: Vec<String> = step_state
+            .and_then(|s| s.duration_secs)
+            .map(|d| format!("{}s", d))
+            .into_iter()
+            .collect();
+        if step.retries > 0 {
+            let budget = step.retries + 1;
+            let used = step_state.map_or(0, |s| s.attempts).min(budget);
+            details.push(format!("{} of {} attempts left", budget - used, budget));
+        }
+        let details = details.join("  ");
+        if verbose {
+            println!(
+                "  {:<width$}  {}  {:<5}  {}",
+                step.id,
+                status,
+                step.step_type.name(),
+                details
+            );
+        } else {
+            println!("  {:<width$}  {}  {}", step.id, status, details);
         }
+        if verbose && let Some(description) = &step.description {
+            println!("  {:<width$}  {}", "", description.trim());
+        }
+        if let Some(message) = step_state.and_then(|s| s.error_message.as_deref()) {
+            println!("  {:<width$}  error: {}", "", message);
+        }
+    }
 
-        found = true;
+    // Cleanup steps, once they have run after a failure
+    let cleanup: Vec<_> = pipeline
+        .cleanup_steps()
+        .iter()
+        .filter_map(|step| Some((step, state.cleanup.get(&step.id)?)))
+        .collect();
+    if !cleanup.is_empty() {
+        println!("  cleanup:");
+    }
+    for (step, step_state) in cleanup {
+        let status = step_state.status.name();
+        println!("  {:<width$}  {}", step.id, paint(status, status));
+        if let Some(message) = &step_state.error_message {
+            println!("  {:<width$}  error: {}", "", message);
+        }
+    }
 
-        if let Err(e) = runner::run_pipeline(&path, &cfg, verbose) {
-            errors.push(e);
+    Ok(state.steps.values().any(|s| s.status == StepStatus::Failed))
+}
+
+fn cmd_status(home: &Path, pipeline: Option<&str>, tags: &[String], verbose: bool, json: bool) {
+    ensure_initialised(home);
+
+    let dirs = selected_pipeline_dirs(home, pipeline, tags);
+
+    if json {
+        let reports: Vec<PipelineReport> = dirs.iter().map(|dir| status_report(dir)).collect();
+        println!("{}", serde_json::to_string_pretty(&reports).unwrap());
+        let failed = reports
+            .iter()
+            .any(|r| r.error.is_some() || r.status == "failed");
+        if failed {
+            std::process::exit(1);
         }
+        return;
     }
 
-    if !found && verbose {
+    if dirs.is_empty() {
         println!("No pipelines found.");
+        return;
     }
 
-    if !errors.is_empty() {
-        eprintln!();
-        for e in &errors {
+    let mut any_failed = false;
+    for (i, dir) in dirs.iter().enumerate() {
+        if i > 0 {
+            println!();
+        }
+        match print_status(dir, verbose) {
+            Ok(failed) => any_failed |= failed,
+            Err(e) => {
+                eprintln!("error: {}", e);
+                any_failed = true;
+            }
+        }
+    }
+
+    if any_failed {
+        std::process::exit(1);
+    }
+}
+
+/// One pipeline in `cronclaw status --json`.
+#[derive(Serialize)]
+struct PipelineReport {
+    name: String,
+    /// Overall state as `cronclaw status` words it, or "unknown" if the
+    /// pipeline couldn't be loaded
+    status: &'static str,
+    paused: bool,
+    /// Why the pipeline couldn't be loaded
+    error: Option<String>,
+    /// In pipeline order
+    steps: Vec<StepReport>,
+}
+
+/// One step in `cronclaw status --json`.
+#[derive(Serialize)]
+struct StepReport {
+    id: String,
+    #[serde(rename = "type")]
+    step_type: &'static str,
+    status: &'static str,
+    attempts: u32,
+    started_at: Option<String>,
+    finished_at: Option<String>,
+    duration_secs: Option<u64>,
+    exit_code: Option<i32>,
+    error: Option<String>,
+}
+
+/// The state of one pipeline directory for `cronclaw status --json`. A
+/// pipeline that can't be loaded is reported with its error and no steps.
+fn status_report(pipeline_dir: &Path) -> PipelineReport {
+    let name = pipeline_dir
+        .file_name()
+        .unwrap()
+        .to_string_lossy()
+        .to_string();
+    let paused = runner::is_paused(pipeline_dir);
+    let loaded = pipeline::load(&pipeline_dir.join("pipeline.yaml"))
+        .map_err(|e| e.to_string())
+        .and_then(|pipeline| {
+            let state = state::load(&pipeline_dir.join("state.json"))
+                .map_err(|e| format!("[{}] {}", name, e))?
+                .unwrap_or_else(|| State::from_pipeline(&pipeline));
+            Ok((pipeline, state))
+        });
+    let (pipeline, state) = match loaded {
+        Ok(loaded) => loaded,
+        Err(e) => {
+            return PipelineReport {
+                name,
+                status: "unknown",
+                paused,
+                error: Some(e),
+                steps: Vec::new(),
+            };
+        }
+    };
+
+    let steps = pipeline
+        .steps
+        .iter()
+        .map(|step| {
+            let step_state = state.steps.get(&step.id);
+            StepReport {
+                id: step.id.clone(),
+                step_type: step.step_type.name(),
+                status: step_state.map(|s| s.status.name()).unwrap_or("unknown"),
+                attempts: step_state.map_or(0, |s| s.attempts),
+                started_at: step_state.and_then(|s| s.started_at.clone()),
+                finished_at: step_state.and_then(|s| s.finished_at.clone()),
+                duration_secs: step_state.and_then(|s| s.duration_secs),
+                exit_code: step_state.and_then(|s| s.exit_code),
+                error: step_state.and_then(|s| s.error_message.clone()),
+            }
+        })
+        .collect();
+    PipelineReport {
+        name,
+        status: overall_status(&state),
+        paused,
+        error: None,
+        steps,
+    }
+}
+
+/// Load and check one pipeline file, returning every problem found.
+/// Each problem is prefixed with the file path.
+fn validate_file(path: &Path) -> Vec<String> {
+    match pipeline::load(path) {
+        Ok(_) => Vec::new(),
+        Err(e) => e.to_string().lines().map(str::to_string).collect(),
+    }
+}
+
+fn cmd_validate(home: &Path, path: Option<&Path>) {
+    let files = match path {
+        Some(p) if p.is_dir() => vec![p.join("pipeline.yaml")],
+        Some(p) => vec![p.to_path_buf()],
+        None => {
+            ensure_initialised(home);
+            pipeline_dirs(home)
+        }
+        .iter()
+        .map(|dir| dir.join("pipeline.yaml"))
+        .collect(),
+    };
+
+    if files.is_empty() {
+        println!("No pipelines found.");
+        return;
+    }
+
+    let mut failed = false;
+    for file in &files {
+        let problems = validate_file(file);
+        if problems.is_empty() {
+            println!("OK {}", file.display());
+        } else {
+            failed = true;
+            for problem in &problems {
+                eprintln!("error: {}", problem);
+            }
+        }
+    }
+
+    if failed {
+        std::process::exit(EXIT_INVALID);
+    }
+}
+
+/// Print `path` from byte `offset` on, returning the new end offset.
+fn print_log_from(path: &Path, offset: u64) -> u64 {
+    let Ok(mut file) = fs::File::open(path) else {
+        return offset;
+    };
+    let mut buf = Vec::new();
+    if file.seek(SeekFrom::Start(offset)).is_err() || file.read_to_end(&mut buf).is_err() {
+        return offset;
+    }
+    print!("{}", String::from_utf8_lossy(&buf));
+    offset + buf.len() as u64
+}
+
+fn cmd_history(home: &Path, pipeline: &str, since: Option<&str>, json: bool) {
+    ensure_initialised(home);
+    let dir = named_pipeline_dir(home, pipeline);
+    let max_age = match since.map(history::parse_age).transpose() {
+        Ok(max_age) => max_age,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            std::process::exit(1);
+        }
+    };
+    let runs = match history::load(&dir) {
+        Ok(runs) => runs,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            std::process::exit(1);
+        }
+    };
+    let runs: Vec<_> = runs
+        .into_iter()
+        .filter(|run| match max_age {
+            Some(max_age) => run
+                .secs_since_finished()
+                .is_some_and(|secs| secs <= max_age),
+            None => true,
+        })
+        .collect();
+
+    if json {
+        for run in &runs {
+            println!("{}", serde_json::to_string(run).unwrap());
+        }
+        return;
+    }
+    if runs.is_empty() {
+        println!("No completed runs of '{}'.", pipeline);
+        return;
+    }
+
+    for run in &runs {
+        let duration = run
+            .duration_secs
+            .map(|d| format!("{}s", d))
+            .unwrap_or_else(|| "?".to_string());
+        let steps: Vec<String> = run
+            .steps
+            .iter()
+            .map(|step| match (&step.status, step.duration_secs) {
+                (StepStatus::Completed, Some(d)) => format!("{} {}s", step.id, d),
+                (status, _) => format!("{} {}", step.id, status.name()),
+            })
+            .collect();
+        println!("{}  {:>6}  {}", run.finished_at, duration, steps.join(", "));
+    }
+
+    let timed: Vec<u64> = runs.iter().filter_map(|run| run.duration_secs).collect();
+    let average = match timed.len() {
+        0 => String::new(),
+        n => format!(", {}s on average", timed.iter().sum::<u64>() / n as u64),
+    };
+    println!(
+        "{} run{}{}",
+        runs.len(),
+        if runs.len() == 1 { "" } else { "s" },
+        average
+    );
+}
+
+/// Reprint a pipeline's step table whenever a step changes state, noting
+/// each change, until every step is done or one has failed. Only reads state.
+fn cmd_tail(home: &Path, pipeline: &str, interval_secs: u64) {
+    ensure_initialised(home);
+    let dir = named_pipeline_dir(home, pipeline);
+
+    let mut last: Option<BTreeMap<String, StepStatus>> = None;
+    loop {
+        let state = pipeline::load(&dir.join("pipeline.yaml")).and_then(|p| {
+            Ok(state::load(&dir.join("state.json"))?.unwrap_or_else(|| State::from_pipeline(&p)))
+        });
+        let state = match state {
+            Ok(state) => state,
+            Err(e) => {
+                eprintln!("error: {}", e);
+                std::process::exit(1);
+            }
+        };
+
+        let statuses: BTreeMap<String, StepStatus> = state
+            .steps
+            .iter()
+            .map(|(id, s)| (id.clone(), s.status.clone()))
+            .collect();
+        if last.as_ref() != Some(&statuses) {
+            if let Some(previous) = &last {
+                let now = chrono::Local::now().format("%H:%M:%S");
+                for (id, status) in &statuses {
+                    let before = previous.get(id).map_or("unknown", |s| s.name());
+                    if before != status.name() {
+                        println!(
+                            "{}  {}: {} -> {}",
+                            now,
+                            id,
+                            before,
+                            paint(status.name(), status.name())
+                        );
+                    }
+                }
+                println!();
+            }
+            if let Err(e) = print_status(&dir, false) {
+                eprintln!("error: {}", e);
+                std::process::exit(1);
+            }
+            last = Some(statuses);
+        }
+
+        match overall_status(&state) {
+            "completed" => return,
+            "failed" => std::process::exit(1),
+            _ => std::thread::sleep(Duration::from_secs(interval_secs.max(1))),
+        }
+    }
+}
+
+fn cmd_logs(home: &Path, pipeline: &str, step: Option<&str>, follow: bool) {
+    ensure_initialised(home);
+    let dir = named_pipeline_dir(home, pipeline);
+    let p = match pipeline::load(&dir.join("pipeline.yaml")) {
+        Ok(p) => p,
+        Err(e) => {
             eprintln!("error: {}", e);
+            std::process::exit(1);
+        }
+    };
+
+    let step_ids: Vec<&str> = match step {
+        Some(id) if p.steps.iter().any(|s| s.id == id) => vec![id],
+        Some(id) => {
+            eprintln!("No step named '{}' in pipeline '{}'.", id, pipeline);
+            std::process::exit(1);
+        }
+        None => p.steps.iter().map(|s| s.id.as_str()).collect(),
+    };
+    let paths: Vec<PathBuf> = step_ids.iter().map(|id| logs::log_path(&dir, id)).collect();
+
+    // With several steps, label each one's output like `tail` does
+    let labelled = step_ids.len() > 1;
+    let mut offsets = vec![0; paths.len()];
+    let mut last_printed = None;
+    for (i, path) in paths.iter().enumerate() {
+        let rotated = logs::rotated_path(path);
+        if !path.exists() && !rotated.exists() {
+            continue;
+        }
+        if labelled {
+            if last_printed.is_some() {
+                println!();
+            }
+            println!("==> {} <==", step_ids[i]);
+        }
+        print_log_from(&rotated, 0);
+        offsets[i] = print_log_from(path, 0);
+        last_printed = Some(i);
+    }
+
+    if !follow {
+        if last_printed.is_none() {
+            println!("No logs for pipeline '{}' yet.", pipeline);
         }
+        return;
+    }
+
+    loop {
+        std::thread::sleep(Duration::from_secs(1));
+        for (i, path) in paths.iter().enumerate() {
+            let len = fs::metadata(path).map(|m| m.len()).unwrap_or(0);
+            if len < offsets[i] {
+                // Rotated since we last looked
+                offsets[i] = 0;
+            }
+            if len == offsets[i] {
+                continue;
+            }
+            if labelled && last_printed != Some(i) {
+                println!("\n==> {} <==", step_ids[i]);
+                last_printed = Some(i);
+            }
+            offsets[i] = print_log_from(path, offsets[i]);
+        }
+    }
+}
+
+fn cmd_explain(home: &Path, pipeline: &str, strict: bool) {
+    ensure_initialised(home);
+    let dir = named_pipeline_dir(home, pipeline);
+    let cfg = load_config(home, strict);
+    if let Err(e) = runner::explain(&dir, &cfg) {
+        eprintln!("error: {}", e);
+        std::process::exit(1);
+    }
+}
+
+fn cmd_show(home: &Path, pipeline: &str, step: &str, strict: bool) {
+    ensure_initialised(home);
+    let dir = named_pipeline_dir(home, pipeline);
+    load_pipeline_with_step(&dir, step);
+    let cfg = load_config(home, strict);
+    if let Err(e) = runner::show(&dir, step, &cfg) {
+        eprintln!("error: {}", e);
+        std::process::exit(1);
+    }
+}
+
+fn cmd_config(home: &Path, action: ConfigAction, strict: bool) {
+    ensure_initialised(home);
+    let path = home.join("config.yaml");
+    let result = match action {
+        ConfigAction::Get { key: Some(key) } => {
+            config::get(&load_config(home, strict), &key).map(|value| println!("{}", value))
+        }
+        ConfigAction::Get { key: None } => {
+            config::show(&load_config(home, strict)).map(|settings| print!("{}", settings))
+        }
+        ConfigAction::Set { key, value } => config::set(&path, &key, &value),
+    };
+    if let Err(e) = result {
+        eprintln!("error: {}", e);
         std::process::exit(1);
     }
 }
 
-fn cmd_reset(pipeline: &str) {
-    let home = cronclaw_home();
-    let state_file = home.join("pipelines").join(pipeline).join("state.json");
+fn cmd_graph(home: &Path, pipeline: &str, format: GraphFormat) {
+    ensure_initialised(home);
+    let dir = named_pipeline_dir(home, pipeline);
+    let p = match pipeline::load(&dir.join("pipeline.yaml")) {
+        Ok(p) => p,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            std::process::exit(1);
+        }
+    };
+    let state = state::load(&dir.join("state.json"))
+        .ok()
+        .flatten()
+        .unwrap_or_else(|| State::from_pipeline(&p));
 
-    if !state_file.exists() {
+    match format {
+        GraphFormat::Dot => print!("{}", graph::to_dot(pipeline, &p, &state)),
+        GraphFormat::Mermaid => print!("{}", graph::to_mermaid(&p, &state)),
+    }
+}
+
+fn cmd_reset(
+    home: &Path,
+    pipeline: Option<&str>,
+    failed_only: bool,
+    step: Option<&str>,
+    from: Option<&str>,
+) {
+    ensure_initialised(home);
+
+    let Some(name) = pipeline else {
+        let dirs = pipeline_dirs(home);
+        let reset = dirs
+            .iter()
+            .filter(|dir| reset_pipeline(dir, failed_only))
+            .count();
         println!(
-            "No state file for pipeline '{}'. Nothing to reset.",
-            pipeline
+            "Reset {} of {} pipeline{}.",
+            reset,
+            dirs.len(),
+            if dirs.len() == 1 { "" } else { "s" }
         );
         return;
+    };
+
+    let dir = home.join("pipelines").join(name);
+    match (step, from) {
+        (Some(step), _) => reset_step(&dir, step, failed_only),
+        (None, Some(from)) => reset_from(&dir, from),
+        (None, None) => {
+            reset_pipeline(&dir, failed_only);
+        }
+    }
+}
+
+/// Remove a pipeline's state file, or with `failed_only` only if one of its
+/// steps has failed. Prints what happened and returns whether it was reset.
+fn reset_pipeline(dir: &Path, failed_only: bool) -> bool {
+    let name = dir.file_name().unwrap().to_string_lossy();
+    let state_file = dir.join("state.json");
+
+    if !state_file.exists() {
+        println!("No state file for pipeline '{}'. Nothing to reset.", name);
+        return false;
+    }
+
+    if failed_only {
+        let failed = match state::load(&state_file) {
+            Ok(Some(state)) => overall_status(&state) == "failed",
+            Ok(None) => false,
+            Err(e) => {
+                eprintln!("error: {}", e);
+                return false;
+            }
+        };
+        if !failed {
+            println!("Pipeline '{}' has not failed. Nothing to reset.", name);
+            return false;
+        }
     }
 
     fs::remove_file(&state_file).expect("failed to remove state file");
-    println!("Reset pipeline '{}'.", pipeline);
+    println!("Reset pipeline '{}'.", name);
+    true
+}
+
+/// Load a pipeline that must have a step called `step`, exiting otherwise.
+fn load_pipeline_with_step(dir: &Path, step: &str) -> pipeline::Pipeline {
+    let pipeline = match pipeline::load(&dir.join("pipeline.yaml")) {
+        Ok(p) => p,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            std::process::exit(1);
+        }
+    };
+    if !pipeline.steps.iter().any(|s| s.id == step) {
+        let name = dir.file_name().unwrap().to_string_lossy();
+        eprintln!("No step '{}' in pipeline '{}'.", step, name);
+        std::process::exit(1);
+    }
+    pipeline
+}
+
+/// Put one step of a pipeline back to pending.
+fn reset_step(dir: &Path, step: &str, failed_only: bool) {
+    let name = dir.file_name().unwrap().to_string_lossy();
+    load_pipeline_with_step(dir, step);
+
+    match runner::reset_step(dir, step, failed_only) {
+        Ok(true) => println!("Reset step '{}' in pipeline '{}'.", step, name),
+        Ok(false) if failed_only => println!(
+            "Step '{}' in pipeline '{}' has not failed. Nothing to reset.",
+            step, name
+        ),
+        Ok(false) => println!(
+            "Step '{}' in pipeline '{}' is already pending. Nothing to reset.",
+            step, name
+        ),
+        Err(e) => {
+            eprintln!("error: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+fn cmd_retry(home: &Path, pipeline: &str, step: &str) {
+    ensure_initialised(home);
+    let dir = named_pipeline_dir(home, pipeline);
+    let p = load_pipeline_with_step(&dir, step);
+    let budget = p.steps.iter().find(|s| s.id == step).unwrap().retries + 1;
+
+    match runner::retry_step(&dir, step) {
+        Ok(Some((status, attempts))) => println!(
+            "Step '{}' in pipeline '{}': {} ({} of {} attempts used) -> pending (0 of {} used).",
+            step,
+            pipeline,
+            status.name(),
+            attempts,
+            budget,
+            budget
+        ),
+        Ok(None) => println!(
+            "Step '{}' in pipeline '{}' has not failed. Nothing to retry.",
+            step, pipeline
+        ),
+        Err(e) => {
+            eprintln!("error: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Report steps added to or removed from pipeline.yaml since state.json was
+/// written, which would stop the next run. Exits 1 if there are any.
+fn cmd_check(home: &Path, pipeline: &str) {
+    ensure_initialised(home);
+    let dir = named_pipeline_dir(home, pipeline);
+    let p = match pipeline::load(&dir.join("pipeline.yaml")) {
+        Ok(p) => p,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            std::process::exit(EXIT_INVALID);
+        }
+    };
+    let state = match state::load(&dir.join("state.json")) {
+        Ok(Some(state)) => state,
+        Ok(None) => {
+            println!(
+                "Pipeline '{}' has no state yet. Nothing to compare.",
+                pipeline
+            );
+            return;
+        }
+        Err(e) => {
+            eprintln!("error: {}", e);
+            std::process::exit(EXIT_INVALID);
+        }
+    };
+
+    let drift = state.drift(&p);
+    if drift.is_empty() {
+        println!("Pipeline '{}': state.json matches pipeline.yaml.", pipeline);
+        return;
+    }
+    println!(
+        "Pipeline '{}': state.json doesn't match pipeline.yaml; the next run will fail.",
+        pipeline
+    );
+    for id in &drift.added {
+        println!("  added:   {} (in pipeline.yaml, not in state.json)", id);
+    }
+    for id in &drift.removed {
+        println!("  removed: {} (in state.json, not in pipeline.yaml)", id);
+    }
+    println!(
+        "Reset with `cronclaw reset {}` to start the pipeline over.",
+        pipeline
+    );
+    std::process::exit(1);
+}
+
+/// Pause or resume a pipeline.
+fn cmd_pause(home: &Path, pipeline: &str, paused: bool) {
+    ensure_initialised(home);
+    let dir = named_pipeline_dir(home, pipeline);
+
+    match runner::set_paused(&dir, paused) {
+        Ok(true) if paused => println!(
+            "Paused pipeline '{}'. Its steps keep their state; `cronclaw resume {}` to continue.",
+            pipeline, pipeline
+        ),
+        Ok(true) => println!("Resumed pipeline '{}'.", pipeline),
+        Ok(false) if paused => println!("Pipeline '{}' is already paused.", pipeline),
+        Ok(false) => println!("Pipeline '{}' isn't paused.", pipeline),
+        Err(e) => {
+            eprintln!("error: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Rewind a pipeline so `step` and everything declared after it run again.
+fn reset_from(dir: &Path, step: &str) {
+    let name = dir.file_name().unwrap().to_string_lossy();
+    let pipeline = load_pipeline_with_step(dir, step);
+
+    match runner::reset_from(dir, &pipeline, step) {
+        Ok(reset) if reset.is_empty() => println!(
+            "Steps from '{}' in pipeline '{}' are already pending. Nothing to reset.",
+            step, name
+        ),
+        Ok(reset) => println!(
+            "Reset pipeline '{}' from step '{}': {}.",
+            name,
+            step,
+            reset.join(", ")
+        ),
+        Err(e) => {
+            eprintln!("error: {}", e);
+            std::process::exit(1);
+        }
+    }
 }
 
 fn main() {
     let cli = Cli::parse();
+    let home = cronclaw_home(cli.home);
 
     match cli.command {
-        Some(Commands::Init) => cmd_init(),
-        Some(Commands::Run) => cmd_run(cli.verbose),
-        Some(Commands::Reset { pipeline }) => cmd_reset(&pipeline),
+        Some(Commands::Init { no_example, force }) => cmd_init(&home, no_example, force),
+        Some(Commands::Run {
+            pipeline,
+            dry_run,
+            tags,
+            step_timeout_override,
+            vars,
+        }) => {
+            let opts = runner::RunOptions {
+                verbose: cli.verbose,
+                dry_run,
+                json: cli.json,
+                quiet: cli.quiet,
+                step_timeout_override,
+                vars: vars.into_iter().collect(),
+                stop: Some(&STOP),
+            };
+            cmd_run(&home, pipeline.as_deref(), &tags, &opts, cli.strict)
+        }
+        Some(Commands::Daemon) => {
+            let opts = runner::RunOptions {
+                verbose: cli.verbose,
+                dry_run: false,
+                json: cli.json,
+                quiet: cli.quiet,
+                step_timeout_override: None,
+                vars: BTreeMap::new(),
+                // The daemon lets a tick finish before it stops
+                stop: None,
+            };
+            cmd_daemon(&home, &opts, cli.strict)
+        }
+        Some(Commands::Reset {
+            pipeline,
+            all: _,
+            failed_only,
+            step,
+            from,
+        }) => cmd_reset(
+            &home,
+            pipeline.as_deref(),
+            failed_only,
+            step.as_deref(),
+            from.as_deref(),
+        ),
+        Some(Commands::Retry { pipeline, step }) => cmd_retry(&home, &pipeline, &step),
+        Some(Commands::Pause { pipeline }) => cmd_pause(&home, &pipeline, true),
+        Some(Commands::Resume { pipeline }) => cmd_pause(&home, &pipeline, false),
+        Some(Commands::Check { pipeline }) => cmd_check(&home, &pipeline),
+        Some(Commands::Validate { path }) => cmd_validate(&home, path.as_deref()),
+        Some(Commands::Status { pipeline, tags }) => {
+            cmd_status(&home, pipeline.as_deref(), &tags, cli.verbose, cli.json)
+        }
+        Some(Commands::List { tags }) => cmd_list(&home, &tags, cli.verbose),
+        Some(Commands::Graph { pipeline, format }) => cmd_graph(&home, &pipeline, format),
+        Some(Commands::Explain { pipeline }) => cmd_explain(&home, &pipeline, cli.strict),
+        Some(Commands::Show { pipeline, step }) => cmd_show(&home, &pipeline, &step, cli.strict),
+        Some(Commands::Config { action }) => cmd_config(&home, action, cli.strict),
+        Some(Commands::Logs {
+            pipeline,
+            step,
+            follow,
+        }) => cmd_logs(&home, &pipeline, step.as_deref(), follow),
+        Some(Commands::Tail { pipeline, interval }) => cmd_tail(&home, &pipeline, interval),
+        Some(Commands::History { pipeline, since }) => {
+            cmd_history(&home, &pipeline, since.as_deref(), cli.json)
+        }
         None => {
             let _ = Cli::parse_from(["cronclaw", "--help"]);
         }
@@ -1,31 +1,116 @@
 use std::path::Path;
 use std::process::Command;
 
+/// Longest part of a prompt `describe_command` shows.
+const PROMPT_PREVIEW_CHARS: usize = 60;
+
 /// Resolve the openclaw binary. Checks `OPENCLAW_BIN` env var first,
 /// falls back to `openclaw` (found via PATH).
 pub fn resolve_binary() -> String {
     std::env::var("OPENCLAW_BIN").unwrap_or_else(|_| "openclaw".to_string())
 }
 
+/// Optional openclaw settings from an agent step.
+#[derive(Debug)]
+pub struct AgentOptions<'a> {
+    /// Run with `--local` (no gateway). On by default.
+    pub local: bool,
+    /// Passed as `--gateway` when not running locally.
+    pub gateway: Option<&'a str>,
+    /// Passed as `--model`.
+    pub model: Option<&'a str>,
+    /// Appended to the command verbatim.
+    pub extra_args: &'a [String],
+}
+
+impl Default for AgentOptions<'_> {
+    fn default() -> Self {
+        AgentOptions {
+            local: true,
+            gateway: None,
+            model: None,
+            extra_args: &[],
+        }
+    }
+}
+
 /// Build an `openclaw agent` Command ready to spawn.
 ///
 /// Maps the pipeline's `agent` field to `--to` (agent routing) and passes
-/// the resolved prompt via `--message`. Runs in `--local` mode (no gateway).
+/// the resolved prompt via `--message`. Runs in `--local` mode unless
+/// `options.local` is off, in which case `--gateway` is passed if set.
 /// Passes `--timeout` so openclaw can shut down gracefully before cronclaw's
-/// hard kill.
+/// hard kill. `--model` and any extra arguments follow.
 ///
 /// The binary can be overridden via the `OPENCLAW_BIN` environment variable.
-pub fn build_command(agent: &str, prompt: &str, workspace: &Path, timeout_secs: u64) -> Command {
+pub fn build_command(
+    agent: &str,
+    prompt: &str,
+    workspace: &Path,
+    timeout_secs: u64,
+    options: &AgentOptions,
+) -> Command {
     let bin = resolve_binary();
     let mut cmd = Command::new(bin);
     cmd.arg("agent")
         .arg("--message")
         .arg(prompt)
         .arg("--to")
-        .arg(agent)
-        .arg("--local")
-        .arg("--timeout")
+        .arg(agent);
+    if options.local {
+        cmd.arg("--local");
+    } else if let Some(gateway) = options.gateway {
+        cmd.arg("--gateway").arg(gateway);
+    }
+    cmd.arg("--timeout")
         .arg(timeout_secs.to_string())
         .current_dir(workspace);
+    if let Some(model) = options.model {
+        cmd.arg("--model").arg(model);
+    }
+    cmd.args(options.extra_args);
     cmd
 }
+
+/// The command line `cmd` would run, shell-quoted, for verbose output. Built
+/// from the command's own program and arguments rather than rebuilt from the
+/// step, so it is exactly what gets spawned, except that the `--message`
+/// prompt is cut to its first line and `PROMPT_PREVIEW_CHARS` characters:
+/// prompts can be long, and carry whatever their templates pulled in.
+pub fn describe_command(cmd: &Command) -> String {
+    let mut words = vec![shell_quote(&cmd.get_program().to_string_lossy())];
+    let mut is_prompt = false;
+    for arg in cmd.get_args() {
+        let arg = arg.to_string_lossy();
+        if is_prompt {
+            words.push(shell_quote(&preview(&arg)));
+        } else {
+            words.push(shell_quote(&arg));
+        }
+        is_prompt = arg == "--message";
+    }
+    words.join(" ")
+}
+
+/// The start of a prompt, noting its full size when some is left out.
+fn preview(prompt: &str) -> String {
+    let first_line = prompt.lines().next().unwrap_or("");
+    let shown: String = first_line.chars().take(PROMPT_PREVIEW_CHARS).collect();
+    if shown.len() == prompt.len() {
+        shown
+    } else {
+        format!("{}... ({} bytes)", shown, prompt.len())
+    }
+}
+
+fn shell_quote(word: &str) -> String {
+    let plain = !word.is_empty()
+        && word
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "-_./=:,@+%".contains(c));
+    if plain {
+        word.to_string()
+    } else {
+        format!("'{}'", word.replace('\'', "'\\''"))
+    }
+}
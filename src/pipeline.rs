@@ -1,22 +1,39 @@
+use regex::Regex;
 use serde::Deserialize;
+use serde::de::DeserializeOwned;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fs;
-use std::path::Path;
+use std::path::{Component, Path, PathBuf};
+use std::str::FromStr;
+
+use crate::error::CronclawError;
+
+/// The `version` of pipeline.yaml this build understands.
+pub const SUPPORTED_VERSION: u32 = 1;
 
 /// Where to route a stream (stdout or stderr) from a step.
 ///
 /// - Missing from YAML → `Terminal` (print to terminal)
 /// - `output: null`    → `Void` (discard)
 /// - `output: path`    → `File(path)` (write to file in workspace)
-#[derive(Debug, Clone, PartialEq)]
+/// - `output: {file: path, append: true}` → `Append(path)` (add to the end
+///   of the file instead of replacing it)
+#[derive(Debug, Clone, Default, PartialEq)]
 pub enum StreamTarget {
+    #[default]
     Terminal,
     Void,
     File(String),
+    Append(String),
 }
 
-impl Default for StreamTarget {
-    fn default() -> Self {
-        StreamTarget::Terminal
+impl StreamTarget {
+    /// The workspace file the stream is written to, if any.
+    pub fn file(&self) -> Option<&str> {
+        match self {
+            StreamTarget::File(path) | StreamTarget::Append(path) => Some(path),
+            StreamTarget::Terminal | StreamTarget::Void => None,
+        }
     }
 }
 
@@ -25,30 +42,170 @@ impl<'de> Deserialize<'de> for StreamTarget {
     where
         D: serde::Deserializer<'de>,
     {
-        let opt: Option<String> = Option::deserialize(deserializer)?;
-        match opt {
+        use serde::de::Error;
+
+        #[derive(Deserialize)]
+        #[serde(deny_unknown_fields)]
+        struct FileTarget {
+            file: String,
+            #[serde(default)]
+            append: bool,
+        }
+
+        match Option::<serde_yaml::Value>::deserialize(deserializer)? {
             None => Ok(StreamTarget::Void),
-            Some(s) => Ok(StreamTarget::File(s)),
+            Some(serde_yaml::Value::String(path)) => Ok(StreamTarget::File(path)),
+            Some(spec @ serde_yaml::Value::Mapping(_)) => {
+                let target: FileTarget = serde_yaml::from_value(spec).map_err(D::Error::custom)?;
+                if target.append {
+                    Ok(StreamTarget::Append(target.file))
+                } else {
+                    Ok(StreamTarget::File(target.file))
+                }
+            }
+            Some(_) => Err(D::Error::custom(
+                "expected a file path, null, or {file: <path>, append: true}",
+            )),
         }
     }
 }
 
 #[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct Pipeline {
     pub version: u32,
     pub workspace: String,
+
+    // Whether every run shares `workspace` or gets a fresh directory in it
+    #[serde(default)]
+    pub workspace_mode: WorkspaceMode,
+
+    // Cron expression limiting when the pipeline ticks
+    #[serde(default)]
+    pub schedule: Option<String>,
+
+    // Files, relative to the pipeline's directory, one of which has to
+    // change for the pipeline to tick, and what a missing one means
+    #[serde(default)]
+    pub watch: Vec<String>,
+    #[serde(default)]
+    pub watch_missing: WatchMissing,
+
+    // Timeout for steps that don't set their own, in place of the config's
+    #[serde(default, deserialize_with = "crate::config::deserialize_opt_secs")]
+    pub default_timeout: Option<u64>,
+
+    // Labels for selecting pipelines with `--tag`
+    #[serde(default)]
+    pub tags: Vec<String>,
+
+    // Environment variables for every step
+    #[serde(default)]
+    pub env: BTreeMap<String, String>,
+
+    // Values for `{{ var:NAME }}` templates, each with its default, or null
+    // if it must be given with `run --var`
+    #[serde(default)]
+    pub vars: BTreeMap<String, Option<String>>,
+
+    // Shell commands run when the pipeline completes or a step fails for
+    // good, in place of the config's. `on_failure` can instead be a list of
+    // cleanup steps.
+    #[serde(default)]
+    pub on_success: Option<String>,
+    #[serde(default)]
+    pub on_failure: Option<OnFailure>,
+
+    // Files of shared steps spliced in ahead of `steps`, relative to the
+    // file that includes them
+    #[serde(default)]
+    pub include: Vec<String>,
+
     pub steps: Vec<Step>,
 }
 
+/// How a pipeline's runs use its workspace.
+#[derive(Debug, Clone, Copy, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum WorkspaceMode {
+    /// Every run works in `workspace` itself, seeing what earlier runs left
+    #[default]
+    Shared,
+    /// Each run gets a new timestamped directory inside `workspace`, with
+    /// `latest` linking to the current one
+    Isolated,
+}
+
+/// What a missing `watch` file means for a pipeline's tick.
+#[derive(Debug, Clone, Copy, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum WatchMissing {
+    /// It counts as unchanged
+    #[default]
+    Ignore,
+    /// The run fails, naming the file
+    Fail,
+}
+
+/// What a pipeline does when one of its steps fails for good.
+#[derive(Debug)]
+pub enum OnFailure {
+    /// A shell command, like the config's `on_failure` hook
+    Command(String),
+    /// Cleanup steps run in order, whether or not the one before succeeded
+    Steps(Vec<Step>),
+}
+
+impl<'de> Deserialize<'de> for OnFailure {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        use serde::de::Error;
+        match serde_yaml::Value::deserialize(deserializer)? {
+            serde_yaml::Value::String(command) => Ok(OnFailure::Command(command)),
+            steps @ serde_yaml::Value::Sequence(_) => serde_yaml::from_value(steps)
+                .map(OnFailure::Steps)
+                .map_err(|e| D::Error::custom(format!("on_failure steps: {}", e))),
+            _ => Err(D::Error::custom(
+                "on_failure must be a shell command or a list of steps",
+            )),
+        }
+    }
+}
+
+/// A file of shared steps named in a pipeline's `include`. It may include
+/// further fragments itself.
 #[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Fragment {
+    #[serde(default)]
+    include: Vec<String>,
+    steps: Vec<Step>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct Step {
     pub id: String,
+    // What the step is for; shown in output, no effect on running it
+    #[serde(default)]
+    pub description: Option<String>,
     #[serde(rename = "type")]
     pub step_type: StepType,
 
     // Agent fields
     pub agent: Option<String>,
     pub prompt: Option<String>,
+    // Workspace file holding the prompt, in place of `prompt`
+    pub prompt_file: Option<String>,
+    pub model: Option<String>,
+    #[serde(default)]
+    pub extra_args: Vec<String>,
+    // Run openclaw with `--local`; turn off to go through a gateway
+    #[serde(default = "default_local")]
+    pub local: bool,
+    pub gateway: Option<String>,
 
     // Stream routing (shared across step types)
     #[serde(default)]
@@ -58,34 +215,438 @@ pub struct Step {
 
     // Bash fields
     pub bash: Option<String>,
+    pub shell: Option<String>,
+
+    // Python fields
+    pub python: Option<String>,
 
-    // Per-step timeout override (seconds)
+    // HTTP fields. `expect_status` empty means any 2xx.
+    pub method: Option<String>,
+    pub url: Option<String>,
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+    pub body: Option<String>,
+    #[serde(default)]
+    pub expect_status: Vec<u16>,
+
+    // Directory to run in, relative to the workspace
+    pub working_dir: Option<String>,
+
+    // Fed to the process's stdin: text (templates allowed), or a workspace file
+    pub stdin: Option<String>,
+    pub stdin_file: Option<String>,
+
+    // Per-step timeout override: seconds, or a duration such as "30m"
+    #[serde(default, deserialize_with = "crate::config::deserialize_opt_secs")]
     pub timeout: Option<u64>,
 
+    // Step dependencies. Missing means "the previous step", an explicit
+    // list (possibly empty) replaces that implicit edge.
+    pub needs: Option<Vec<String>>,
+
+    // Extra attempts after a failure, and the minimum wait between them
+    #[serde(default)]
+    pub retries: u32,
+    #[serde(default)]
+    pub retry_delay_secs: u64,
+
+    // Wall time allowed across all attempts (seconds), counted from the first
+    #[serde(default)]
+    pub deadline_secs: Option<u64>,
+
+    // Shell condition; the step is skipped unless it exits 0
+    #[serde(default)]
+    pub when: Option<String>,
+
+    // Complete the step even if it fails, so later steps still run
+    #[serde(default)]
+    pub allow_failure: bool,
+
+    // Environment variables for this step, overriding the pipeline's
+    #[serde(default)]
+    pub env: BTreeMap<String, String>,
+
+    // Workspace files that must exist before the step runs
+    #[serde(default)]
+    pub inputs: Vec<String>,
+
     // Outputs
     #[serde(default)]
     pub outputs: Vec<Output>,
+
+    // Values to run the step once for each, as `<id>-<value>` with
+    // `{{ matrix }}` replaced by the value. Expanded away when parsing.
+    #[serde(default)]
+    pub matrix: Vec<String>,
 }
 
-#[derive(Debug, Deserialize, PartialEq)]
+fn default_local() -> bool {
+    true
+}
+
+#[derive(Debug, Clone, Deserialize, PartialEq)]
 #[serde(rename_all = "lowercase")]
 pub enum StepType {
     Agent,
     Bash,
+    Python,
+    Http,
+    /// Runs nothing and succeeds straight away: a join point other steps
+    /// can `needs` in place of several
+    Noop,
 }
 
-#[derive(Debug, Deserialize)]
+impl StepType {
+    pub fn name(&self) -> &'static str {
+        match self {
+            StepType::Agent => "agent",
+            StepType::Bash => "bash",
+            StepType::Python => "python",
+            StepType::Http => "http",
+            StepType::Noop => "noop",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct Output {
     pub name: String,
     pub path: String,
+    // Defaults to `<path>.tmp` for an output taken from stdout
+    #[serde(default)]
     pub tmp: String,
+
+    // Where the runner gets the output from; unset means the step writes `tmp` itself
+    #[serde(default)]
+    pub from: Option<OutputSource>,
+
+    // Smallest size the tmp file may be promoted at; 1 rejects an empty file
+    #[serde(default)]
+    pub min_bytes: u64,
 }
 
-pub fn parse(content: &str) -> Result<Pipeline, String> {
-    let pipeline: Pipeline =
-        serde_yaml::from_str(content).map_err(|e| format!("failed to parse pipeline: {}", e))?;
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputSource {
+    Stdout,
+}
+
+impl Step {
+    /// Put a matrix value in place of `{{ matrix }}` in every text field.
+    fn fill_in_matrix(&mut self, value: &str) {
+        let re = Regex::new(r"\{\{\s*matrix\s*\}\}").unwrap();
+        let fill = |text: &mut String| {
+            *text = re.replace_all(text, regex::NoExpand(value)).into_owned();
+        };
+        let texts = [
+            &mut self.description,
+            &mut self.agent,
+            &mut self.prompt,
+            &mut self.prompt_file,
+            &mut self.model,
+            &mut self.bash,
+            &mut self.python,
+            &mut self.url,
+            &mut self.body,
+            &mut self.working_dir,
+            &mut self.stdin,
+            &mut self.stdin_file,
+            &mut self.when,
+        ];
+        for text in texts.into_iter().flatten() {
+            fill(text);
+        }
+        for target in [&mut self.output, &mut self.error] {
+            if let StreamTarget::File(path) | StreamTarget::Append(path) = target {
+                fill(path);
+            }
+        }
+        for text in self
+            .extra_args
+            .iter_mut()
+            .chain(self.headers.values_mut())
+            .chain(self.env.values_mut())
+            .chain(self.inputs.iter_mut())
+        {
+            fill(text);
+        }
+        for output in &mut self.outputs {
+            fill(&mut output.path);
+            fill(&mut output.tmp);
+        }
+    }
+
+    /// The output the step's stdout is written to, if it has one.
+    pub fn stdout_output(&self) -> Option<&Output> {
+        self.outputs
+            .iter()
+            .find(|o| o.from == Some(OutputSource::Stdout))
+    }
+
+    /// Where the step's stdout goes: its `from: stdout` output's tmp file
+    /// if it has one, otherwise its `output`.
+    pub fn stdout_target(&self) -> StreamTarget {
+        match self.stdout_output() {
+            Some(output) => StreamTarget::File(output.tmp.clone()),
+            None => self.output.clone(),
+        }
+    }
+}
 
+impl Pipeline {
+    /// The shell command to run when a step fails for good, if `on_failure`
+    /// is one.
+    pub fn on_failure_command(&self) -> Option<&String> {
+        match &self.on_failure {
+            Some(OnFailure::Command(command)) => Some(command),
+            _ => None,
+        }
+    }
+
+    /// The cleanup steps to run when a step fails for good, if `on_failure`
+    /// lists any.
+    pub fn cleanup_steps(&self) -> &[Step] {
+        match &self.on_failure {
+            Some(OnFailure::Steps(steps)) => steps,
+            _ => &[],
+        }
+    }
+
+    /// Give declared `vars` the values passed with `run --var`, in place of
+    /// their defaults. Errors on a name the pipeline doesn't declare, or a
+    /// variable left without a value.
+    pub fn set_vars(&mut self, given: &BTreeMap<String, String>) -> Result<(), String> {
+        for (name, value) in given {
+            let Some(slot) = self.vars.get_mut(name) else {
+                return Err(format!(
+                    "unknown variable '{}': the pipeline doesn't declare it in 'vars'",
+                    name
+                ));
+            };
+            *slot = Some(value.clone());
+        }
+        match self.vars.iter().find(|(_, value)| value.is_none()) {
+            Some((name, _)) => Err(format!(
+                "variable '{}' has no default; pass it with --var {}=VALUE",
+                name, name
+            )),
+            None => Ok(()),
+        }
+    }
+
+    /// Environment for a step: the pipeline's `env` overlaid with the step's own.
+    pub fn env_for(&self, step: &Step) -> BTreeMap<String, String> {
+        let mut env = self.env.clone();
+        env.extend(step.env.iter().map(|(k, v)| (k.clone(), v.clone())));
+        env
+    }
+
+    /// Ids of the steps that must be completed before step `index` may run.
+    ///
+    /// A step without `needs` depends on the step declared before it, so
+    /// pipelines that never use `needs` keep their linear order.
+    pub fn needs_of(&self, index: usize) -> Vec<&str> {
+        match &self.steps[index].needs {
+            Some(needs) => needs.iter().map(|s| s.as_str()).collect(),
+            None if index > 0 => vec![self.steps[index - 1].id.as_str()],
+            None => Vec::new(),
+        }
+    }
+}
+
+/// Reject `needs` entries that name unknown steps or form a cycle.
+fn check_needs(pipeline: &Pipeline) -> Result<(), String> {
+    let index_of: HashMap<&str, usize> = pipeline
+        .steps
+        .iter()
+        .enumerate()
+        .map(|(i, s)| (s.id.as_str(), i))
+        .collect();
+
+    let mut edges = Vec::with_capacity(pipeline.steps.len());
+    for (i, step) in pipeline.steps.iter().enumerate() {
+        let mut deps = Vec::new();
+        for need in pipeline.needs_of(i) {
+            match index_of.get(need) {
+                Some(&j) => deps.push(j),
+                None => {
+                    return Err(format!("step '{}': needs unknown step '{}'", step.id, need));
+                }
+            }
+        }
+        edges.push(deps);
+    }
+
+    // Depth-first search: 0 = unvisited, 1 = on the current path, 2 = done.
+    // `path` holds the steps on the current path, to name the cycle.
+    fn visit(
+        i: usize,
+        edges: &[Vec<usize>],
+        marks: &mut [u8],
+        path: &mut Vec<usize>,
+        pipeline: &Pipeline,
+    ) -> Result<(), String> {
+        match marks[i] {
+            1 => {
+                let start = path.iter().position(|&j| j == i).unwrap();
+                let cycle: Vec<&str> = path[start..]
+                    .iter()
+                    .chain(std::iter::once(&i))
+                    .map(|&j| pipeline.steps[j].id.as_str())
+                    .collect();
+                return Err(format!(
+                    "step '{}': dependency cycle detected in 'needs': {}",
+                    pipeline.steps[i].id,
+                    cycle.join(" -> ")
+                ));
+            }
+            2 => return Ok(()),
+            _ => {}
+        }
+        marks[i] = 1;
+        path.push(i);
+        for &j in &edges[i] {
+            visit(j, edges, marks, path, pipeline)?;
+        }
+        path.pop();
+        marks[i] = 2;
+        Ok(())
+    }
+
+    let mut marks = vec![0u8; pipeline.steps.len()];
+    let mut path = Vec::new();
+    for i in 0..pipeline.steps.len() {
+        visit(i, &edges, &mut marks, &mut path, pipeline)?;
+    }
+    Ok(())
+}
+
+/// Checks on outputs across the whole pipeline, run by `parse`. Returns
+/// every problem found rather than stopping at the first.
+pub fn check(pipeline: &Pipeline) -> Vec<String> {
+    let mut problems = Vec::new();
+
+    // Where each output file comes from, to spot two outputs claiming the same file
+    let mut files: HashMap<&str, String> = HashMap::new();
     for step in &pipeline.steps {
+        let mut names = HashSet::new();
+        for output in &step.outputs {
+            if !names.insert(output.name.as_str()) {
+                problems.push(format!(
+                    "step '{}': duplicate output name '{}'",
+                    step.id, output.name
+                ));
+            }
+
+            // Renaming a file onto itself would hide a step that never wrote its tmp
+            if output.tmp == output.path {
+                problems.push(format!(
+                    "step '{}' output '{}': tmp and path are both '{}'",
+                    step.id, output.name, output.path
+                ));
+                continue;
+            }
+
+            for (file, role) in [(&output.path, "path"), (&output.tmp, "tmp")] {
+                let owner = format!("step '{}' output '{}' {}", step.id, output.name, role);
+                if let Some(other) = files.get(file.as_str()) {
+                    problems.push(format!("{} '{}' collides with {}", owner, file, other));
+                } else {
+                    files.insert(file, owner);
+                }
+            }
+        }
+    }
+
+    problems
+}
+
+/// Parse and validate a pipeline on its own. A pipeline with `include`
+/// needs `load`, which knows where to resolve the included files from.
+pub fn parse(content: &str) -> Result<Pipeline, CronclawError> {
+    let pipeline = deserialize(content).map_err(CronclawError::Parse)?;
+    if !pipeline.include.is_empty() {
+        return Err(CronclawError::Parse(
+            "'include' needs a pipeline file to resolve paths from".to_string(),
+        ));
+    }
+    validate(pipeline).map_err(CronclawError::Parse)
+}
+
+fn deserialize(content: &str) -> Result<Pipeline, String> {
+    let pipeline: Pipeline =
+        from_yaml(content).map_err(|e| format!("failed to parse pipeline: {}", e))?;
+
+    if pipeline.version > SUPPORTED_VERSION {
+        return Err(format!(
+            "pipeline version {} is newer than this cronclaw supports (version {}); upgrade cronclaw to run it",
+            pipeline.version, SUPPORTED_VERSION
+        ));
+    }
+    if pipeline.version != SUPPORTED_VERSION {
+        return Err(format!(
+            "unsupported pipeline version {} (expected {})",
+            pipeline.version, SUPPORTED_VERSION
+        ));
+    }
+
+    Ok(pipeline)
+}
+
+/// Deserialize a pipeline or included file. Anchors and aliases are expanded
+/// by serde_yaml, each alias into a copy of its own; on top of that, `<<`
+/// merge keys are applied and top-level `x-` keys, which only hold anchors
+/// for the rest of the file, are dropped.
+fn from_yaml<T: DeserializeOwned>(content: &str) -> Result<T, serde_yaml::Error> {
+    let mut value: serde_yaml::Value = serde_yaml::from_str(content)?;
+    let original = value.clone();
+    if let serde_yaml::Value::Mapping(map) = &mut value {
+        map.retain(|key, _| !key.as_str().is_some_and(|key| key.starts_with("x-")));
+    }
+    value.apply_merge()?;
+    if value == original {
+        // Parsing the text itself keeps line numbers in error messages
+        return serde_yaml::from_str(content);
+    }
+    serde_yaml::from_value(value)
+}
+
+fn validate(mut pipeline: Pipeline) -> Result<Pipeline, String> {
+    expand_matrices(&mut pipeline)?;
+
+    // State is keyed by step id, so ids must be unique; cleanup steps
+    // share the id space as they log alongside the others. Ids also name
+    // files under logs/, so they can't be paths.
+    let mut ids = HashSet::new();
+    for step in pipeline.steps.iter().chain(pipeline.cleanup_steps()) {
+        if step.id.is_empty() || step.id.contains(['/', '\\']) || step.id.contains("..") {
+            return Err(format!(
+                "step id '{}' must be non-empty, without '/', '\\' or '..', as it names the step's log file",
+                step.id
+            ));
+        }
+        if !ids.insert(step.id.as_str()) {
+            return Err(format!("duplicate step id '{}'", step.id));
+        }
+    }
+
+    for step in pipeline.cleanup_steps() {
+        let unsupported = [
+            ("needs", step.needs.is_some()),
+            ("outputs", !step.outputs.is_empty()),
+            ("matrix", !step.matrix.is_empty()),
+            ("retries", step.retries > 0),
+        ];
+        if let Some((field, _)) = unsupported.iter().find(|(_, set)| *set) {
+            return Err(format!(
+                "on_failure step '{}': '{}' isn't supported in cleanup steps",
+                step.id, field
+            ));
+        }
+    }
+
+    for step in pipeline.steps.iter().chain(pipeline.cleanup_steps()) {
         match step.step_type {
             StepType::Bash => {
                 if step.bash.is_none() {
@@ -95,10 +656,52 @@ pub fn parse(content: &str) -> Result<Pipeline, String> {
                     ));
                 }
             }
+            StepType::Python => {
+                if step.python.is_none() {
+                    return Err(format!(
+                        "step '{}': type is python but 'python' field is missing",
+                        step.id
+                    ));
+                }
+            }
             StepType::Agent => {
-                if step.agent.is_none() || step.prompt.is_none() {
+                if step.agent.is_none() {
                     return Err(format!(
-                        "step '{}': type is agent but 'agent' or 'prompt' field is missing",
+                        "step '{}': type is agent but 'agent' field is missing",
+                        step.id
+                    ));
+                }
+                if step.prompt.is_none() && step.prompt_file.is_none() {
+                    return Err(format!(
+                        "step '{}': type is agent but neither 'prompt' nor 'prompt_file' is set",
+                        step.id
+                    ));
+                }
+                if step.prompt.is_some() && step.prompt_file.is_some() {
+                    return Err(format!(
+                        "step '{}': set either 'prompt' or 'prompt_file', not both",
+                        step.id
+                    ));
+                }
+                if step.local && step.gateway.is_some() {
+                    return Err(format!(
+                        "step '{}': 'gateway' needs 'local: false'",
+                        step.id
+                    ));
+                }
+            }
+            StepType::Http => {
+                if step.url.is_none() {
+                    return Err(format!(
+                        "step '{}': type is http but 'url' field is missing",
+                        step.id
+                    ));
+                }
+            }
+            StepType::Noop => {
+                if !step.outputs.is_empty() {
+                    return Err(format!(
+                        "step '{}': a noop step has no 'outputs' to produce",
                         step.id
                     ));
                 }
@@ -106,11 +709,381 @@ pub fn parse(content: &str) -> Result<Pipeline, String> {
         }
     }
 
+    if pipeline.default_timeout == Some(0) {
+        return Err("default_timeout must be at least 1 second".to_string());
+    }
+    for step in pipeline.steps.iter().chain(pipeline.cleanup_steps()) {
+        if step.timeout == Some(0) {
+            return Err(format!(
+                "step '{}': timeout must be at least 1 second",
+                step.id
+            ));
+        }
+        if step.deadline_secs == Some(0) {
+            return Err(format!(
+                "step '{}': deadline_secs must be at least 1 second",
+                step.id
+            ));
+        }
+        if step.stdin.is_some() && step.stdin_file.is_some() {
+            return Err(format!(
+                "step '{}': set either 'stdin' or 'stdin_file', not both",
+                step.id
+            ));
+        }
+        if let Some(dir) = &step.working_dir
+            && !stays_inside(dir)
+        {
+            return Err(format!(
+                "step '{}': working_dir '{}' must be a relative path inside the workspace",
+                step.id, dir
+            ));
+        }
+        if let Some(input) = step.inputs.iter().find(|input| !stays_inside(input)) {
+            return Err(format!(
+                "step '{}': input '{}' must be a relative path inside the workspace",
+                step.id, input
+            ));
+        }
+        for (field, path) in step_paths(step) {
+            if !stays_inside(path) {
+                return Err(format!(
+                    "step '{}': {} '{}' must be a relative path inside the workspace",
+                    step.id, field, path
+                ));
+            }
+        }
+        for (field, text) in templated_fields(&pipeline, step) {
+            if let Some(name) = template_vars(text)
+                .into_iter()
+                .find(|name| !pipeline.vars.contains_key(*name))
+            {
+                return Err(format!(
+                    "step '{}': {} uses variable '{}', which isn't declared in 'vars'",
+                    step.id, field, name
+                ));
+            }
+            if let Some(path) = template_paths(text)
+                .into_iter()
+                .find(|path| !stays_inside(path))
+            {
+                return Err(format!(
+                    "step '{}': {} template path '{}' must be a relative path inside the workspace",
+                    step.id, field, path
+                ));
+            }
+        }
+        let from_stdout = step
+            .outputs
+            .iter()
+            .filter(|o| o.from == Some(OutputSource::Stdout))
+            .count();
+        if from_stdout > 1 {
+            return Err(format!(
+                "step '{}': only one output can be 'from: stdout'",
+                step.id
+            ));
+        }
+        if from_stdout == 1 && step.output != StreamTarget::Terminal {
+            return Err(format!(
+                "step '{}': set either 'output' or an output 'from: stdout', not both",
+                step.id
+            ));
+        }
+    }
+
+    // An output taken from stdout gets a tmp file next to its path unless it names one
+    for step in &mut pipeline.steps {
+        for output in &mut step.outputs {
+            if !output.tmp.is_empty() {
+                continue;
+            }
+            if output.from != Some(OutputSource::Stdout) {
+                return Err(format!(
+                    "step '{}' output '{}': missing 'tmp'",
+                    step.id, output.name
+                ));
+            }
+            output.tmp = format!("{}.tmp", output.path);
+        }
+    }
+
+    check_needs(&pipeline)?;
+
+    // A declared variable nothing uses is most likely a typo for one that is.
+    // A `prompt_file` is only read at run time and may use any of them, so
+    // with one around there's no telling.
+    let prompt_files = pipeline
+        .steps
+        .iter()
+        .chain(pipeline.cleanup_steps())
+        .any(|step| step.prompt_file.is_some());
+    let used: HashSet<&str> = pipeline
+        .steps
+        .iter()
+        .chain(pipeline.cleanup_steps())
+        .flat_map(|step| templated_fields(&pipeline, step))
+        .flat_map(|(_, text)| template_vars(text))
+        .collect();
+    if let Some(name) = pipeline
+        .vars
+        .keys()
+        .find(|name| !prompt_files && !used.contains(name.as_str()))
+    {
+        return Err(format!(
+            "variable '{}' is declared in 'vars' but no step uses {{{{ var:{} }}}}",
+            name, name
+        ));
+    }
+
+    // One problem per line, so `validate` can list them all
+    let problems = check(&pipeline);
+    if !problems.is_empty() {
+        return Err(problems.join("\n"));
+    }
+
+    if let Some(expr) = &pipeline.schedule {
+        parse_schedule(expr).map_err(|e| e.to_string())?;
+    }
+
     Ok(pipeline)
 }
 
-pub fn load(path: &Path) -> Result<Pipeline, String> {
+/// The workspace files a step names directly, with the field naming each.
+fn step_paths(step: &Step) -> Vec<(String, &str)> {
+    let mut paths = Vec::new();
+    for (field, target) in [("output", &step.output), ("error", &step.error)] {
+        if let Some(path) = target.file() {
+            paths.push((field.to_string(), path));
+        }
+    }
+    if let Some(file) = &step.stdin_file {
+        paths.push(("stdin_file".to_string(), file.as_str()));
+    }
+    if let Some(file) = &step.prompt_file {
+        paths.push(("prompt_file".to_string(), file.as_str()));
+    }
+    for output in &step.outputs {
+        paths.push((
+            format!("output '{}' path", output.name),
+            output.path.as_str(),
+        ));
+        if !output.tmp.is_empty() {
+            paths.push((format!("output '{}' tmp", output.name), output.tmp.as_str()));
+        }
+    }
+    paths
+}
+
+/// The fields of a step whose `{{ ... }}` templates are resolved when it
+/// runs, with the field naming each. Includes the pipeline's `env`.
+fn templated_fields<'a>(pipeline: &'a Pipeline, step: &'a Step) -> Vec<(String, &'a str)> {
+    let mut fields: Vec<(String, &str)> = [
+        ("prompt", &step.prompt),
+        ("python", &step.python),
+        ("stdin", &step.stdin),
+        ("url", &step.url),
+        ("body", &step.body),
+    ]
+    .into_iter()
+    .filter_map(|(field, text)| Some((field.to_string(), text.as_deref()?)))
+    .collect();
+    for (name, value) in &step.headers {
+        fields.push((format!("header '{}'", name), value));
+    }
+    for (key, value) in pipeline.env.iter().chain(&step.env) {
+        fields.push((format!("env '{}'", key), value));
+    }
+    fields
+}
+
+/// The paths and patterns named by `{{ file:... }}` and `{{ glob:... }}`
+/// templates in `text`.
+fn template_paths(text: &str) -> Vec<&str> {
+    let re = Regex::new(r"\{\{\s*(?:file|glob):\s*(.+?)\s*\}\}").unwrap();
+    re.captures_iter(text)
+        .map(|cap| cap.get(1).unwrap().as_str())
+        .collect()
+}
+
+/// The variables named by `{{ var:... }}` templates in `text`.
+fn template_vars(text: &str) -> Vec<&str> {
+    let re = Regex::new(r"\{\{\s*var:\s*(.+?)\s*\}\}").unwrap();
+    re.captures_iter(text)
+        .map(|cap| cap.get(1).unwrap().as_str())
+        .collect()
+}
+
+/// Whether a workspace-relative path stays inside the workspace: relative,
+/// and without `..`.
+fn stays_inside(path: &str) -> bool {
+    Path::new(path)
+        .components()
+        .all(|c| matches!(c, Component::Normal(_) | Component::CurDir))
+}
+
+/// Replace every step with a `matrix` by one step per value, and point
+/// `needs` naming such a step at all of its copies.
+fn expand_matrices(pipeline: &mut Pipeline) -> Result<(), String> {
+    let mut expanded: HashMap<String, Vec<String>> = HashMap::new();
+    let mut steps = Vec::new();
+    for step in std::mem::take(&mut pipeline.steps) {
+        if step.matrix.is_empty() {
+            steps.push(step);
+            continue;
+        }
+        let mut ids = Vec::new();
+        for value in &step.matrix {
+            if value.is_empty() || value.contains(|c: char| c.is_whitespace() || c == '/') {
+                return Err(format!(
+                    "step '{}': matrix value '{}' must be non-empty, without spaces or '/', as it becomes part of a step id",
+                    step.id, value
+                ));
+            }
+            let mut copy = step.clone();
+            copy.id = format!("{}-{}", step.id, value);
+            copy.matrix = Vec::new();
+            copy.fill_in_matrix(value);
+            ids.push(copy.id.clone());
+            steps.push(copy);
+        }
+        expanded.insert(step.id, ids);
+    }
+    pipeline.steps = steps;
+
+    for step in &mut pipeline.steps {
+        if let Some(needs) = &mut step.needs {
+            *needs = needs
+                .iter()
+                .flat_map(|need| match expanded.get(need) {
+                    Some(ids) => ids.clone(),
+                    None => vec![need.clone()],
+                })
+                .collect();
+        }
+    }
+    Ok(())
+}
+
+/// Parse a standard five-field cron expression (minute, hour, day of month,
+/// month, day of week), evaluated in local time.
+pub fn parse_schedule(expr: &str) -> Result<cron::Schedule, CronclawError> {
+    let fields: Vec<&str> = expr.split_whitespace().collect();
+    if fields.len() != 5 {
+        return Err(CronclawError::Parse(format!(
+            "schedule '{}': expected 5 fields (minute hour day month weekday), found {}",
+            expr,
+            fields.len()
+        )));
+    }
+
+    // The cron crate wants a seconds field, and numbers weekdays from 1 = Sunday
+    // rather than cron's 0 = Sunday, so spell weekdays out by name
+    let weekdays = fields[4]
+        .split(',')
+        .map(|part| {
+            let (range, step) = match part.split_once('/') {
+                Some((range, step)) => (range, Some(step)),
+                None => (part, None),
+            };
+            let range = range
+                .split('-')
+                .map(|day| match day.parse::<usize>() {
+                    Ok(n) if n <= 7 => ["Sun", "Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"][n],
+                    _ => day,
+                })
+                .collect::<Vec<_>>()
+                .join("-");
+            match step {
+                Some(step) => format!("{}/{}", range, step),
+                None => range,
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(",");
+
+    let full = format!("0 {} {}", fields[..4].join(" "), weekdays);
+    cron::Schedule::from_str(&full)
+        .map_err(|e| CronclawError::Parse(format!("schedule '{}': {}", expr, e)))
+}
+
+pub fn load(path: &Path) -> Result<Pipeline, CronclawError> {
     let content = fs::read_to_string(path)
-        .map_err(|e| format!("failed to read {}: {}", path.display(), e))?;
-    parse(&content).map_err(|e| format!("{}: {}", path.display(), e))
+        .map_err(|e| CronclawError::Parse(format!("failed to read {}: {}", path.display(), e)))?;
+    let in_file = |e: String| {
+        let lines: Vec<String> = e
+            .lines()
+            .map(|line| format!("{}: {}", path.display(), line))
+            .collect();
+        CronclawError::Parse(lines.join("\n"))
+    };
+    let mut pipeline = deserialize(&content).map_err(in_file)?;
+    if !pipeline.include.is_empty() {
+        let dir = path.parent().unwrap_or(Path::new("."));
+        let mut chain = vec![path.canonicalize().unwrap_or_else(|_| path.to_path_buf())];
+        let included = include_steps(&pipeline.include, dir, &mut chain).map_err(in_file)?;
+        check_included_ids(&included, &pipeline.steps).map_err(in_file)?;
+        let own = std::mem::take(&mut pipeline.steps);
+        pipeline.steps = included.into_iter().map(|(_, step)| step).collect();
+        pipeline.steps.extend(own);
+    }
+    validate(pipeline).map_err(in_file)
+}
+
+/// The steps of every fragment in `includes`, in order, each paired with the
+/// include path it came from. `chain` holds the files being included, so a
+/// fragment that includes itself again, however indirectly, is an error.
+fn include_steps(
+    includes: &[String],
+    dir: &Path,
+    chain: &mut Vec<PathBuf>,
+) -> Result<Vec<(String, Step)>, String> {
+    let mut steps = Vec::new();
+    for include in includes {
+        let in_include = |e: String| format!("include '{}': {}", include, e);
+        let path = dir.join(include);
+        let canonical = path
+            .canonicalize()
+            .map_err(|e| in_include(format!("failed to read {}: {}", path.display(), e)))?;
+        if chain.contains(&canonical) {
+            let cycle: Vec<String> = chain
+                .iter()
+                .chain([&canonical])
+                .map(|p| p.display().to_string())
+                .collect();
+            return Err(in_include(format!("include cycle: {}", cycle.join(" -> "))));
+        }
+        let content = fs::read_to_string(&canonical)
+            .map_err(|e| in_include(format!("failed to read {}: {}", path.display(), e)))?;
+        let fragment: Fragment =
+            from_yaml(&content).map_err(|e| in_include(format!("failed to parse: {}", e)))?;
+
+        chain.push(canonical.clone());
+        let nested = include_steps(&fragment.include, canonical.parent().unwrap_or(dir), chain)
+            .map_err(in_include)?;
+        chain.pop();
+
+        steps.extend(nested);
+        steps.extend(fragment.steps.into_iter().map(|s| (include.clone(), s)));
+    }
+    Ok(steps)
+}
+
+/// Included steps share one id space with the pipeline's own, so name the
+/// file behind each side of a clash.
+fn check_included_ids(included: &[(String, Step)], own: &[Step]) -> Result<(), String> {
+    let mut sources: HashMap<&str, &str> = HashMap::new();
+    let all = included
+        .iter()
+        .map(|(source, step)| (source.as_str(), step))
+        .chain(own.iter().map(|step| ("pipeline.yaml", step)));
+    for (source, step) in all {
+        if let Some(first) = sources.insert(&step.id, source) {
+            return Err(format!(
+                "duplicate step id '{}' (from {} and {})",
+                step.id, first, source
+            ));
+        }
+    }
+    Ok(())
 }
@@ -1,218 +1,1926 @@
+use std::collections::hash_map::Entry;
+use std::collections::{BTreeMap, HashMap};
 use std::fs::{self, File};
-use std::path::Path;
-use std::process::Command;
+use std::io::{Read, Write};
+use std::os::unix::process::CommandExt;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::time::{Duration, Instant};
 
+use chrono::{DateTime, Local};
 use fs2::FileExt;
 use regex::Regex;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
 
-use crate::config::Config;
-use crate::pipeline::{Step, StepType, StreamTarget};
-use crate::state::{self, State, StepStatus};
+use crate::config::{Config, MissingInputs};
+use crate::error::CronclawError;
+use crate::history;
+use crate::logs::StepLog;
+use crate::openclaw::AgentOptions;
+use crate::pipeline::{
+    OutputSource, Pipeline, Step, StepType, StreamTarget, WatchMissing, WorkspaceMode,
+};
+use crate::state::{self, State, StepState, StepStatus};
 
-/// Result of acquiring the state lock and deciding what to do.
+/// Why a step failed. `exit_code` is set when the process ran to completion
+/// and exited non-zero; spawn errors, timeouts and the like leave it empty.
+struct StepFailure {
+    message: String,
+    exit_code: Option<i32>,
+    /// Stopped for running past its timeout
+    timed_out: bool,
+    /// Stopped because the tick was interrupted
+    interrupted: bool,
+}
+
+impl From<String> for StepFailure {
+    fn from(message: String) -> Self {
+        StepFailure {
+            message,
+            exit_code: None,
+            timed_out: false,
+            interrupted: false,
+        }
+    }
+}
+
+impl From<CronclawError> for StepFailure {
+    fn from(error: CronclawError) -> Self {
+        StepFailure::from(error.to_string())
+    }
+}
+
+/// How a `run_pipeline` tick that didn't fail ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Ticked {
+    /// Whatever was due has run, which may have been nothing
+    Done,
+    /// A step is still running in another process, so nothing else could run
+    Busy,
+}
+
+/// How a `run_pipeline` tick behaves and reports its progress.
+#[derive(Debug, Clone, Default)]
+pub struct RunOptions {
+    /// Explain ticks that don't run anything.
+    pub verbose: bool,
+    /// Report the step that would run instead of running it, writing nothing.
+    pub dry_run: bool,
+    /// Report progress as one JSON object per line on stdout instead of text.
+    pub json: bool,
+    /// Leave out routine text progress such as "running step"; failures are
+    /// still reported on stderr.
+    pub quiet: bool,
+    /// Timeout in seconds for whichever steps this tick runs, in place of
+    /// their own, `default_timeout`, `timeout` and `max_timeout`. Not saved.
+    pub step_timeout_override: Option<u64>,
+    /// Values for the pipeline's `vars`, in place of their defaults.
+    pub vars: BTreeMap<String, String>,
+    /// Set, e.g. by a signal handler, to interrupt the tick: running steps
+    /// are stopped as on a timeout and go back to pending, and no more start.
+    pub stop: Option<&'static AtomicBool>,
+}
+
+impl RunOptions {
+    fn stopped(&self) -> bool {
+        self.stop.is_some_and(|stop| stop.load(Ordering::SeqCst))
+    }
+}
+
+/// A progress event, printed as one line of JSON in `--json` mode.
+#[derive(Serialize)]
+struct Event<'a> {
+    event: &'a str,
+    pipeline: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    step: Option<&'a str>,
+    status: &'a str,
+    timestamp: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    attempt: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    duration_secs: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    exit_code: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<&'a str>,
+}
+
+impl<'a> Event<'a> {
+    fn new(event: &'a str, pipeline: &'a str, step: Option<&'a str>, status: &'a str) -> Self {
+        Event {
+            event,
+            pipeline,
+            step,
+            status,
+            timestamp: state::now(),
+            attempt: None,
+            duration_secs: None,
+            exit_code: None,
+            error: None,
+        }
+    }
+
+    fn emit(&self) {
+        println!("{}", serde_json::to_string(self).unwrap());
+    }
+}
+
+/// Print a verbose-only note, keeping it off stdout in JSON mode.
+fn note(opts: &RunOptions, message: String) {
+    if !opts.verbose {
+        return;
+    }
+    if opts.json {
+        eprintln!("{}", message);
+    } else {
+        println!("{}", message);
+    }
+}
+
+/// Print a routine progress line, unless running quietly.
+fn progress(opts: &RunOptions, message: String) {
+    if !opts.quiet {
+        println!("{}", message);
+    }
+}
+
+/// A step claimed by this tick: already marked running in state.json.
 struct Ticket {
     step_index: usize,
-    step_id: String,
+    /// Which attempt this run is, counting from 1
+    attempt: u32,
     timeout_secs: u64,
-    state: State,
+    /// When the step's `deadline_secs` runs out, if it has one
+    deadline: Option<Instant>,
+    /// Why the step can't start, found while claiming it. It fails without
+    /// running, like any other failed attempt.
+    preflight: Option<String>,
 }
 
-/// Lock state.json, load state, find the next pending step, mark it running,
-/// save, and release the lock. Returns None if there's nothing to do.
-fn acquire_ticket(
+/// Take the exclusive lock on the pipeline's state. Released when the
+/// returned file is dropped.
+fn lock_state(pipeline_dir: &Path, pipeline_name: &str) -> Result<File, CronclawError> {
+    let lock_file = File::create(pipeline_dir.join("state.lock")).map_err(|e| {
+        CronclawError::Io(format!(
+            "[{}] failed to create state lock: {}",
+            pipeline_name, e
+        ))
+    })?;
+    lock_file.lock_exclusive().map_err(|e| {
+        CronclawError::Io(format!(
+            "[{}] failed to acquire state lock: {}",
+            pipeline_name, e
+        ))
+    })?;
+    Ok(lock_file)
+}
+
+/// Lock state.json, load state, find up to `max` pending steps that are ready,
+/// mark them running, save, and release the lock. Returns no tickets if
+/// there's nothing to do, and None if a step is running elsewhere.
+///
+/// With `dry_run` nothing is locked, created or saved: the tickets describe
+/// the steps that would run, and their state is left untouched.
+fn acquire_tickets(
     pipeline_dir: &Path,
-    pipeline: &crate::pipeline::Pipeline,
+    pipeline: &Pipeline,
     cfg: &Config,
-    verbose: bool,
-) -> Result<Option<Ticket>, String> {
+    opts: &RunOptions,
+    max: usize,
+) -> Result<Option<Vec<Ticket>>, CronclawError> {
+    let dry_run = opts.dry_run;
     let state_file = pipeline_dir.join("state.json");
-    let workspace = pipeline_dir.join(&pipeline.workspace);
     let pipeline_name = pipeline_dir.file_name().unwrap().to_string_lossy();
 
     // Lock state.json for the read-decide-write transition
-    let lock_file = File::create(pipeline_dir.join("state.lock"))
-        .map_err(|e| format!("[{}] failed to create state lock: {}", pipeline_name, e))?;
-    lock_file
-        .lock_exclusive()
-        .map_err(|e| format!("[{}] failed to acquire state lock: {}", pipeline_name, e))?;
+    let _lock_file = if dry_run {
+        None
+    } else {
+        Some(lock_state(pipeline_dir, &pipeline_name)?)
+    };
+
+    // Load or create state (while holding lock)
+    let mut state = load_or_create_state(pipeline_dir, pipeline, dry_run)?;
+
+    // Verify state matches pipeline
+    let drift = state.drift(pipeline);
+    if !drift.is_empty() {
+        return Err(CronclawError::StateMismatch(format!(
+            "[{}] state file mismatch — steps in pipeline.yaml don't match state.json ({}). \
+             Consider resetting the pipeline with `cronclaw reset {}`.",
+            pipeline_name, drift, pipeline_name
+        )));
+    }
+
+    // State files from before `order` was recorded pick it up on their next save
+    if state.order.is_empty() {
+        state.order = pipeline.steps.iter().map(|s| s.id.clone()).collect();
+    }
+
+    // A running or failed step anywhere blocks the whole pipeline
+    for step in &pipeline.steps {
+        match state.steps[&step.id].status {
+            StepStatus::Running => {
+                let timeout_secs = step_timeout(pipeline, step, cfg);
+                let stale = cfg
+                    .reclaim_stale
+                    .then(|| stale_reason(&state.steps[&step.id], timeout_secs, cfg))
+                    .flatten();
+                if let Some(reason) = stale {
+                    eprintln!(
+                        "[{}] step '{}' was left running ({}) — resetting to pending",
+                        pipeline_name, step.id, reason
+                    );
+                    let step_state = state.steps.get_mut(&step.id).unwrap();
+                    step_state.status = StepStatus::Pending;
+                    step_state.pid = None;
+                    step_state.error_message = Some(format!("reclaimed: {}", reason));
+                    if !dry_run {
+                        state::save(&state_file, &state)?;
+                    }
+                    continue;
+                }
+
+                note(
+                    opts,
+                    format!(
+                        "[{}] step '{}' is already running — exiting",
+                        pipeline_name, step.id
+                    ),
+                );
+                return Ok(None);
+            }
+            StepStatus::Failed => {
+                note(
+                    opts,
+                    format!(
+                        "[{}] step '{}' is in failed state — skipping pipeline",
+                        pipeline_name, step.id
+                    ),
+                );
+                return Ok(Some(Vec::new()));
+            }
+            StepStatus::Pending | StepStatus::Completed | StepStatus::Skipped => {}
+        }
+    }
+
+    // Take the earliest declared pending steps whose dependencies are met
+    let mut any_pending = false;
+    let mut tickets = Vec::new();
+    for (i, step) in pipeline.steps.iter().enumerate() {
+        if tickets.len() == max {
+            break;
+        }
+        if state.steps[&step.id].status != StepStatus::Pending {
+            continue;
+        }
+        any_pending = true;
+
+        if !needs_met(pipeline, &state, i) {
+            continue;
+        }
+
+        // A step that failed and is waiting to be retried
+        let step_state = &state.steps[&step.id];
+        if step_state.attempts > 0
+            && step_state
+                .secs_since_finished()
+                .is_some_and(|secs| secs < step.retry_delay_secs)
+        {
+            note(
+                opts,
+                format!(
+                    "[{}] step '{}' is waiting {}s before retrying",
+                    pipeline_name, step.id, step.retry_delay_secs
+                ),
+            );
+            continue;
+        }
+
+        // Declared inputs are checked before the step is claimed, so a
+        // missing one is reported as such rather than as a failed read
+        let workspace = workspace_dir(pipeline_dir, pipeline, Some(&state));
+        let mut preflight = None;
+        if let Some(input) = step
+            .inputs
+            .iter()
+            .find(|input| !workspace.join(input).exists())
+        {
+            let problem = format!("requires input '{}' which is missing", input);
+            if cfg.missing_inputs == MissingInputs::Wait {
+                eprintln!(
+                    "[{}] step '{}' {} — waiting",
+                    pipeline_name, step.id, problem
+                );
+                continue;
+            }
+            if dry_run {
+                return Err(CronclawError::StepFailed {
+                    id: step.id.clone(),
+                    code: None,
+                    message: format!("[{}] step '{}' {}", pipeline_name, step.id, problem),
+                });
+            }
+            preflight = Some(problem);
+        }
+
+        // With `min_free_bytes` set, a full or read-only workspace fails the
+        // step before it starts rather than partway through
+        if let Some(min_free) = cfg.min_free_bytes
+            && !dry_run
+            && preflight.is_none()
+            && let Err(problem) = check_workspace(&workspace, min_free)
+        {
+            preflight = Some(problem);
+        }
+
+        let mut timeout_secs = opts
+            .step_timeout_override
+            .unwrap_or_else(|| step_timeout(pipeline, step, cfg));
+        if opts.step_timeout_override.is_some() {
+            note(
+                opts,
+                format!(
+                    "[{}] step '{}': timeout overridden to {}s",
+                    pipeline_name, step.id, timeout_secs
+                ),
+            );
+        } else if let Some(requested) = step.timeout.or(pipeline.default_timeout)
+            && requested > timeout_secs
+        {
+            eprintln!(
+                "warning: [{}] step '{}': timeout {}s is over max_timeout, using {}s",
+                pipeline_name, step.id, requested, timeout_secs
+            );
+        }
+        let left = deadline_left(step, step_state);
+        if let Some(left) = left {
+            timeout_secs = timeout_secs.min(left.max(1));
+        }
+        tickets.push(Ticket {
+            step_index: i,
+            attempt: step_state.attempts + 1,
+            timeout_secs,
+            deadline: left.map(|left| Instant::now() + Duration::from_secs(left)),
+            preflight,
+        });
+        // A step that can't start fails as soon as it is run, which may
+        // block the pipeline, so nothing else is claimed alongside it
+        if tickets.last().is_some_and(|t| t.preflight.is_some()) {
+            tickets.drain(..tickets.len() - 1);
+            break;
+        }
+    }
+
+    // Mark the claims and save them while we still hold the lock (released
+    // when lock_file is dropped on return)
+    if !tickets.is_empty() {
+        if !dry_run {
+            for ticket in &tickets {
+                let id = &pipeline.steps[ticket.step_index].id;
+                state.steps.get_mut(id).unwrap().start();
+            }
+            state::save(&state_file, &state)?;
+        }
+        return Ok(Some(tickets));
+    }
+
+    if any_pending {
+        note(
+            opts,
+            format!("[{}] no pending step is ready to run", pipeline_name),
+        );
+    } else {
+        note(
+            opts,
+            format!("[{}] pipeline already completed", pipeline_name),
+        );
+    }
+    Ok(Some(Vec::new()))
+}
+
+/// Put one step back to `pending` under the state lock, keeping the rest of
+/// the pipeline's state. With `failed_only`, a step that isn't failed is left
+/// alone. Returns whether the step was reset.
+pub fn reset_step(
+    pipeline_dir: &Path,
+    step_id: &str,
+    failed_only: bool,
+) -> Result<bool, CronclawError> {
+    let pipeline_name = pipeline_dir.file_name().unwrap().to_string_lossy();
+    let _lock_file = lock_state(pipeline_dir, &pipeline_name)?;
+
+    let state_path = pipeline_dir.join("state.json");
+    let Some(mut state) = state::load(&state_path)? else {
+        // No state yet: every step is already pending
+        return Ok(false);
+    };
+    let step_state = state
+        .steps
+        .get_mut(step_id)
+        .ok_or_else(|| format!("[{}] no step '{}' in state", pipeline_name, step_id))?;
+    if step_state.status == StepStatus::Pending
+        || (failed_only && step_state.status != StepStatus::Failed)
+    {
+        return Ok(false);
+    }
+
+    *step_state = StepState::new();
+    state::save(&state_path, &state)?;
+    Ok(true)
+}
+
+/// Give a step that has used up its attempts, or is waiting to retry, a
+/// fresh retry budget: its attempt count goes back to 0 and it is `pending`,
+/// due on the next tick. Only that step's state is rewritten, under the
+/// state lock. Returns its status and attempt count from before, or None if
+/// it had neither failed nor been retried, leaving it as it was.
+pub fn retry_step(
+    pipeline_dir: &Path,
+    step_id: &str,
+) -> Result<Option<(StepStatus, u32)>, CronclawError> {
+    let pipeline_name = pipeline_dir.file_name().unwrap().to_string_lossy();
+    let _lock_file = lock_state(pipeline_dir, &pipeline_name)?;
+
+    let state_path = pipeline_dir.join("state.json");
+    let Some(mut state) = state::load(&state_path)? else {
+        return Ok(None);
+    };
+    let step_state = state
+        .steps
+        .get_mut(step_id)
+        .ok_or_else(|| format!("[{}] no step '{}' in state", pipeline_name, step_id))?;
+    let retryable = match step_state.status {
+        StepStatus::Failed => true,
+        StepStatus::Pending => step_state.attempts > 0,
+        _ => false,
+    };
+    if !retryable {
+        return Ok(None);
+    }
+
+    let before = (step_state.status.clone(), step_state.attempts);
+    step_state.status = StepStatus::Pending;
+    step_state.attempts = 0;
+    step_state.pid = None;
+    step_state.first_started_at = None;
+    state::save(&state_path, &state)?;
+    Ok(Some(before))
+}
+
+/// Marker file that keeps `run_pipeline` from advancing a pipeline while it
+/// exists. Kept apart from state.json so a reset doesn't resume the pipeline.
+pub fn paused_path(pipeline_dir: &Path) -> PathBuf {
+    pipeline_dir.join("paused")
+}
+
+pub fn is_paused(pipeline_dir: &Path) -> bool {
+    paused_path(pipeline_dir).exists()
+}
+
+/// Pause or resume a pipeline. Its steps keep their state either way.
+/// Returns false if it already was paused, or not paused, as asked.
+pub fn set_paused(pipeline_dir: &Path, paused: bool) -> Result<bool, CronclawError> {
+    let path = paused_path(pipeline_dir);
+    if is_paused(pipeline_dir) == paused {
+        return Ok(false);
+    }
+    let result = if paused {
+        fs::write(&path, "")
+    } else {
+        fs::remove_file(&path)
+    };
+    result.map_err(|e| CronclawError::Io(format!("failed to update {}: {}", path.display(), e)))?;
+    Ok(true)
+}
+
+/// Rewind a pipeline to `step_id`: that step and every step declared after
+/// it go back to `pending` under the state lock, while earlier steps keep
+/// their state. Returns the ids of the steps that were reset.
+pub fn reset_from(
+    pipeline_dir: &Path,
+    pipeline: &Pipeline,
+    step_id: &str,
+) -> Result<Vec<String>, CronclawError> {
+    let pipeline_name = pipeline_dir.file_name().unwrap().to_string_lossy();
+    let start = pipeline
+        .steps
+        .iter()
+        .position(|s| s.id == step_id)
+        .ok_or_else(|| format!("[{}] no step '{}' in pipeline", pipeline_name, step_id))?;
+
+    let _lock_file = lock_state(pipeline_dir, &pipeline_name)?;
+    let state_path = pipeline_dir.join("state.json");
+    let Some(mut state) = state::load(&state_path)? else {
+        return Ok(Vec::new());
+    };
+
+    let mut reset = Vec::new();
+    for step in &pipeline.steps[start..] {
+        if let Some(step_state) = state.steps.get_mut(&step.id)
+            && step_state.status != StepStatus::Pending
+        {
+            *step_state = StepState::new();
+            reset.push(step.id.clone());
+        }
+    }
+    if !reset.is_empty() {
+        state::save(&state_path, &state)?;
+    }
+    Ok(reset)
+}
+
+/// Load the pipeline's state, creating it and the workspace on the first run
+/// (only in memory with `dry_run`). Call with the state lock held.
+fn load_or_create_state(
+    pipeline_dir: &Path,
+    pipeline: &Pipeline,
+    dry_run: bool,
+) -> Result<State, CronclawError> {
+    let state_file = pipeline_dir.join("state.json");
+    let loaded = state::load(&state_file)?;
+    match loaded {
+        Some(s) => Ok(s),
+        None if dry_run => Ok(State::from_pipeline(pipeline)),
+        None => {
+            fs::create_dir_all(pipeline_dir.join(&pipeline.workspace))
+                .map_err(|e| format!("failed to create workspace: {}", e))?;
+            let mut s = State::from_pipeline(pipeline);
+            // A new state file is a new run
+            if pipeline.workspace_mode == WorkspaceMode::Isolated {
+                s.run_workspace = Some(create_run_workspace(pipeline_dir, pipeline)?);
+            }
+            state::save(&state_file, &s)?;
+            Ok(s)
+        }
+    }
+}
+
+/// The directory a pipeline's steps work in: its `workspace`, or with
+/// `workspace_mode: isolated` the current run's directory inside it. Before
+/// the first run of an isolated pipeline, that is where `latest` will point.
+pub fn workspace_dir(pipeline_dir: &Path, pipeline: &Pipeline, state: Option<&State>) -> PathBuf {
+    let workspace = pipeline_dir.join(&pipeline.workspace);
+    if pipeline.workspace_mode == WorkspaceMode::Shared {
+        return workspace;
+    }
+    match state.and_then(|s| s.run_workspace.as_deref()) {
+        Some(run) => workspace.join(run),
+        None => workspace.join("latest"),
+    }
+}
+
+/// `workspace_dir`, reading the run from state.json when it's needed.
+fn current_workspace(pipeline_dir: &Path, pipeline: &Pipeline) -> Result<PathBuf, CronclawError> {
+    if pipeline.workspace_mode == WorkspaceMode::Shared {
+        return Ok(workspace_dir(pipeline_dir, pipeline, None));
+    }
+    let state = state::load(&pipeline_dir.join("state.json"))?;
+    Ok(workspace_dir(pipeline_dir, pipeline, state.as_ref()))
+}
+
+/// Make a fresh directory for a new run of an isolated pipeline, named for
+/// when it started, and point `latest` in the workspace at it. Earlier runs'
+/// directories are left for inspection. Returns the new directory's name.
+fn create_run_workspace(pipeline_dir: &Path, pipeline: &Pipeline) -> Result<String, String> {
+    let workspace = pipeline_dir.join(&pipeline.workspace);
+    let stamp = Local::now().format("%Y%m%d-%H%M%S").to_string();
+    // Runs reset within the same second still get a directory each
+    let name = (1..)
+        .map(|n| match n {
+            1 => stamp.clone(),
+            n => format!("{}-{}", stamp, n),
+        })
+        .find(|name| !workspace.join(name).exists())
+        .unwrap();
+    fs::create_dir(workspace.join(&name))
+        .map_err(|e| format!("failed to create run workspace '{}': {}", name, e))?;
+
+    let latest = workspace.join("latest");
+    if latest.symlink_metadata().is_ok() {
+        let _ = fs::remove_file(&latest);
+    }
+    if let Err(e) = std::os::unix::fs::symlink(&name, &latest) {
+        eprintln!(
+            "warning: failed to link {} to run '{}': {}",
+            latest.display(),
+            name,
+            e
+        );
+    }
+    Ok(name)
+}
+
+/// Count this invocation in state, scheduled or not, so `status` can show
+/// whether cron is firing at all.
+fn record_run(pipeline_dir: &Path, pipeline: &Pipeline) -> Result<(), CronclawError> {
+    let pipeline_name = pipeline_dir.file_name().unwrap().to_string_lossy();
+    let _lock_file = lock_state(pipeline_dir, &pipeline_name)?;
+    let mut state = load_or_create_state(pipeline_dir, pipeline, false)?;
+    state.tick_count += 1;
+    state.last_run_at = Some(state::now());
+    state::save(&pipeline_dir.join("state.json"), &state)
+}
+
+/// For a pipeline with a `schedule` or `watch`, decide whether this
+/// invocation is due: a scheduled time has passed since its last tick, and
+/// one of its watched files has changed since then. A pipeline that has never
+/// ticked is due straight away. A due tick is recorded in state, along with
+/// the watched files' modification times.
+fn claim_tick(
+    pipeline_dir: &Path,
+    pipeline: &Pipeline,
+    opts: &RunOptions,
+) -> Result<bool, CronclawError> {
+    let pipeline_name = pipeline_dir.file_name().unwrap().to_string_lossy();
+    let schedule = pipeline
+        .schedule
+        .as_deref()
+        .map(crate::pipeline::parse_schedule)
+        .transpose()?;
+
+    let _lock_file = if opts.dry_run {
+        None
+    } else {
+        Some(lock_state(pipeline_dir, &pipeline_name)?)
+    };
+    let mut state = load_or_create_state(pipeline_dir, pipeline, opts.dry_run)?;
+
+    let last_tick = state
+        .last_tick
+        .as_deref()
+        .and_then(|t| DateTime::parse_from_rfc3339(t).ok());
+    if let (Some(schedule), Some(expr), Some(last_tick)) =
+        (&schedule, &pipeline.schedule, last_tick)
+    {
+        match schedule.after(&last_tick.with_timezone(&Local)).next() {
+            Some(next) if next <= Local::now() => {}
+            next => {
+                let next = next.map_or("never".to_string(), |t| {
+                    t.format("%Y-%m-%d %H:%M").to_string()
+                });
+                note(
+                    opts,
+                    format!("[{}] not due until {} ({})", pipeline_name, next, expr),
+                );
+                return Ok(false);
+            }
+        }
+    }
+
+    let mtimes = watched_mtimes(pipeline_dir, pipeline)
+        .map_err(|e| CronclawError::Io(format!("[{}] {}", pipeline_name, e)))?;
+    let changed = mtimes
+        .iter()
+        .any(|(path, mtime)| state.watched.get(path) != Some(mtime));
+    if last_tick.is_some() && !pipeline.watch.is_empty() && !changed {
+        note(
+            opts,
+            format!("[{}] no watched file has changed", pipeline_name),
+        );
+        return Ok(false);
+    }
+
+    if !opts.dry_run {
+        state.last_tick = Some(state::now());
+        state.watched.extend(mtimes);
+        state::save(&pipeline_dir.join("state.json"), &state)?;
+    }
+    Ok(true)
+}
+
+/// The modification time (RFC3339) of each of the pipeline's `watch` files,
+/// keyed by path as written. A missing file is left out, or is an error with
+/// `watch_missing: fail`.
+fn watched_mtimes(
+    pipeline_dir: &Path,
+    pipeline: &Pipeline,
+) -> Result<BTreeMap<String, String>, String> {
+    let mut mtimes = BTreeMap::new();
+    for path in &pipeline.watch {
+        match fs::metadata(pipeline_dir.join(path)).and_then(|m| m.modified()) {
+            Ok(modified) => {
+                mtimes.insert(path.clone(), DateTime::<Local>::from(modified).to_rfc3339());
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                if pipeline.watch_missing == WatchMissing::Fail {
+                    return Err(format!("watched file '{}' is missing", path));
+                }
+            }
+            Err(e) => return Err(format!("failed to read watched file '{}': {}", path, e)),
+        }
+    }
+    Ok(mtimes)
+}
+
+/// Check that `workspace`, created if need be, can be written to and has at
+/// least `min_free` bytes available.
+fn check_workspace(workspace: &Path, min_free: u64) -> Result<(), String> {
+    let probe = workspace.join(".cronclaw-write-check");
+    fs::create_dir_all(workspace)
+        .and_then(|()| File::create(&probe))
+        .and_then(|_| fs::remove_file(&probe))
+        .map_err(|e| format!("workspace {} is not writable: {}", workspace.display(), e))?;
+    let free = fs2::available_space(workspace).map_err(|e| {
+        format!(
+            "failed to read free space of workspace {}: {}",
+            workspace.display(),
+            e
+        )
+    })?;
+    if free < min_free {
+        return Err(format!(
+            "workspace {} has {} bytes free, under min_free_bytes of {}",
+            workspace.display(),
+            free,
+            min_free
+        ));
+    }
+    Ok(())
+}
+
+/// A step's timeout: its own override, else the pipeline's
+/// `default_timeout`, else the configured default, capped at `max_timeout`
+/// if that is set.
+fn step_timeout(pipeline: &Pipeline, step: &Step, cfg: &Config) -> u64 {
+    let timeout = step
+        .timeout
+        .or(pipeline.default_timeout)
+        .unwrap_or(cfg.timeout);
+    match cfg.max_timeout {
+        Some(max) => timeout.min(max),
+        None => timeout,
+    }
+}
+
+/// Seconds left of a step's `deadline_secs`, counted from its first attempt,
+/// or None if it has no deadline.
+fn deadline_left(step: &Step, step_state: &StepState) -> Option<u64> {
+    let deadline = step.deadline_secs?;
+    let elapsed = step_state.secs_since_first_started().unwrap_or(0);
+    Some(deadline.saturating_sub(elapsed))
+}
+
+/// Whether every step the step at `index` needs is done.
+fn needs_met(pipeline: &Pipeline, state: &State, index: usize) -> bool {
+    pipeline
+        .needs_of(index)
+        .iter()
+        .all(|id| state.steps.get(*id).is_some_and(|s| s.status.is_done()))
+}
+
+/// The step the next tick would run, ignoring retry delays: the earliest
+/// pending step whose dependencies are done. None while a step is running or
+/// failed, or if nothing is ready.
+pub fn next_step<'a>(pipeline: &'a Pipeline, state: &State) -> Option<&'a Step> {
+    let status = |step: &Step| state.steps.get(&step.id).map(|s| &s.status);
+    if pipeline
+        .steps
+        .iter()
+        .any(|s| matches!(status(s), Some(StepStatus::Running | StepStatus::Failed)))
+    {
+        return None;
+    }
+    pipeline
+        .steps
+        .iter()
+        .enumerate()
+        .find(|(i, s)| status(s) == Some(&StepStatus::Pending) && needs_met(pipeline, state, *i))
+        .map(|(_, s)| s)
+}
+
+/// Extra time past a step's timeout (and kill grace) before a running step
+/// whose runner is still alive is considered stale.
+const STALE_MARGIN_SECS: u64 = 60;
+
+/// Why a `Running` step looks abandoned, or None if it may still be in progress:
+/// either the runner process that claimed it is gone, or it has been running
+/// well past its timeout.
+fn stale_reason(step_state: &StepState, timeout_secs: u64, cfg: &Config) -> Option<String> {
+    if let Some(pid) = step_state.pid
+        && !process_alive(pid)
+    {
+        return Some(format!("runner pid {} is gone", pid));
+    }
+
+    let limit = timeout_secs + cfg.kill_grace_secs + STALE_MARGIN_SECS;
+    match step_state.secs_since_started() {
+        Some(elapsed) if elapsed > limit => Some(format!(
+            "running for {}s, past its {}s timeout",
+            elapsed, timeout_secs
+        )),
+        _ => None,
+    }
+}
+
+fn process_alive(pid: u32) -> bool {
+    // SAFETY: signal 0 only checks for existence and permission.
+    let rc = unsafe { libc::kill(pid as libc::pid_t, 0) };
+    rc == 0 || std::io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+}
+
+/// Advance a pipeline by one tick: run the next ready step, or with
+/// `max_parallel` above 1, every ready step up to that many at once. With
+/// `dry_run`, report the steps that would run and their resolved commands
+/// instead of running them, writing nothing.
+pub fn run_pipeline(
+    pipeline_dir: &Path,
+    cfg: &Config,
+    opts: &RunOptions,
+) -> Result<Ticked, CronclawError> {
+    let pipeline_file = pipeline_dir.join("pipeline.yaml");
+    let pipeline_name = pipeline_dir
+        .file_name()
+        .unwrap()
+        .to_string_lossy()
+        .to_string();
+
+    let mut pipeline = crate::pipeline::load(&pipeline_file)?;
+    pipeline
+        .set_vars(&opts.vars)
+        .map_err(|e| CronclawError::Parse(format!("[{}] {}", pipeline_name, e)))?;
+
+    // Checked before anything is recorded, so a paused pipeline is left
+    // exactly as it was, and ahead of the schedule, so a resumed pipeline
+    // that was due while paused runs straight away
+    if is_paused(pipeline_dir) {
+        note(opts, format!("[{}] paused, skipping", pipeline_name));
+        return Ok(Ticked::Done);
+    }
+    if !opts.dry_run {
+        record_run(pipeline_dir, &pipeline)?;
+    }
+    // Only known once the run's state exists
+    let workspace = current_workspace(pipeline_dir, &pipeline)?;
+    let tick = Tick {
+        pipeline_dir,
+        pipeline_name: &pipeline_name,
+        pipeline: &pipeline,
+        workspace: &workspace,
+        cfg,
+        opts,
+    };
+    if (pipeline.schedule.is_some() || !pipeline.watch.is_empty())
+        && !claim_tick(pipeline_dir, &pipeline, opts)?
+    {
+        return Ok(Ticked::Done);
+    }
+
+    // A skipped step doesn't use up the tick, so keep going until a step runs
+    loop {
+        if opts.stopped() {
+            return Ok(Ticked::Done);
+        }
+        // Acquire tickets: lock state, find ready steps, mark running, release lock
+        let Some(tickets) =
+            acquire_tickets(pipeline_dir, &pipeline, cfg, opts, cfg.max_parallel.max(1))?
+        else {
+            return Ok(Ticked::Busy);
+        };
+        if tickets.is_empty() {
+            return Ok(Ticked::Done);
+        }
+
+        if opts.dry_run {
+            for ticket in &tickets {
+                let step = &pipeline.steps[ticket.step_index];
+                println!(
+                    "[{}] dry run: would run step {}/{}: '{}' ({}){}",
+                    pipeline_name,
+                    ticket.step_index + 1,
+                    pipeline.steps.len(),
+                    step.id,
+                    step.step_type.name(),
+                    description_suffix(step)
+                );
+                print_step(&pipeline, step, &workspace, ticket.timeout_secs, cfg)
+                    .map_err(|e| format!("[{}] step '{}': {}", pipeline_name, step.id, e))?;
+            }
+            return Ok(Ticked::Done);
+        }
+
+        // Execute steps (no lock held — other pipelines and processes are free to run)
+        let outcomes: Vec<Result<Outcome, CronclawError>> = if tickets.len() == 1 {
+            vec![tick.run_step(&tickets[0])]
+        } else {
+            std::thread::scope(|scope| {
+                let handles: Vec<_> = tickets
+                    .iter()
+                    .map(|ticket| {
+                        let tick = &tick;
+                        scope.spawn(move || tick.run_step(ticket))
+                    })
+                    .collect();
+                handles.into_iter().map(|h| h.join().unwrap()).collect()
+            })
+        };
+
+        let mut ran = false;
+        let mut errors = Vec::new();
+        for outcome in outcomes {
+            match outcome {
+                Ok(Outcome::Ran) => ran = true,
+                Ok(Outcome::Skipped) => {}
+                Err(e) => errors.push(e),
+            }
+        }
+        if errors.len() == 1 {
+            return Err(errors.pop().unwrap());
+        }
+        if !errors.is_empty() {
+            return Err(CronclawError::Several(errors));
+        }
+        if ran {
+            return Ok(Ticked::Done);
+        }
+    }
+}
+
+/// What became of a claimed step.
+enum Outcome {
+    Ran,
+    Skipped,
+}
+
+/// Everything a tick needs to run one of its claimed steps.
+struct Tick<'a> {
+    pipeline_dir: &'a Path,
+    pipeline_name: &'a str,
+    pipeline: &'a Pipeline,
+    workspace: &'a Path,
+    cfg: &'a Config,
+    opts: &'a RunOptions,
+}
+
+impl Tick<'_> {
+    /// Run a claimed step and record how it went. Returns Err if the step
+    /// failed for good.
+    fn run_step(&self, ticket: &Ticket) -> Result<Outcome, CronclawError> {
+        let (pipeline, pipeline_name, opts) = (self.pipeline, self.pipeline_name, self.opts);
+        let step = &pipeline.steps[ticket.step_index];
+        let log = StepLog::new(self.pipeline_dir, &step.id, self.cfg.max_log_bytes);
+
+        let should_run = match (&ticket.preflight, &step.when) {
+            (Some(problem), _) => Err(StepFailure::from(problem.clone())),
+            (None, Some(condition)) => check_condition(
+                pipeline,
+                step,
+                condition,
+                self.workspace,
+                ticket.timeout_secs,
+                self.cfg,
+                opts.stop,
+            ),
+            (None, None) => Ok(true),
+        };
+
+        let result = match should_run {
+            Ok(true) => {
+                if opts.json {
+                    let mut event =
+                        Event::new("step_started", pipeline_name, Some(&step.id), "running");
+                    event.attempt = Some(ticket.attempt);
+                    event.emit();
+                } else {
+                    progress(
+                        opts,
+                        format!(
+                            "[{}] running step {}/{}: '{}' ({}){}",
+                            pipeline_name,
+                            ticket.step_index + 1,
+                            pipeline.steps.len(),
+                            step.id,
+                            step.step_type.name(),
+                            description_suffix(step)
+                        ),
+                    );
+                }
+                log.line(&format!("started (attempt {})", ticket.attempt));
+                execute_step(self, step, ticket.timeout_secs, &log)
+            }
+            Ok(false) => {
+                if opts.json {
+                    Event::new("step_skipped", pipeline_name, Some(&step.id), "skipped").emit();
+                } else {
+                    progress(
+                        opts,
+                        format!(
+                            "[{}] skipping step '{}': condition not met",
+                            pipeline_name, step.id
+                        ),
+                    );
+                }
+                log.line("skipped: condition not met");
+                let state = self.update_step(&step.id, |s| s.finish(StepStatus::Skipped))?;
+                if report_completion(pipeline, &state, pipeline_name, opts) {
+                    self.pipeline_completed(&state);
+                }
+                return Ok(Outcome::Skipped);
+            }
+            Err(failure) => Err(failure),
+        };
+        // Outputs that can't be promoted fail the step like a bad exit code
+        let result =
+            result.and_then(|()| promote_outputs(step, self.workspace).map_err(StepFailure::from));
+
+        match result {
+            Ok(hashes) => {
+                let state = self.update_step(&step.id, |s| {
+                    s.finish(StepStatus::Completed);
+                    s.output_hashes = hashes;
+                })?;
+                if opts.json {
+                    let mut event =
+                        Event::new("step_completed", pipeline_name, Some(&step.id), "completed");
+                    event.duration_secs = state.steps[&step.id].duration_secs;
+                    event.emit();
+                }
+                log.line("completed");
+                if report_completion(pipeline, &state, pipeline_name, opts) {
+                    self.pipeline_completed(&state);
+                }
+                Ok(Outcome::Ran)
+            }
+            Err(failure) if failure.interrupted => {
+                log.line(&format!("interrupted: {}", failure.message));
+                // The attempt doesn't count: the step runs again next tick
+                self.update_step(&step.id, |s| {
+                    s.attempts = ticket.attempt - 1;
+                    s.finish(StepStatus::Pending);
+                    s.error_message = Some(failure.message.clone());
+                })?;
+                if opts.json {
+                    let mut event =
+                        Event::new("step_interrupted", pipeline_name, Some(&step.id), "pending");
+                    event.attempt = Some(ticket.attempt);
+                    event.error = Some(&failure.message);
+                    event.emit();
+                }
+                Err(CronclawError::Interrupted {
+                    id: step.id.clone(),
+                    message: format!(
+                        "[{}] step '{}' {}, left pending",
+                        pipeline_name, step.id, failure.message
+                    ),
+                })
+            }
+            Err(mut failure) => {
+                log.line(&format!("failed: {}", failure.message));
+
+                // Leave the step pending while it still has retries left and
+                // its deadline leaves room for another attempt (or complete it
+                // anyway once they're spent, if failure is allowed)
+                let past_deadline = ticket.deadline.is_some_and(|deadline| {
+                    Instant::now() + Duration::from_secs(step.retry_delay_secs) >= deadline
+                });
+                let retries_left = ticket.attempt <= step.retries;
+                if retries_left && past_deadline {
+                    failure.message = format!(
+                        "{} (deadline of {}s reached, not retrying)",
+                        failure.message,
+                        step.deadline_secs.unwrap_or_default()
+                    );
+                }
+                let retry = retries_left && !past_deadline;
+                let status = if retry {
+                    StepStatus::Pending
+                } else if step.allow_failure {
+                    StepStatus::Completed
+                } else {
+                    StepStatus::Failed
+                };
+                let state = self.update_step(&step.id, |s| {
+                    s.attempts = ticket.attempt;
+                    s.finish(status.clone());
+                    s.error_message = Some(failure.message.clone());
+                    s.exit_code = failure.exit_code;
+                })?;
+                if opts.json {
+                    let mut event =
+                        Event::new("step_failed", pipeline_name, Some(&step.id), status.name());
+                    event.attempt = Some(ticket.attempt);
+                    event.duration_secs = state.steps[&step.id].duration_secs;
+                    event.exit_code = failure.exit_code;
+                    event.error = Some(&failure.message);
+                    event.emit();
+                }
+
+                if retry {
+                    eprintln!(
+                        "[{}] step '{}' failed (attempt {} of {}), will retry: {}",
+                        pipeline_name,
+                        step.id,
+                        ticket.attempt,
+                        step.retries + 1,
+                        failure.message
+                    );
+                    return Ok(Outcome::Ran);
+                }
+
+                if step.allow_failure {
+                    eprintln!(
+                        "[{}] step '{}' failed, continuing (allow_failure): {}",
+                        pipeline_name, step.id, failure.message
+                    );
+                    if report_completion(pipeline, &state, pipeline_name, opts) {
+                        self.pipeline_completed(&state);
+                    }
+                    return Ok(Outcome::Ran);
+                }
+
+                self.run_cleanup();
+                self.run_hook(Some((step, &failure)));
+                let message = format!(
+                    "[{}] step '{}' failed: {}",
+                    pipeline_name, step.id, failure.message
+                );
+                let id = step.id.clone();
+                Err(if failure.timed_out {
+                    CronclawError::Timeout { id, message }
+                } else {
+                    CronclawError::StepFailed {
+                        id,
+                        code: failure.exit_code,
+                        message,
+                    }
+                })
+            }
+        }
+    }
+
+    /// Update one step's state under the state lock. state.json is re-read
+    /// first so that steps finishing concurrently don't overwrite each other.
+    /// Returns the state as saved.
+    fn update_step(
+        &self,
+        step_id: &str,
+        update: impl FnOnce(&mut StepState),
+    ) -> Result<State, CronclawError> {
+        let state_file = self.pipeline_dir.join("state.json");
+        let _lock_file = lock_state(self.pipeline_dir, self.pipeline_name)?;
+
+        let mut state = state::load(&state_file)?.ok_or_else(|| {
+            format!(
+                "[{}] state.json was removed while step '{}' was running",
+                self.pipeline_name, step_id
+            )
+        })?;
+        let step_state = state.steps.get_mut(step_id).ok_or_else(|| {
+            format!(
+                "[{}] step '{}' is no longer in state.json",
+                self.pipeline_name, step_id
+            )
+        })?;
+        update(step_state);
+        state::save(&state_file, &state)?;
+        Ok(state)
+    }
+
+    /// Run the pipeline's `on_failure` cleanup steps, in order, after a step
+    /// has failed for good. Each is recorded under `cleanup` in state.json
+    /// rather than among the steps. A cleanup step failing is reported, but
+    /// the rest still run and the pipeline's own error stands.
+    fn run_cleanup(&self) {
+        for step in self.pipeline.cleanup_steps() {
+            let log = StepLog::new(self.pipeline_dir, &step.id, self.cfg.max_log_bytes);
+            if !self.opts.json {
+                progress(
+                    self.opts,
+                    format!(
+                        "[{}] running cleanup step '{}'",
+                        self.pipeline_name, step.id
+                    ),
+                );
+            }
+            log.line("started (cleanup)");
+
+            let mut step_state = StepState::new();
+            step_state.start();
+            let result = execute_step(
+                self,
+                step,
+                step_timeout(self.pipeline, step, self.cfg),
+                &log,
+            );
+            match result {
+                Ok(()) => {
+                    step_state.finish(StepStatus::Completed);
+                    log.line("completed");
+                }
+                Err(failure) => {
+                    step_state.finish(StepStatus::Failed);
+                    step_state.exit_code = failure.exit_code;
+                    log.line(&format!("failed: {}", failure.message));
+                    eprintln!(
+                        "warning: [{}] cleanup step '{}' failed: {}",
+                        self.pipeline_name, step.id, failure.message
+                    );
+                    step_state.error_message = Some(failure.message);
+                }
+            }
+            if let Err(e) = self.record_cleanup(&step.id, step_state) {
+                eprintln!("warning: [{}] {}", self.pipeline_name, e);
+            }
+        }
+    }
+
+    /// Store how a cleanup step went, under the state lock.
+    fn record_cleanup(&self, step_id: &str, step_state: StepState) -> Result<(), CronclawError> {
+        let state_file = self.pipeline_dir.join("state.json");
+        let _lock_file = lock_state(self.pipeline_dir, self.pipeline_name)?;
+        let mut state = state::load(&state_file)?.ok_or_else(|| {
+            format!(
+                "[{}] state.json was removed while cleaning up",
+                self.pipeline_name
+            )
+        })?;
+        state.cleanup.insert(step_id.to_string(), step_state);
+        state::save(&state_file, &state)
+    }
+
+    /// Record the run that just completed in the pipeline's history, then
+    /// run the `on_success` hook. Neither can fail the pipeline any more.
+    fn pipeline_completed(&self, state: &State) {
+        let run = history::Run::from_state(state);
+        if let Err(e) = history::append(self.pipeline_dir, &run) {
+            eprintln!("warning: [{}] {}", self.pipeline_name, e);
+        }
+        self.run_hook(None);
+    }
+
+    /// Run the `on_success` hook, or `on_failure` with the step that failed,
+    /// taken from the pipeline or else the config. The hook only gets reported
+    /// if it fails; the pipeline's outcome stands either way.
+    fn run_hook(&self, failed: Option<(&Step, &StepFailure)>) {
+        let (name, status, hook) = match failed {
+            None => (
+                "on_success",
+                "completed",
+                self.pipeline
+                    .on_success
+                    .as_ref()
+                    .or(self.cfg.on_success.as_ref()),
+            ),
+            // Cleanup steps take the config hook's place too
+            Some(_) => (
+                "on_failure",
+                "failed",
+                match &self.pipeline.on_failure {
+                    Some(_) => self.pipeline.on_failure_command(),
+                    None => self.cfg.on_failure.as_ref(),
+                },
+            ),
+        };
+        let Some(hook) = hook else {
+            return;
+        };
+
+        let mut cmd = Command::new(&self.cfg.shell);
+        cmd.arg("-c")
+            .arg(hook)
+            .current_dir(self.pipeline_dir)
+            .env("CRONCLAW_PIPELINE", self.pipeline_name)
+            .env("CRONCLAW_STATUS", status);
+        if let Some((step, failure)) = failed {
+            cmd.env("CRONCLAW_STEP", &step.id)
+                .env("CRONCLAW_ERROR", &failure.message);
+            if let Some(code) = failure.exit_code {
+                cmd.env("CRONCLAW_EXIT_CODE", code.to_string());
+            }
+        }
+
+        let capture = Capture {
+            stdout: StreamCapture::quiet(self.cfg.max_output_bytes),
+            stderr: StreamCapture::quiet(self.cfg.max_output_bytes),
+        };
+        let problem = match spawn_with_timeout(
+            &mut cmd,
+            Duration::from_secs(self.cfg.timeout),
+            self.cfg.kill_grace_secs,
+            None,
+            &capture,
+            self.opts.stop,
+        ) {
+            Ok(output) if output.status.success() => return,
+            Ok(output) => match String::from_utf8_lossy(&output.stderr).trim() {
+                "" => output.status.to_string(),
+                stderr => format!("{}: {}", output.status, stderr),
+            },
+            Err(failure) => failure.message,
+        };
+        eprintln!(
+            "warning: [{}] {} hook failed: {}",
+            self.pipeline_name, name, problem
+        );
+    }
+}
+
+/// Print the "pipeline completed" line once every step is done, naming any
+/// steps whose failure was allowed. Returns whether the pipeline is done.
+fn report_completion(
+    pipeline: &Pipeline,
+    state: &State,
+    pipeline_name: &str,
+    opts: &RunOptions,
+) -> bool {
+    let all_done = pipeline.steps.iter().all(|s| {
+        state
+            .steps
+            .get(&s.id)
+            .map(|ss| ss.status.is_done())
+            .unwrap_or(false)
+    });
+    if !all_done {
+        return false;
+    }
+
+    let failed: Vec<&str> = pipeline
+        .steps
+        .iter()
+        .filter(|s| s.allow_failure && state.steps[&s.id].error_message.is_some())
+        .map(|s| s.id.as_str())
+        .collect();
+    if opts.json {
+        Event::new("pipeline_completed", pipeline_name, None, "completed").emit();
+    } else if failed.is_empty() {
+        progress(opts, format!("[{}] pipeline completed", pipeline_name));
+    } else {
+        progress(
+            opts,
+            format!(
+                "[{}] pipeline completed (allowed failures: {})",
+                pipeline_name,
+                failed.join(", ")
+            ),
+        );
+    }
+    true
+}
+
+/// A script written to the system temp dir, removed again when dropped.
+struct TempScript(PathBuf);
+
+/// Numbers the scripts written by this process, as pipelines ticked side by
+/// side may run steps with the same id at once.
+static SCRIPT_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+impl TempScript {
+    fn create(name: &str, content: &str) -> Result<Self, String> {
+        let path = std::env::temp_dir().join(format!(
+            "cronclaw-{}-{}-{}",
+            std::process::id(),
+            SCRIPT_COUNTER.fetch_add(1, Ordering::Relaxed),
+            name
+        ));
+        let error =
+            |e: std::io::Error| format!("failed to write script '{}': {}", path.display(), e);
+        let mut file = fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&path)
+            .map_err(error)?;
+        // Removed on drop from here on, even if the write fails
+        let script = TempScript(path.clone());
+        file.write_all(content.as_bytes()).map_err(error)?;
+        Ok(script)
+    }
+}
+
+impl Drop for TempScript {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.0);
+    }
+}
+
+/// Print what `execute_step` would run, with templates resolved.
+fn print_step(
+    pipeline: &Pipeline,
+    step: &Step,
+    workspace: &Path,
+    timeout_secs: u64,
+    cfg: &Config,
+) -> Result<(), CronclawError> {
+    let templates = TemplateContext {
+        pipeline: Some(pipeline),
+    };
+
+    println!("  timeout: {}s", timeout_secs);
+    if let Some(dir) = &step.working_dir {
+        println!("  working_dir: {}", dir);
+    }
+    if step.step_type != StepType::Agent {
+        if let Some(text) = &step.stdin {
+            let text = resolve_templates_with(text, workspace, &templates)
+                .map_err(|e| format!("stdin: {}", e))?;
+            println!("  stdin:\n{}", indent(&text));
+        } else if let Some(file) = &step.stdin_file {
+            println!("  stdin_file: {}", file);
+        }
+    }
+    if let Some(condition) = &step.when {
+        println!("  when: {}", condition);
+    }
+    match step.step_type {
+        StepType::Bash => {
+            let shell = step.shell.as_deref().unwrap_or(&cfg.shell);
+            println!("  shell: {}", shell);
+            println!("  script:\n{}", indent(step.bash.as_ref().unwrap()));
+        }
+        StepType::Python => {
+            let source =
+                resolve_templates_with(step.python.as_ref().unwrap(), workspace, &templates)?;
+            println!("  script:\n{}", indent(&source));
+        }
+        StepType::Agent => {
+            let prompt = agent_prompt(step, workspace, &templates)?;
+            println!("  agent: {}", step.agent.as_ref().unwrap());
+            if let Some(gateway) = &step.gateway {
+                println!("  gateway: {}", gateway);
+            }
+            if let Some(model) = &step.model {
+                println!("  model: {}", model);
+            }
+            if !step.extra_args.is_empty() {
+                println!("  extra_args: {}", step.extra_args.join(" "));
+            }
+            println!("  prompt:\n{}", indent(&prompt));
+        }
+        StepType::Http => {
+            let request = HttpRequest::build(step, workspace, &templates)?;
+            println!("  request: {} {}", request.method, request.url);
+            for (name, value) in &request.headers {
+                println!("  header: {}: {}", name, value);
+            }
+            if let Some(body) = &request.body {
+                println!("  body:\n{}", indent(body));
+            }
+        }
+        StepType::Noop => println!("  noop: nothing to run"),
+    }
+
+    for (key, value) in pipeline.env_for(step) {
+        let value = resolve_templates_with(&value, workspace, &templates)
+            .map_err(|e| format!("env '{}': {}", key, e))?;
+        println!("  env: {}={}", key, value);
+    }
+    Ok(())
+}
+
+/// Print every step of a pipeline the way `--dry-run` prints the next one:
+/// its dependencies, effective timeout, what it would run with templates
+/// resolved against the current workspace, and its inputs and outputs. A
+/// template that can't be resolved yet, e.g. one reading an output an earlier
+/// step hasn't produced, is noted rather than treated as an error. Runs
+/// nothing and doesn't touch state.
+pub fn explain(pipeline_dir: &Path, cfg: &Config) -> Result<(), CronclawError> {
+    let pipeline = crate::pipeline::load(&pipeline_dir.join("pipeline.yaml"))?;
+    let state = state::load(&pipeline_dir.join("state.json"))?;
+    let workspace = workspace_dir(pipeline_dir, &pipeline, state.as_ref());
+
+    println!("workspace: {}", workspace.display());
+    if let Some(expr) = &pipeline.schedule {
+        println!("schedule: {}", expr);
+    }
+    if !pipeline.watch.is_empty() {
+        println!("watch: {}", pipeline.watch.join(", "));
+    }
+    for (i, step) in pipeline.steps.iter().enumerate() {
+        let status = state
+            .as_ref()
+            .and_then(|s| s.steps.get(&step.id))
+            .map_or("pending", |s| s.status.name());
+        println!();
+        println!(
+            "step {}/{}: '{}' ({}, {}){}",
+            i + 1,
+            pipeline.steps.len(),
+            step.id,
+            step.step_type.name(),
+            status,
+            description_suffix(step)
+        );
+        describe_step(&pipeline, i, &workspace, cfg);
+    }
+    Ok(())
+}
 
-    // Load or create state (while holding lock)
-    let mut state = match state::load(&state_file)? {
-        Some(s) => s,
-        None => {
-            fs::create_dir_all(&workspace)
-                .map_err(|e| format!("failed to create workspace: {}", e))?;
-            let s = State::from_pipeline(pipeline);
-            state::save(&state_file, &s)?;
-            s
-        }
+/// Print everything about one step: what `explain` prints for it, then its
+/// state from the last run (attempts, timing, last error) and whether each
+/// of its outputs exists in the workspace. Runs nothing and doesn't touch
+/// state.
+pub fn show(pipeline_dir: &Path, step_id: &str, cfg: &Config) -> Result<(), CronclawError> {
+    let pipeline = crate::pipeline::load(&pipeline_dir.join("pipeline.yaml"))?;
+    let Some(i) = pipeline.steps.iter().position(|s| s.id == step_id) else {
+        return Err(format!("no step '{}' in this pipeline", step_id).into());
     };
+    let step = &pipeline.steps[i];
+    let state = state::load(&pipeline_dir.join("state.json"))?;
+    let step_state = state.as_ref().and_then(|s| s.steps.get(step_id));
+    let workspace = workspace_dir(pipeline_dir, &pipeline, state.as_ref());
 
-    // Verify state matches pipeline
-    {
-        let pipeline_ids: std::collections::BTreeSet<&str> =
-            pipeline.steps.iter().map(|s| s.id.as_str()).collect();
-        let state_ids: std::collections::BTreeSet<&str> =
-            state.steps.keys().map(|s| s.as_str()).collect();
+    println!(
+        "step '{}' ({}){}",
+        step.id,
+        step.step_type.name(),
+        description_suffix(step)
+    );
+    describe_step(&pipeline, i, &workspace, cfg);
 
-        if pipeline_ids != state_ids {
-            return Err(format!(
-                "[{}] state file mismatch — steps in pipeline.yaml don't match state.json. \
-                 Consider resetting the pipeline with `cronclaw reset {}`.",
-                pipeline_name, pipeline_name
-            ));
+    println!();
+    let Some(s) = step_state else {
+        println!("status: pending (not run yet)");
+        return Ok(());
+    };
+    println!("status: {}", s.status.name());
+    println!("attempts: {} of {}", s.attempts, step.retries + 1);
+    if let Some(started) = &s.started_at {
+        println!("started: {}", started);
+    }
+    if let Some(finished) = &s.finished_at {
+        println!("finished: {}", finished);
+    }
+    if let Some(secs) = s.duration_secs {
+        println!("duration: {}s", secs);
+    }
+    if let Some(code) = s.exit_code {
+        println!("exit code: {}", code);
+    }
+    if let Some(error) = &s.error_message {
+        println!("error:\n{}", indent(error));
+    }
+    for output in &step.outputs {
+        match fs::metadata(workspace.join(&output.path)) {
+            Ok(meta) => println!("file: {} ({} bytes)", output.path, meta.len()),
+            Err(_) => println!("file: {} (missing)", output.path),
         }
     }
+    Ok(())
+}
 
-    // Find the next actionable step
-    for (i, step) in pipeline.steps.iter().enumerate() {
-        let step_state = &state.steps[&step.id];
-
-        match step_state.status {
-            StepStatus::Completed => continue,
-            StepStatus::Running => {
-                if verbose {
-                    println!(
-                        "[{}] step '{}' is already running — exiting",
-                        pipeline_name, step.id
-                    );
-                }
-                return Ok(None);
-            }
-            StepStatus::Failed => {
-                if verbose {
-                    println!(
-                        "[{}] step '{}' is in failed state — skipping pipeline",
-                        pipeline_name, step.id
-                    );
-                }
-                return Ok(None);
-            }
-            StepStatus::Pending => {
-                // Mark as running and save while we still hold the lock
-                state.steps.get_mut(&step.id).unwrap().status = StepStatus::Running;
-                state::save(&state_file, &state)?;
+/// The indented details `explain` and `show` print under a step's heading.
+fn describe_step(pipeline: &Pipeline, i: usize, workspace: &Path, cfg: &Config) {
+    let step = &pipeline.steps[i];
+    let needs = pipeline.needs_of(i);
+    if !needs.is_empty() {
+        println!("  needs: {}", needs.join(", "));
+    }
+    if step.retries > 0 {
+        println!("  retries: {}", step.retries);
+    }
+    if let Some(deadline) = step.deadline_secs {
+        println!("  deadline: {}s", deadline);
+    }
+    if step.allow_failure {
+        println!("  allow_failure: true");
+    }
+    if let Err(e) = print_step(
+        pipeline,
+        step,
+        workspace,
+        step_timeout(pipeline, step, cfg),
+        cfg,
+    ) {
+        println!("  (not resolvable yet: {})", e);
+    }
+    for input in &step.inputs {
+        println!("  input: {}", input);
+    }
+    for output in &step.outputs {
+        let source = match output.from {
+            Some(OutputSource::Stdout) => "stdout via ",
+            None => "",
+        };
+        println!(
+            "  output: {} ({}{} -> {})",
+            output.name, source, output.tmp, output.path
+        );
+    }
+}
 
-                // Lock released when lock_file is dropped here
-                return Ok(Some(Ticket {
-                    step_index: i,
-                    step_id: step.id.clone(),
-                    timeout_secs: step.timeout.unwrap_or(cfg.timeout),
-                    state,
-                }));
-            }
-        }
+/// " — <description>" for a step that has one, to follow its id in output.
+fn description_suffix(step: &Step) -> String {
+    match &step.description {
+        Some(description) => format!(" — {}", description.trim()),
+        None => String::new(),
     }
+}
 
-    // All steps completed
-    if verbose {
-        println!("[{}] pipeline already completed", pipeline_name);
+fn indent(text: &str) -> String {
+    text.trim_end()
+        .lines()
+        .map(|line| format!("    {}", line))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Run a step's `when` condition with the step's shell and environment.
+/// Returns whether it exited 0.
+fn check_condition(
+    pipeline: &Pipeline,
+    step: &Step,
+    condition: &str,
+    workspace: &Path,
+    timeout_secs: u64,
+    cfg: &Config,
+    stop: Option<&AtomicBool>,
+) -> Result<bool, StepFailure> {
+    let shell = step.shell.as_deref().unwrap_or(&cfg.shell);
+    let mut cmd = Command::new(shell);
+    cmd.arg("-c")
+        .arg(condition)
+        .current_dir(step_dir(step, workspace)?);
+    set_env(&mut cmd, pipeline, step, workspace)?;
+
+    let capture = Capture {
+        stdout: StreamCapture::quiet(cfg.max_output_bytes),
+        stderr: StreamCapture::quiet(cfg.max_output_bytes),
+    };
+    let output = spawn_with_timeout(
+        &mut cmd,
+        Duration::from_secs(timeout_secs),
+        cfg.kill_grace_secs,
+        None,
+        &capture,
+        stop,
+    )
+    .map_err(|e| StepFailure {
+        message: format!("when: {}", e.message),
+        ..e
+    })?;
+    Ok(output.status.success())
+}
+
+/// What to feed a step's stdin: its `stdin` text with templates resolved,
+/// or the contents of its `stdin_file`.
+fn step_input(
+    step: &Step,
+    workspace: &Path,
+    templates: &TemplateContext,
+) -> Result<Option<Vec<u8>>, String> {
+    if let Some(text) = &step.stdin {
+        let text = resolve_templates_with(text, workspace, templates)
+            .map_err(|e| format!("stdin: {}", e))?;
+        return Ok(Some(text.into_bytes()));
+    }
+    if let Some(file) = &step.stdin_file {
+        let path = workspace_path(workspace, file).map_err(|e| format!("stdin_file: {}", e))?;
+        let bytes = fs::read(&path)
+            .map_err(|e| format!("stdin_file: failed to read '{}': {}", path.display(), e))?;
+        return Ok(Some(bytes));
     }
     Ok(None)
 }
 
-pub fn run_pipeline(pipeline_dir: &Path, cfg: &Config, verbose: bool) -> Result<(), String> {
-    let pipeline_file = pipeline_dir.join("pipeline.yaml");
-    let state_file = pipeline_dir.join("state.json");
-    let pipeline_name = pipeline_dir
-        .file_name()
-        .unwrap()
-        .to_string_lossy()
-        .to_string();
-
-    let pipeline = crate::pipeline::load(&pipeline_file)?;
-    let workspace = pipeline_dir.join(&pipeline.workspace);
+/// An agent step's prompt, from `prompt` or the contents of its
+/// `prompt_file`, with templates resolved.
+fn agent_prompt(
+    step: &Step,
+    workspace: &Path,
+    templates: &TemplateContext,
+) -> Result<String, CronclawError> {
+    let Some(file) = &step.prompt_file else {
+        return resolve_templates_with(step.prompt.as_ref().unwrap(), workspace, templates);
+    };
+    let path = workspace_path(workspace, file).map_err(|e| format!("prompt_file: {}", e))?;
+    let prompt = fs::read_to_string(&path)
+        .map_err(|e| format!("prompt_file: failed to read '{}': {}", path.display(), e))?;
+    resolve_templates_with(&prompt, workspace, templates)
+        .map_err(|e| CronclawError::Template(format!("prompt_file: {}", e)))
+}
 
-    // Acquire a ticket: lock state, find next step, mark running, release lock
-    let mut ticket = match acquire_ticket(pipeline_dir, &pipeline, cfg, verbose)? {
-        Some(t) => t,
-        None => return Ok(()),
+/// Directory a step's process runs in: its `working_dir` under the
+/// workspace, created if missing, or the workspace itself.
+fn step_dir(step: &Step, workspace: &Path) -> Result<PathBuf, String> {
+    let Some(working_dir) = &step.working_dir else {
+        return Ok(workspace.to_path_buf());
     };
+    let dir = workspace_path(workspace, working_dir)?;
+    fs::create_dir_all(&dir)
+        .map_err(|e| format!("failed to create working_dir '{}': {}", dir.display(), e))?;
+    Ok(dir)
+}
 
-    let step = &pipeline.steps[ticket.step_index];
+/// Set the step's merged environment on `cmd`, resolving templates in values.
+fn set_env(
+    cmd: &mut Command,
+    pipeline: &Pipeline,
+    step: &Step,
+    workspace: &Path,
+) -> Result<(), String> {
+    let templates = TemplateContext {
+        pipeline: Some(pipeline),
+    };
+    for (key, value) in pipeline.env_for(step) {
+        let value = resolve_templates_with(&value, workspace, &templates)
+            .map_err(|e| format!("env '{}': {}", key, e))?;
+        cmd.env(key, value);
+    }
+    Ok(())
+}
 
-    println!(
-        "[{}] running step {}/{}: '{}' ({})",
+fn execute_step(
+    tick: &Tick,
+    step: &Step,
+    timeout_secs: u64,
+    log: &StepLog,
+) -> Result<(), StepFailure> {
+    let Tick {
+        pipeline,
         pipeline_name,
-        ticket.step_index + 1,
-        pipeline.steps.len(),
-        step.id,
-        match step.step_type {
-            StepType::Bash => "bash",
-            StepType::Agent => "agent",
-        }
-    );
+        workspace,
+        cfg,
+        opts,
+        ..
+    } = *tick;
+    let json = opts.json;
+    // Held until the step finishes so the interpreter can read it
+    let mut _script = None;
 
-    // Execute step (no lock held — other pipelines and processes are free to run)
-    match execute_step(step, &workspace, ticket.timeout_secs) {
-        Ok(()) => {
-            promote_outputs(step, &workspace)?;
+    let templates = TemplateContext {
+        pipeline: Some(pipeline),
+    };
 
-            ticket.state.steps.get_mut(&ticket.step_id).unwrap().status = StepStatus::Completed;
-            state::save(&state_file, &ticket.state)?;
+    // Only what this run writes gets promoted
+    clear_stale_tmp(step, workspace)?;
 
-            let all_done = pipeline.steps.iter().all(|s| {
-                ticket
-                    .state
-                    .steps
-                    .get(&s.id)
-                    .map(|ss| ss.status == StepStatus::Completed)
-                    .unwrap_or(false)
-            });
-            if all_done {
-                println!("[{}] pipeline completed", pipeline_name);
-            }
+    match step.step_type {
+        StepType::Http => {
+            return execute_http(step, workspace, timeout_secs, &templates, log, json);
         }
-        Err(e) => {
-            ticket.state.steps.get_mut(&ticket.step_id).unwrap().status = StepStatus::Failed;
-            state::save(&state_file, &ticket.state)?;
-
-            return Err(format!(
-                "[{}] step '{}' failed: {}",
-                pipeline_name, step.id, e
-            ));
+        StepType::Noop => {
+            log.line("noop: nothing to run");
+            return Ok(());
         }
+        _ => {}
     }
 
-    Ok(())
-}
+    // Templates and output files stay relative to the workspace itself
+    let dir = step_dir(step, workspace)?;
 
-fn execute_step(step: &Step, workspace: &Path, timeout_secs: u64) -> Result<(), String> {
     // Build the command based on step type
     let mut cmd = match step.step_type {
         StepType::Bash => {
             let script = step.bash.as_ref().unwrap();
-            let mut c = Command::new("sh");
-            c.arg("-c").arg(script).current_dir(workspace);
+            let shell = step.shell.as_deref().unwrap_or(&cfg.shell);
+            let mut c = Command::new(shell);
+            c.arg("-c").arg(script).current_dir(&dir);
+            c
+        }
+        StepType::Python => {
+            let source =
+                resolve_templates_with(step.python.as_ref().unwrap(), workspace, &templates)?;
+            let script = TempScript::create(&format!("{}-{}.py", pipeline_name, step.id), &source)?;
+            let mut c = Command::new("python3");
+            c.arg(&script.0).current_dir(&dir);
+            _script = Some(script);
             c
         }
         StepType::Agent => {
             let agent = step.agent.as_ref().unwrap();
-            let raw_prompt = step.prompt.as_ref().unwrap();
-            let prompt = resolve_templates(raw_prompt, workspace)?;
-            crate::openclaw::build_command(agent, &prompt, workspace, timeout_secs)
+            let prompt = agent_prompt(step, workspace, &templates)?;
+            let options = AgentOptions {
+                local: step.local,
+                gateway: step.gateway.as_deref(),
+                model: step.model.as_deref(),
+                extra_args: &step.extra_args,
+            };
+            let cmd = crate::openclaw::build_command(agent, &prompt, &dir, timeout_secs, &options);
+            note(
+                opts,
+                format!(
+                    "  openclaw command: {}",
+                    crate::openclaw::describe_command(&cmd)
+                ),
+            );
+            cmd
+        }
+        StepType::Http | StepType::Noop => {
+            unreachable!("http and noop steps don't spawn a process")
         }
     };
 
-    // Spawn with timeout, with a better error for missing openclaw
-    let output = spawn_with_timeout(&mut cmd, timeout_secs).map_err(|e| {
-        if step.step_type == StepType::Agent && e.contains("failed to spawn") {
-            let bin = crate::openclaw::resolve_binary();
-            format!(
-                "openclaw binary not found — is OpenClaw installed? (looked for: {})",
-                bin
-            )
-        } else {
-            e
+    set_env(&mut cmd, pipeline, step, workspace)?;
+
+    // openclaw takes its input through --message
+    let input = match step.step_type {
+        StepType::Agent => None,
+        _ => step_input(step, workspace, &templates)?,
+    };
+
+    // Output going into one of the step's own artifacts is never cut short,
+    // and terminal output is shown as it arrives (on stderr in JSON mode,
+    // where stdout is reserved for events)
+    let stream = |target: &StreamTarget, echo: Echo| match target {
+        StreamTarget::File(path) | StreamTarget::Append(path)
+            if step.outputs.iter().any(|o| &o.tmp == path) =>
+        {
+            StreamCapture::quiet(0)
         }
-    })?;
+        StreamTarget::Terminal => StreamCapture {
+            max_bytes: cfg.max_output_bytes,
+            echo: Some(echo),
+        },
+        _ => StreamCapture::quiet(cfg.max_output_bytes),
+    };
+    let stdout_target = step.stdout_target();
+    let capture = Capture {
+        stdout: stream(
+            &stdout_target,
+            if json { Echo::Stderr } else { Echo::Stdout },
+        ),
+        stderr: stream(&step.error, Echo::Stderr),
+    };
+    // A better error for a missing interpreter or openclaw
+    let spawn_error = |failure: StepFailure| {
+        if !failure.message.contains("failed to spawn") {
+            return failure;
+        }
+        let e = failure.message;
+        StepFailure::from(match step.step_type {
+            StepType::Agent => {
+                let bin = crate::openclaw::resolve_binary();
+                format!(
+                    "openclaw binary not found — is OpenClaw installed? (looked for: {})",
+                    bin
+                )
+            }
+            StepType::Python => format!("python3 not found — is Python installed? ({})", e),
+            StepType::Bash | StepType::Http | StepType::Noop => e,
+        })
+    };
+
+    // An agent exiting non-zero is retried with exponential backoff, for
+    // flaky gateways, as long as the step's timeout leaves room for it
+    let retries = match step.step_type {
+        StepType::Agent => cfg.agent_retries,
+        _ => 0,
+    };
+    let deadline = Instant::now() + Duration::from_secs(timeout_secs);
+    let mut attempt = 0;
+    let output = loop {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        let output = spawn_with_timeout(
+            &mut cmd,
+            remaining,
+            cfg.kill_grace_secs,
+            input.clone(),
+            &capture,
+            opts.stop,
+        )
+        .map_err(spawn_error)?;
+        if output.status.success() || attempt >= retries {
+            break output;
+        }
+        let delay = Duration::from_secs(
+            cfg.agent_backoff_base_secs
+                .saturating_mul(1 << attempt.min(16)),
+        );
+        if Instant::now() + delay >= deadline {
+            break output;
+        }
+
+        attempt += 1;
+        log.stream("stdout", &output.stdout);
+        log.stream("stderr", &output.stderr);
+        let message = format!(
+            "agent exited with code {}, retrying in {}s ({} of {})",
+            output.status.code().unwrap_or(-1),
+            delay.as_secs(),
+            attempt,
+            retries
+        );
+        log.line(&message);
+        eprintln!("step '{}': {}", step.id, message);
+
+        // Sleep in short slices so a stop request doesn't wait out the backoff
+        let retry_at = Instant::now() + delay;
+        while Instant::now() < retry_at {
+            if opts.stopped() {
+                return Err(StepFailure {
+                    message: "interrupted while waiting to retry".to_string(),
+                    exit_code: output.status.code(),
+                    timed_out: false,
+                    interrupted: true,
+                });
+            }
+            let left = retry_at.saturating_duration_since(Instant::now());
+            std::thread::sleep(left.min(Duration::from_millis(200)));
+        }
+    };
 
-    // Route stdout
-    route_stream(&output.stdout, &step.output, workspace, "output")?;
+    log.stream("stdout", &output.stdout);
+    log.stream("stderr", &output.stderr);
 
-    // Route stderr
-    route_stream(&output.stderr, &step.error, workspace, "stderr")?;
+    // Route stdout and stderr; terminal output has already been shown
+    if stdout_target != StreamTarget::Terminal {
+        route_stream(&output.stdout, &stdout_target, workspace, "output", json)?;
+    }
+    if step.error != StreamTarget::Terminal {
+        route_stream(&output.stderr, &step.error, workspace, "stderr", json)?;
+    }
 
     // Check exit code
     if output.status.success() {
@@ -226,25 +1934,161 @@ fn execute_step(step: &Step, workspace: &Path, timeout_secs: u64) -> Result<(),
                 eprint!("{}", stderr);
             }
         }
+        let code = output.status.code().unwrap_or(-1);
+        // An agent's own diagnostic goes into state, so `status` and `show`
+        // can say why it failed
+        let tail = stderr_tail(&output.stderr);
+        let message = match step.step_type {
+            StepType::Agent if !tail.is_empty() => {
+                format!("agent exited with code {}: {}", code, tail)
+            }
+            StepType::Agent => format!("agent exited with code {}", code),
+            _ => format!("exited with code {}", code),
+        };
+        Err(StepFailure {
+            message,
+            exit_code: output.status.code(),
+            timed_out: false,
+            interrupted: false,
+        })
+    }
+}
+
+/// Lines of a failed agent's stderr kept in its error message, and the most
+/// characters they may come to, so state.json stays small.
+const STDERR_TAIL_LINES: usize = 5;
+const STDERR_TAIL_CHARS: usize = 500;
+
+/// The last few non-blank lines of `stderr` on one line, separated by " | ",
+/// cut to `STDERR_TAIL_CHARS` from the end. Empty if there are none.
+fn stderr_tail(stderr: &[u8]) -> String {
+    let text = String::from_utf8_lossy(stderr);
+    let lines: Vec<&str> = text
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && *line != "[output truncated]")
+        .collect();
+    let tail = lines[lines.len().saturating_sub(STDERR_TAIL_LINES)..].join(" | ");
+    let chars = tail.chars().count();
+    if chars <= STDERR_TAIL_CHARS {
+        return tail;
+    }
+    let kept: String = tail.chars().skip(chars - STDERR_TAIL_CHARS).collect();
+    format!("...{}", kept)
+}
+
+/// An http step's request, with templates resolved.
+struct HttpRequest {
+    method: String,
+    url: String,
+    headers: Vec<(String, String)>,
+    body: Option<String>,
+}
+
+impl HttpRequest {
+    fn build(step: &Step, workspace: &Path, templates: &TemplateContext) -> Result<Self, String> {
+        let resolve = |field: &str, value: &str| {
+            resolve_templates_with(value, workspace, templates)
+                .map_err(|e| format!("{}: {}", field, e))
+        };
+
+        let mut headers = Vec::new();
+        for (name, value) in &step.headers {
+            headers.push((name.clone(), resolve(&format!("header '{}'", name), value)?));
+        }
+        Ok(HttpRequest {
+            method: step.method.as_deref().unwrap_or("GET").to_uppercase(),
+            url: resolve("url", step.url.as_ref().unwrap())?,
+            headers,
+            body: step
+                .body
+                .as_deref()
+                .map(|b| resolve("body", b))
+                .transpose()?,
+        })
+    }
+}
+
+/// Send an http step's request. The response body goes wherever the step's
+/// `output` points; a status outside `expect_status` (any 2xx by default)
+/// fails the step.
+fn execute_http(
+    step: &Step,
+    workspace: &Path,
+    timeout_secs: u64,
+    templates: &TemplateContext,
+    log: &StepLog,
+    json: bool,
+) -> Result<(), StepFailure> {
+    let request = HttpRequest::build(step, workspace, templates)?;
+
+    let agent = ureq::AgentBuilder::new()
+        .timeout(Duration::from_secs(timeout_secs))
+        .build();
+    let mut call = agent.request(&request.method, &request.url);
+    for (name, value) in &request.headers {
+        call = call.set(name, value);
+    }
+    let sent = match &request.body {
+        Some(body) => call.send_string(body),
+        None => call.call(),
+    };
+
+    // ureq reports 4xx/5xx as errors; those still carry a response to check
+    let response = match sent {
+        Ok(response) | Err(ureq::Error::Status(_, response)) => response,
+        Err(ureq::Error::Transport(e)) => {
+            return Err(format!("{} {}: {}", request.method, request.url, e).into());
+        }
+    };
+
+    let status = response.status();
+    let status_text = response.status_text().to_string();
+    let mut body = Vec::new();
+    response
+        .into_reader()
+        .read_to_end(&mut body)
+        .map_err(|e| format!("failed to read response body: {}", e))?;
+
+    log.line(&format!(
+        "{} {} -> {} {}",
+        request.method, request.url, status, status_text
+    ));
+    log.stream("response", &body);
+
+    route_stream(&body, &step.stdout_target(), workspace, "output", json)?;
+
+    let expected = if step.expect_status.is_empty() {
+        (200..300).contains(&status)
+    } else {
+        step.expect_status.contains(&status)
+    };
+    if expected {
+        Ok(())
+    } else {
         Err(format!(
-            "exited with code {}",
-            output.status.code().unwrap_or(-1)
-        ))
+            "{} {} returned {} {}",
+            request.method, request.url, status, status_text
+        )
+        .into())
     }
 }
 
-/// Route a stream's bytes according to a StreamTarget.
+/// Route a stream's bytes according to a StreamTarget. Files get the bytes
+/// exactly as the step wrote them; only the terminal copy is decoded, lossily.
 fn route_stream(
     data: &[u8],
     target: &StreamTarget,
     workspace: &Path,
     label: &str,
+    json: bool,
 ) -> Result<(), String> {
     match target {
         StreamTarget::Terminal => {
             if !data.is_empty() {
+                // stdout is reserved for events in JSON mode
                 let text = String::from_utf8_lossy(data);
-                if label == "stderr" {
+                if label == "stderr" || json {
                     eprint!("{}", text);
                 } else {
                     print!("{}", text);
@@ -252,9 +2096,18 @@ fn route_stream(
             }
         }
         StreamTarget::Void => {}
-        StreamTarget::File(path) => {
-            let full_path = workspace.join(path);
-            fs::write(&full_path, data).map_err(|e| {
+        StreamTarget::File(path) | StreamTarget::Append(path) => {
+            let full_path = workspace_path(workspace, path)
+                .map_err(|e| format!("failed to write {}: {}", label, e))?;
+            let written = match target {
+                StreamTarget::Append(_) => fs::OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(&full_path)
+                    .and_then(|mut file| file.write_all(data)),
+                _ => fs::write(&full_path, data),
+            };
+            written.map_err(|e| {
                 format!(
                     "failed to write {} to '{}': {}",
                     label,
@@ -267,93 +2120,520 @@ fn route_stream(
     Ok(())
 }
 
+/// How to collect a child's stdout and stderr.
+struct Capture {
+    stdout: StreamCapture,
+    stderr: StreamCapture,
+}
+
+/// How to collect one output stream: keep up to `max_bytes` of it (0 keeps
+/// everything), and optionally show it live as it arrives.
+#[derive(Clone, Copy)]
+struct StreamCapture {
+    max_bytes: u64,
+    echo: Option<Echo>,
+}
+
+impl StreamCapture {
+    fn quiet(max_bytes: u64) -> Self {
+        StreamCapture {
+            max_bytes,
+            echo: None,
+        }
+    }
+}
+
+/// Where live output is shown.
+#[derive(Clone, Copy)]
+enum Echo {
+    Stdout,
+    Stderr,
+}
+
+impl Echo {
+    fn print(self, bytes: &[u8]) {
+        let text = String::from_utf8_lossy(bytes);
+        match self {
+            Echo::Stdout => print!("{}", text),
+            Echo::Stderr => eprint!("{}", text),
+        }
+    }
+}
+
+/// Marker appended to output that went past its limit.
+const TRUNCATED_MARKER: &[u8] = b"\n[output truncated]\n";
+
 /// Spawn a command and wait for it to finish, with a timeout.
 /// Returns the raw process output on completion (success or failure).
-/// Returns Err only for spawn failures or timeouts.
+/// Returns Err only for spawn failures, timeouts or interruptions.
+///
+/// The child runs in its own process group. On timeout, or once `stop` is
+/// set, the whole group gets SIGTERM, then SIGKILL if the child is still
+/// alive after `kill_grace_secs`.
+/// Once the child exits, whatever is left in its group is killed too.
+///
+/// `input`, if any, is fed to the child's stdin, which is then closed.
+/// stdout and stderr are read while the child runs, as set out in `capture`;
+/// anything past a stream's limit is read and dropped, so the child is never
+/// stopped or blocked by it.
 fn spawn_with_timeout(
     cmd: &mut Command,
-    timeout_secs: u64,
-) -> Result<std::process::Output, String> {
+    timeout: Duration,
+    kill_grace_secs: u64,
+    input: Option<Vec<u8>>,
+    capture: &Capture,
+    stop: Option<&AtomicBool>,
+) -> Result<std::process::Output, StepFailure> {
+    if input.is_some() {
+        cmd.stdin(std::process::Stdio::piped());
+    }
     let mut child = cmd
         .stdout(std::process::Stdio::piped())
         .stderr(std::process::Stdio::piped())
+        .process_group(0)
         .spawn()
         .map_err(|e| format!("failed to spawn: {}", e))?;
 
-    let timeout = Duration::from_secs(timeout_secs);
+    // Written from its own thread, as the child may not read it all before
+    // filling its output pipes. A child that exits early just closes it.
+    if let (Some(input), Some(mut stdin)) = (input, child.stdin.take()) {
+        std::thread::spawn(move || {
+            let _ = stdin.write_all(&input);
+        });
+    }
+
+    let stdout = read_stream(child.stdout.take().unwrap(), capture.stdout);
+    let stderr = read_stream(child.stderr.take().unwrap(), capture.stderr);
+
     let start = Instant::now();
 
     loop {
         match child.try_wait() {
-            Ok(Some(_status)) => {
-                return child
-                    .wait_with_output()
-                    .map_err(|e| format!("failed to read output: {}", e));
+            Ok(Some(status)) => {
+                // Anything the step left running in the background would leak
+                // and keep our pipes open, so clear out the rest of its group.
+                kill_group(&child, libc::SIGKILL);
+                let read = |handle: std::thread::JoinHandle<std::io::Result<Vec<u8>>>| {
+                    handle
+                        .join()
+                        .unwrap()
+                        .map_err(|e| format!("failed to read output: {}", e))
+                };
+                return Ok(std::process::Output {
+                    status,
+                    stdout: read(stdout)?,
+                    stderr: read(stderr)?,
+                });
             }
             Ok(None) => {
-                if start.elapsed() >= timeout {
-                    let _ = child.kill();
-                    let _ = child.wait();
-                    return Err(format!("timed out after {}s", timeout_secs));
+                let interrupted = stop.is_some_and(|stop| stop.load(Ordering::SeqCst));
+                let timed_out = start.elapsed() >= timeout;
+                if interrupted || timed_out {
+                    let hard_killed = terminate(&mut child, Duration::from_secs(kill_grace_secs));
+                    let reason = if interrupted {
+                        "interrupted".to_string()
+                    } else {
+                        format!("timed out after {}s", timeout.as_secs_f64().round())
+                    };
+                    let message = if hard_killed {
+                        format!(
+                            "{} (killed, still running {}s after SIGTERM)",
+                            reason, kill_grace_secs
+                        )
+                    } else {
+                        format!("{} (terminated)", reason)
+                    };
+                    return Err(StepFailure {
+                        message,
+                        exit_code: None,
+                        timed_out: !interrupted,
+                        interrupted,
+                    });
                 }
                 std::thread::sleep(Duration::from_millis(100));
             }
             Err(e) => {
-                return Err(format!("failed to check process status: {}", e));
+                return Err(format!("failed to check process status: {}", e).into());
             }
         }
     }
 }
 
-/// Replace {{ file:path }} with the contents of the file relative to workspace.
-pub fn resolve_templates(input: &str, workspace: &Path) -> Result<String, String> {
-    let re = Regex::new(r"\{\{\s*file:\s*(.+?)\s*\}\}").unwrap();
-    let mut result = input.to_string();
+/// Read a child's stream to the end on its own thread, keeping the first
+/// `max_bytes` (0 for all of it) and marking the cut if there was more.
+/// Kept output is echoed a line at a time if asked, so lines from steps
+/// running side by side don't get mixed up.
+fn read_stream<R: Read + Send + 'static>(
+    mut stream: R,
+    capture: StreamCapture,
+) -> std::thread::JoinHandle<std::io::Result<Vec<u8>>> {
+    std::thread::spawn(move || {
+        let mut kept = Vec::new();
+        let mut echoed = 0;
+        let mut truncated = false;
+        let mut buf = [0u8; 8192];
+        loop {
+            let n = match stream.read(&mut buf) {
+                Ok(0) => break,
+                Ok(n) => n,
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            };
+            let room = if capture.max_bytes == 0 {
+                n
+            } else {
+                (capture.max_bytes as usize)
+                    .saturating_sub(kept.len())
+                    .min(n)
+            };
+            kept.extend_from_slice(&buf[..room]);
+            truncated |= room < n;
 
-    // Collect matches first to avoid borrow issues
-    let matches: Vec<(String, String)> = re
-        .captures_iter(input)
-        .map(|cap| {
-            let full_match = cap[0].to_string();
-            let file_path = cap[1].to_string();
-            (full_match, file_path)
-        })
-        .collect();
+            if let Some(echo) = capture.echo
+                && let Some(end) = kept[echoed..].iter().rposition(|&b| b == b'\n')
+            {
+                echo.print(&kept[echoed..echoed + end + 1]);
+                echoed += end + 1;
+            }
+        }
+        if truncated {
+            kept.extend_from_slice(TRUNCATED_MARKER);
+        }
+        if let Some(echo) = capture.echo
+            && echoed < kept.len()
+        {
+            echo.print(&kept[echoed..]);
+        }
+        Ok(kept)
+    })
+}
 
-    for (full_match, file_path) in matches {
-        let path = workspace.join(&file_path);
-        let content = fs::read_to_string(&path).map_err(|e| {
-            format!(
-                "template '{}': failed to read '{}': {}",
-                full_match,
-                path.display(),
-                e
-            )
-        })?;
-        result = result.replace(&full_match, &content);
+/// Send SIGTERM to the child's process group, give it `grace` to exit, then
+/// SIGKILL the group. Returns true if the child had to be hard-killed.
+fn terminate(child: &mut Child, grace: Duration) -> bool {
+    kill_group(child, libc::SIGTERM);
+
+    let deadline = Instant::now() + grace;
+    let mut exited = false;
+    while Instant::now() < deadline {
+        if let Ok(Some(_)) = child.try_wait() {
+            exited = true;
+            break;
+        }
+        std::thread::sleep(Duration::from_millis(100));
+    }
+
+    // Also clears out anything left in the group once the child itself is gone
+    kill_group(child, libc::SIGKILL);
+    let _ = child.wait();
+    !exited
+}
+
+/// Signal every process in the child's process group (see `process_group(0)`).
+fn kill_group(child: &Child, signal: libc::c_int) {
+    let pgid = child.id() as libc::pid_t;
+    // SAFETY: kill(2) has no memory-safety preconditions; an empty group just yields ESRCH.
+    unsafe { libc::kill(-pgid, signal) };
+}
+
+/// `relative` joined onto the workspace, refusing anything that resolves
+/// outside it, whether through `..`, an absolute path or a symlink. The path
+/// itself doesn't have to exist yet.
+fn workspace_path(workspace: &Path, relative: &str) -> Result<PathBuf, String> {
+    let root = workspace.canonicalize().map_err(|e| {
+        format!(
+            "failed to resolve workspace '{}': {}",
+            workspace.display(),
+            e
+        )
+    })?;
+    let outside = || format!("path '{}' is outside the workspace", relative);
+
+    // Resolve the longest part that exists, then put the rest back on
+    let joined = root.join(relative);
+    let mut existing = joined.as_path();
+    let mut rest = Vec::new();
+    let resolved = loop {
+        match existing.canonicalize() {
+            Ok(path) => break path,
+            Err(_) => {
+                // Whatever is left ends in `..` (or is the filesystem root)
+                let name = existing.file_name().ok_or_else(outside)?;
+                rest.push(name);
+                existing = existing.parent().ok_or_else(outside)?;
+            }
+        }
+    };
+    if !resolved.starts_with(&root) {
+        return Err(outside());
+    }
+    Ok(rest
+        .iter()
+        .rev()
+        .fold(resolved, |path, name| path.join(name)))
+}
+
+/// What template markers can refer to beyond workspace files and the environment.
+#[derive(Default)]
+pub struct TemplateContext<'a> {
+    /// The pipeline being run, needed for `{{ step:ID }}`.
+    pub pipeline: Option<&'a Pipeline>,
+}
+
+/// Replace `{{ file:.. }}` and `{{ env:.. }}` template markers in `input`.
+/// See [`resolve_templates_with`] for the full syntax.
+pub fn resolve_templates(input: &str, workspace: &Path) -> Result<String, CronclawError> {
+    resolve_templates_with(input, workspace, &TemplateContext::default())
+}
+
+/// Replace template markers in `input`:
+///
+/// - `{{ file:path }}` — contents of the file, relative to workspace
+/// - `{{ env:NAME }}`  — value of the environment variable
+/// - `{{ step:ID }}`   — contents of step ID's `output` file
+/// - `{{ glob:pattern }}` — contents of every matching file, see [`resolve_glob`]
+/// - `{{ var:NAME }}`  — value of the pipeline's variable NAME
+///
+/// Expansion is a single pass over `input`: what a marker expands to is
+/// inserted as is and never scanned for markers itself, so a file can't pull
+/// in other files or environment variables by containing a template.
+pub fn resolve_templates_with(
+    input: &str,
+    workspace: &Path,
+    ctx: &TemplateContext,
+) -> Result<String, CronclawError> {
+    let re = Regex::new(r"\{\{\s*(file|env|step|glob|var):\s*(.+?)\s*\}\}").unwrap();
+    let multiline = input.contains('\n');
+
+    // Each marker is replaced where it stands; the same marker more than
+    // once is only resolved, and its file read, the first time
+    let mut resolved: HashMap<(&str, &str), String> = HashMap::new();
+    let mut result = String::with_capacity(input.len());
+    let mut last = 0;
+    for cap in re.captures_iter(input) {
+        let whole = cap.get(0).unwrap();
+        let (kind, arg) = (cap.get(1).unwrap().as_str(), cap.get(2).unwrap().as_str());
+        result.push_str(&input[last..whole.start()]);
+        last = whole.end();
+
+        let content = match resolved.entry((kind, arg)) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => {
+                let content = resolve_template(kind, arg, workspace, ctx).map_err(|e| {
+                    // The line each marker is on makes a typo in a long prompt easy to find
+                    CronclawError::Template(if multiline {
+                        let line = input[..whole.start()].matches('\n').count() + 1;
+                        format!("template '{}' on line {}: {}", whole.as_str(), line, e)
+                    } else {
+                        format!("template '{}': {}", whole.as_str(), e)
+                    })
+                })?;
+                entry.insert(content)
+            }
+        };
+        result.push_str(content);
+    }
+    result.push_str(&input[last..]);
+
+    Ok(result)
+}
+
+/// What one template marker of `kind` (`file`, `env`, `step`, `glob` or `var`)
+/// expands to.
+fn resolve_template(
+    kind: &str,
+    arg: &str,
+    workspace: &Path,
+    ctx: &TemplateContext,
+) -> Result<String, String> {
+    match kind {
+        "env" => {
+            std::env::var(arg).map_err(|_| format!("environment variable '{}' is not set", arg))
+        }
+        "step" => {
+            let path = step_output_path(arg, ctx)?;
+            let file = workspace_path(workspace, path)?;
+            fs::read_to_string(file)
+                .map_err(|e| format!("step '{}' has not produced '{}' yet: {}", arg, path, e))
+        }
+        "glob" => resolve_glob(arg, workspace),
+        "var" => {
+            let pipeline = ctx
+                .pipeline
+                .ok_or_else(|| "var templates are only available inside a pipeline".to_string())?;
+            match pipeline.vars.get(arg) {
+                Some(Some(value)) => Ok(value.clone()),
+                Some(None) => Err(format!(
+                    "variable '{}' has no value; pass it with --var {}=VALUE",
+                    arg, arg
+                )),
+                None => Err(format!("unknown variable '{}'", arg)),
+            }
+        }
+        _ => {
+            let path = workspace_path(workspace, arg)?;
+            fs::read_to_string(&path)
+                .map_err(|e| format!("failed to read '{}': {}", path.display(), e))
+        }
+    }
+}
+
+/// Concatenated contents of the workspace files matching a glob template's
+/// `pattern | options`, sorted by path. Options:
+///
+/// - `named`    — put a `--- path ---` line before each file
+/// - `optional` — expand to nothing when no file matches, rather than erroring
+fn resolve_glob(arg: &str, workspace: &Path) -> Result<String, String> {
+    let (pattern, options) = match arg.split_once('|') {
+        Some((pattern, options)) => (pattern.trim(), options.split_whitespace().collect()),
+        None => (arg, Vec::new()),
+    };
+    for option in &options {
+        if !matches!(*option, "named" | "optional") {
+            return Err(format!("unknown glob option '{}'", option));
+        }
+    }
+
+    let base = glob::Pattern::escape(&workspace.to_string_lossy());
+    let paths = glob::glob(&format!("{}/{}", base, pattern))
+        .map_err(|e| format!("invalid glob '{}': {}", pattern, e))?;
+
+    let mut files = Vec::new();
+    for path in paths {
+        let path = path.map_err(|e| format!("failed to read '{}': {}", pattern, e))?;
+        if !path.is_file() {
+            continue;
+        }
+        let relative = path
+            .strip_prefix(workspace)
+            .unwrap_or(&path)
+            .to_string_lossy()
+            .into_owned();
+        files.push((workspace_path(workspace, &relative)?, relative));
+    }
+    files.sort_by(|a, b| a.1.cmp(&b.1));
+
+    if files.is_empty() && !options.contains(&"optional") {
+        return Err(format!("no files match '{}'", pattern));
     }
 
+    let mut result = String::new();
+    for (path, relative) in files {
+        let content = fs::read_to_string(&path)
+            .map_err(|e| format!("failed to read '{}': {}", path.display(), e))?;
+        if options.contains(&"named") {
+            if !result.is_empty() && !result.ends_with('\n') {
+                result.push('\n');
+            }
+            result.push_str(&format!("--- {} ---\n", relative));
+        }
+        result.push_str(&content);
+    }
     Ok(result)
 }
 
-pub fn promote_outputs(step: &Step, workspace: &Path) -> Result<(), String> {
+/// The workspace-relative file holding the stdout of the step named `id`:
+/// its `from: stdout` output, or else its `output` file.
+fn step_output_path<'a>(id: &str, ctx: &TemplateContext<'a>) -> Result<&'a str, String> {
+    let pipeline = ctx
+        .pipeline
+        .ok_or_else(|| "step templates are only available inside a pipeline".to_string())?;
+    let step = pipeline
+        .steps
+        .iter()
+        .find(|s| s.id == id)
+        .ok_or_else(|| format!("unknown step '{}'", id))?;
+    if let Some(output) = step.stdout_output() {
+        return Ok(&output.path);
+    }
+    step.output
+        .file()
+        .ok_or_else(|| format!("step '{}' has no output file", id))
+}
+
+/// Remove the tmp files of a step's outputs left behind by an earlier run
+/// that failed or was killed before promoting them, so `promote_outputs`
+/// never mistakes one for the output of the run about to start.
+pub fn clear_stale_tmp(step: &Step, workspace: &Path) -> Result<(), CronclawError> {
     for output in &step.outputs {
-        let tmp_path = workspace.join(&output.tmp);
-        let final_path = workspace.join(&output.path);
+        let tmp_path = workspace_path(workspace, &output.tmp)
+            .map_err(|e| format!("output '{}': {}", output.name, e))?;
+        match fs::remove_file(&tmp_path) {
+            Ok(()) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => {
+                return Err(CronclawError::Io(format!(
+                    "output '{}': failed to remove stale tmp file '{}': {}",
+                    output.name, output.tmp, e
+                )));
+            }
+        }
+    }
+    Ok(())
+}
 
-        if !tmp_path.exists() {
-            return Err(format!(
+/// Move each output's tmp file to its final path. Returns the SHA-256 of
+/// every promoted file, keyed by output name.
+///
+/// Every tmp file is checked before any is moved, so a step missing one of
+/// its outputs, or with one under its `min_bytes`, promotes none of them. A
+/// file that can't be read back for hashing only produces a warning; the
+/// promotion itself already succeeded.
+pub fn promote_outputs(
+    step: &Step,
+    workspace: &Path,
+) -> Result<BTreeMap<String, String>, CronclawError> {
+    let mut moves = Vec::with_capacity(step.outputs.len());
+    for output in &step.outputs {
+        let inside = |path: &str| {
+            workspace_path(workspace, path).map_err(|e| format!("output '{}': {}", output.name, e))
+        };
+        let tmp_path = inside(&output.tmp)?;
+        let final_path = inside(&output.path)?;
+        let Ok(metadata) = fs::metadata(&tmp_path) else {
+            return Err(CronclawError::Io(format!(
                 "output '{}': tmp file '{}' not found after step completed",
                 output.name, output.tmp
-            ));
+            )));
+        };
+        if metadata.len() < output.min_bytes {
+            return Err(CronclawError::Io(format!(
+                "output '{}': tmp file '{}' is {} bytes, under its min_bytes of {}",
+                output.name,
+                output.tmp,
+                metadata.len(),
+                output.min_bytes
+            )));
         }
+        moves.push((output, tmp_path, final_path));
+    }
 
+    let mut hashes = BTreeMap::new();
+    for (output, tmp_path, final_path) in moves {
         fs::rename(&tmp_path, &final_path).map_err(|e| {
-            format!(
+            CronclawError::Io(format!(
                 "output '{}': failed to promote '{}' -> '{}': {}",
                 output.name, output.tmp, output.path, e
-            )
+            ))
         })?;
+
+        match sha256_file(&final_path) {
+            Ok(hash) => {
+                hashes.insert(output.name.clone(), hash);
+            }
+            Err(e) => eprintln!(
+                "warning: output '{}': failed to hash '{}': {}",
+                output.name, output.path, e
+            ),
+        }
     }
-    Ok(())
+    Ok(hashes)
+}
+
+/// Hex-encoded SHA-256 of a file's contents.
+fn sha256_file(path: &Path) -> std::io::Result<String> {
+    let mut file = File::open(path)?;
+    let mut hasher = Sha256::new();
+    std::io::copy(&mut file, &mut hasher)?;
+    Ok(format!("{:x}", hasher.finalize()))
 }
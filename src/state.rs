@@ -1,8 +1,11 @@
+use chrono::{DateTime, SecondsFormat, Utc};
 use serde::{Deserialize, Serialize};
 use std::collections::BTreeMap;
-use std::fs;
-use std::path::Path;
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
 
+use crate::error::CronclawError;
 use crate::pipeline::Pipeline;
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -12,51 +15,311 @@ pub enum StepStatus {
     Running,
     Completed,
     Failed,
+    Skipped,
+}
+
+impl StepStatus {
+    pub fn name(&self) -> &'static str {
+        match self {
+            StepStatus::Pending => "pending",
+            StepStatus::Running => "running",
+            StepStatus::Completed => "completed",
+            StepStatus::Failed => "failed",
+            StepStatus::Skipped => "skipped",
+        }
+    }
+
+    /// Completed or skipped: nothing left to do, and dependents may run.
+    pub fn is_done(&self) -> bool {
+        matches!(self, StepStatus::Completed | StepStatus::Skipped)
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct StepState {
     pub status: StepStatus,
+
+    // Timing of the most recent run (RFC3339). Absent in older state files.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub started_at: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub finished_at: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub duration_secs: Option<u64>,
+
+    // Why the most recent run failed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error_message: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exit_code: Option<i32>,
+
+    // Process id of the runner that claimed the step while it is running
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pid: Option<u32>,
+
+    // When the first of the current run's attempts started, for the step's deadline
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub first_started_at: Option<String>,
+
+    // Number of failed runs so far, used to enforce the step's retry budget
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub attempts: u32,
+
+    // SHA-256 of each output promoted by the last successful run, by output name
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub output_hashes: BTreeMap<String, String>,
+}
+
+fn is_zero(n: &u32) -> bool {
+    *n == 0
 }
 
-/// Ordered map of step id -> step state.
-/// BTreeMap keeps keys sorted, but we rely on pipeline.yaml for ordering
-/// and just use this for lookup.
+impl StepState {
+    pub fn new() -> Self {
+        StepState {
+            status: StepStatus::Pending,
+            started_at: None,
+            finished_at: None,
+            duration_secs: None,
+            error_message: None,
+            exit_code: None,
+            pid: None,
+            first_started_at: None,
+            attempts: 0,
+            output_hashes: BTreeMap::new(),
+        }
+    }
+
+    /// Mark the step running and record when it started and which process claimed it.
+    pub fn start(&mut self) {
+        self.status = StepStatus::Running;
+        self.pid = Some(std::process::id());
+        self.started_at = Some(now());
+        if self.attempts == 0 {
+            self.first_started_at = self.started_at.clone();
+        }
+        self.finished_at = None;
+        self.duration_secs = None;
+        self.error_message = None;
+        self.exit_code = None;
+    }
+
+    /// Move the step to `status` and record when it finished and how long it took.
+    pub fn finish(&mut self, status: StepStatus) {
+        let finished = Utc::now();
+        self.status = status;
+        self.pid = None;
+        self.finished_at = Some(format_time(finished));
+        self.duration_secs = self
+            .started_at
+            .as_deref()
+            .and_then(|s| DateTime::parse_from_rfc3339(s).ok())
+            .map(|started| {
+                (finished - started.with_timezone(&Utc))
+                    .num_seconds()
+                    .max(0) as u64
+            });
+    }
+
+    /// Seconds elapsed since the step last started, if it has.
+    pub fn secs_since_started(&self) -> Option<u64> {
+        secs_since(self.started_at.as_deref()?)
+    }
+
+    /// Seconds elapsed since the first attempt of the step's current run started.
+    pub fn secs_since_first_started(&self) -> Option<u64> {
+        secs_since(self.first_started_at.as_deref()?)
+    }
+
+    /// Seconds elapsed since the step last finished, if it has.
+    pub fn secs_since_finished(&self) -> Option<u64> {
+        secs_since(self.finished_at.as_deref()?)
+    }
+}
+
+/// Seconds elapsed since an RFC3339 timestamp, if it parses.
+pub fn secs_since(timestamp: &str) -> Option<u64> {
+    let then = DateTime::parse_from_rfc3339(timestamp).ok()?;
+    let elapsed = Utc::now() - then.with_timezone(&Utc);
+    Some(elapsed.num_seconds().max(0) as u64)
+}
+
+impl Default for StepState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Map of step id -> step state.
+/// BTreeMap keeps keys sorted, so the pipeline's declaration order is kept
+/// separately in `order`; use `ordered_steps` to walk steps in that order.
 #[derive(Debug, Serialize, Deserialize)]
 pub struct State {
     pub steps: BTreeMap<String, StepState>,
+
+    // Step ids in pipeline.yaml order. Empty in state files written before
+    // it was recorded.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub order: Vec<String>,
+
+    // When a scheduled pipeline last ticked (RFC3339)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_tick: Option<String>,
+
+    // Modification times (RFC3339) of a pipeline's `watch` files as of its
+    // last tick, keyed by path as written in pipeline.yaml
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub watched: BTreeMap<String, String>,
+
+    // How many times `run` has looked at the pipeline, and when it last did
+    // (RFC3339), whether or not a step was due
+    #[serde(default)]
+    pub tick_count: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_run_at: Option<String>,
+
+    // How the pipeline's `on_failure` cleanup steps went when they last ran,
+    // kept apart from the steps proper
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub cleanup: BTreeMap<String, StepState>,
+
+    // The directory inside the workspace this run works in, for a pipeline
+    // with `workspace_mode: isolated`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub run_workspace: Option<String>,
 }
 
 impl State {
     pub fn from_pipeline(pipeline: &Pipeline) -> Self {
         let mut steps = BTreeMap::new();
         for step in &pipeline.steps {
-            steps.insert(
-                step.id.clone(),
-                StepState {
-                    status: StepStatus::Pending,
-                },
-            );
+            steps.insert(step.id.clone(), StepState::new());
+        }
+        State {
+            steps,
+            order: pipeline.steps.iter().map(|s| s.id.clone()).collect(),
+            last_tick: None,
+            watched: BTreeMap::new(),
+            tick_count: 0,
+            last_run_at: None,
+            cleanup: BTreeMap::new(),
+            run_workspace: None,
+        }
+    }
+
+    /// Seconds elapsed since `run` last looked at the pipeline, if it has.
+    pub fn secs_since_last_run(&self) -> Option<u64> {
+        secs_since(self.last_run_at.as_deref()?)
+    }
+
+    /// Steps in pipeline declaration order. Steps missing from `order`, e.g.
+    /// all of them in an older state file, follow in id order.
+    pub fn ordered_steps(&self) -> Vec<(&str, &StepState)> {
+        let mut steps: Vec<(&str, &StepState)> = self
+            .order
+            .iter()
+            .filter_map(|id| self.steps.get_key_value(id))
+            .map(|(id, s)| (id.as_str(), s))
+            .collect();
+        for (id, s) in &self.steps {
+            if !self.order.contains(id) {
+                steps.push((id.as_str(), s));
+            }
+        }
+        steps
+    }
+
+    /// How the steps in this state differ from those `pipeline` declares.
+    pub fn drift(&self, pipeline: &Pipeline) -> StepDrift {
+        let added = pipeline
+            .steps
+            .iter()
+            .filter(|s| !self.steps.contains_key(&s.id))
+            .map(|s| s.id.clone())
+            .collect();
+        let removed = self
+            .ordered_steps()
+            .into_iter()
+            .filter(|(id, _)| !pipeline.steps.iter().any(|s| s.id == *id))
+            .map(|(id, _)| id.to_string())
+            .collect();
+        StepDrift { added, removed }
+    }
+}
+
+/// Step ids that pipeline.yaml and state.json disagree on. Any difference
+/// stops the pipeline until it is reset.
+#[derive(Debug, Default, PartialEq)]
+pub struct StepDrift {
+    /// In pipeline.yaml but not in state.json, in declaration order
+    pub added: Vec<String>,
+    /// In state.json but no longer in pipeline.yaml
+    pub removed: Vec<String>,
+}
+
+impl StepDrift {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty()
+    }
+}
+
+impl std::fmt::Display for StepDrift {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut parts = Vec::new();
+        if !self.added.is_empty() {
+            parts.push(format!("added {}", self.added.join(", ")));
         }
-        State { steps }
+        if !self.removed.is_empty() {
+            parts.push(format!("removed {}", self.removed.join(", ")));
+        }
+        f.write_str(&parts.join("; "))
     }
 }
 
-pub fn load(path: &Path) -> Result<Option<State>, String> {
+/// Current time as an RFC3339 string, the format used for all state timestamps.
+pub fn now() -> String {
+    format_time(Utc::now())
+}
+
+fn format_time(time: DateTime<Utc>) -> String {
+    time.to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+pub fn load(path: &Path) -> Result<Option<State>, CronclawError> {
     if !path.exists() {
         return Ok(None);
     }
     let content = fs::read_to_string(path)
-        .map_err(|e| format!("failed to read state: {}", e))?;
+        .map_err(|e| CronclawError::Parse(format!("failed to read state: {}", e)))?;
     let state: State = serde_json::from_str(&content)
-        .map_err(|e| format!("failed to parse state: {}", e))?;
+        .map_err(|e| CronclawError::Parse(format!("failed to parse state: {}", e)))?;
     Ok(Some(state))
 }
 
-pub fn save(path: &Path, state: &State) -> Result<(), String> {
+/// Write `state` to `path` by way of a `.tmp` file next to it that is then
+/// renamed over it, so a crash or full disk mid-write leaves the previous
+/// state in place rather than a truncated file.
+pub fn save(path: &Path, state: &State) -> Result<(), CronclawError> {
     let content = serde_json::to_string_pretty(state)
         .map_err(|e| format!("failed to serialize state: {}", e))?;
-    fs::write(path, content)
-        .map_err(|e| format!("failed to write state: {}", e))?;
+    let tmp = tmp_path(path);
+    let write = || -> std::io::Result<()> {
+        let mut file = File::create(&tmp)?;
+        file.write_all(content.as_bytes())?;
+        file.sync_all()
+    };
+    if let Err(e) = write() {
+        let _ = fs::remove_file(&tmp);
+        return Err(CronclawError::Io(format!("failed to write state: {}", e)));
+    }
+    fs::rename(&tmp, path)
+        .map_err(|e| CronclawError::Io(format!("failed to write state: {}", e)))?;
     Ok(())
 }
+
+/// Where `save` writes the new state before renaming it into place.
+pub fn tmp_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".tmp");
+    PathBuf::from(name)
+}
@@ -0,0 +1,945 @@
+use cronclaw::{config, pipeline};
+use std::path::Path;
+use std::process::{Command, Output};
+use tempfile::TempDir;
+
+/// Run the cronclaw binary with `home` as HOME.
+fn cronclaw(home: &Path, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_cronclaw"))
+        .args(args)
+        .env("HOME", home)
+        .output()
+        .unwrap()
+}
+
+// ─── init ───
+
+#[test]
+fn init_creates_config_and_example_pipeline() {
+    let dir = TempDir::new().unwrap();
+    let output = cronclaw(dir.path(), &["init"]);
+    assert!(output.status.success());
+
+    let home = dir.path().join(".cronclaw");
+    let cfg = config::load(&home.join("config.yaml")).unwrap();
+    assert_eq!(cfg.timeout, config::Config::default().timeout);
+
+    let example = pipeline::load(&home.join("pipelines/example/pipeline.yaml")).unwrap();
+    assert_eq!(example.steps.len(), 2);
+    assert!(cronclaw(dir.path(), &["validate"]).status.success());
+
+    // The example runs as is, without openclaw
+    assert!(cronclaw(dir.path(), &["run", "example"]).status.success());
+    assert!(cronclaw(dir.path(), &["run", "example"]).status.success());
+    assert!(
+        cronclaw(dir.path(), &["status", "example"])
+            .status
+            .success()
+    );
+}
+
+#[test]
+fn init_no_example_skips_example_pipeline() {
+    let dir = TempDir::new().unwrap();
+    let output = cronclaw(dir.path(), &["init", "--no-example"]);
+    assert!(output.status.success());
+
+    let home = dir.path().join(".cronclaw");
+    assert!(home.join("config.yaml").exists());
+    assert!(home.join("pipelines").is_dir());
+    assert!(!home.join("pipelines/example").exists());
+}
+
+#[test]
+fn init_refuses_existing_directory_without_force() {
+    let dir = TempDir::new().unwrap();
+    std::fs::create_dir(dir.path().join(".cronclaw")).unwrap();
+
+    assert!(!cronclaw(dir.path(), &["init"]).status.success());
+    assert!(!dir.path().join(".cronclaw/config.yaml").exists());
+}
+
+#[test]
+fn init_force_fills_in_empty_directory() {
+    let dir = TempDir::new().unwrap();
+    std::fs::create_dir(dir.path().join(".cronclaw")).unwrap();
+
+    assert!(cronclaw(dir.path(), &["init", "--force"]).status.success());
+    let home = dir.path().join(".cronclaw");
+    assert!(home.join("config.yaml").exists());
+    assert!(home.join("pipelines/example/pipeline.yaml").exists());
+}
+
+#[test]
+fn init_force_keeps_existing_pipelines_and_config() {
+    let dir = TempDir::new().unwrap();
+    let home = dir.path().join(".cronclaw");
+    let mine = home.join("pipelines/mine");
+    std::fs::create_dir_all(&mine).unwrap();
+    std::fs::write(mine.join("pipeline.yaml"), "steps: []\n").unwrap();
+    std::fs::write(mine.join("state.json"), "{}").unwrap();
+    std::fs::write(home.join("config.yaml"), "timeout: 60\n").unwrap();
+
+    assert!(cronclaw(dir.path(), &["init", "--force"]).status.success());
+    assert_eq!(
+        std::fs::read_to_string(home.join("config.yaml")).unwrap(),
+        "timeout: 60\n"
+    );
+    assert_eq!(
+        std::fs::read_to_string(mine.join("state.json")).unwrap(),
+        "{}"
+    );
+    assert!(!home.join("pipelines/example").exists());
+
+    // Idempotent
+    assert!(cronclaw(dir.path(), &["init", "--force"]).status.success());
+}
+
+// ─── home override ───
+
+#[test]
+fn home_flag_overrides_env_and_default() {
+    let dir = TempDir::new().unwrap();
+    let flag = dir.path().join("flag");
+    let env = dir.path().join("env");
+
+    let output = Command::new(env!("CARGO_BIN_EXE_cronclaw"))
+        .args(["init", "--no-example", "--home"])
+        .arg(&flag)
+        .env("HOME", dir.path())
+        .env("CRONCLAW_HOME", &env)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert!(flag.join("config.yaml").exists());
+    assert!(!env.exists());
+    assert!(!dir.path().join(".cronclaw").exists());
+}
+
+#[test]
+fn cronclaw_home_env_overrides_default() {
+    let dir = TempDir::new().unwrap();
+    let root = dir.path().join("root");
+    let run = |args: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_cronclaw"))
+            .args(args)
+            .env("HOME", dir.path())
+            .env("CRONCLAW_HOME", &root)
+            .output()
+            .unwrap()
+    };
+
+    assert!(run(&["init"]).status.success());
+    assert!(root.join("pipelines/example/pipeline.yaml").exists());
+    assert!(!dir.path().join(".cronclaw").exists());
+
+    assert!(run(&["run", "example"]).status.success());
+    assert!(root.join("pipelines/example/state.json").exists());
+    assert!(run(&["status", "example"]).status.success());
+    assert!(run(&["reset", "example"]).status.success());
+}
+
+// ─── daemon ───
+
+#[test]
+fn daemon_exits_cleanly_on_sigterm() {
+    let dir = TempDir::new().unwrap();
+    assert!(cronclaw(dir.path(), &["init"]).status.success());
+    let home = dir.path().join(".cronclaw");
+    std::fs::write(home.join("config.yaml"), "tick_interval_secs: 1\n").unwrap();
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_cronclaw"))
+        .arg("daemon")
+        .env("HOME", dir.path())
+        .spawn()
+        .unwrap();
+    std::thread::sleep(std::time::Duration::from_millis(1500));
+    unsafe { libc::kill(child.id() as libc::pid_t, libc::SIGTERM) };
+    let status = child.wait().unwrap();
+
+    assert!(status.success());
+    assert!(home.join("pipelines/example/state.json").exists());
+}
+
+// ─── explain ───
+
+#[test]
+fn explain_describes_every_step_without_running() {
+    let dir = TempDir::new().unwrap();
+    assert!(cronclaw(dir.path(), &["init"]).status.success());
+
+    let output = cronclaw(dir.path(), &["explain", "example"]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout.contains("step 1/2: 'gather' (bash, pending)"),
+        "{}",
+        stdout
+    );
+    assert!(stdout.contains("output: report (report.txt.tmp -> report.txt)"));
+    assert!(stdout.contains("step 2/2: 'summarise' (agent, pending)"));
+    assert!(stdout.contains("needs: gather"));
+    // report.txt doesn't exist until gather has run
+    assert!(stdout.contains("not resolvable yet"));
+
+    let example = dir.path().join(".cronclaw/pipelines/example");
+    assert!(!example.join("state.json").exists());
+    assert!(!example.join("workspace/report.txt").exists());
+}
+
+#[test]
+fn explain_unknown_pipeline_fails() {
+    let dir = TempDir::new().unwrap();
+    assert!(cronclaw(dir.path(), &["init"]).status.success());
+    assert!(!cronclaw(dir.path(), &["explain", "nope"]).status.success());
+}
+
+#[test]
+fn show_prints_one_step_with_its_state_and_files() {
+    let dir = TempDir::new().unwrap();
+    assert!(cronclaw(dir.path(), &["init"]).status.success());
+
+    let output = cronclaw(dir.path(), &["show", "example", "gather"]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("step 'gather' (bash)"), "{}", stdout);
+    assert!(
+        stdout.contains("status: pending (not run yet)"),
+        "{}",
+        stdout
+    );
+    assert!(!stdout.contains("summarise"), "{}", stdout);
+
+    assert!(cronclaw(dir.path(), &["run"]).status.success());
+    let output = cronclaw(dir.path(), &["show", "example", "gather"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("status: completed"), "{}", stdout);
+    assert!(stdout.contains("attempts: 0 of 1"), "{}", stdout);
+    assert!(stdout.contains("file: report.txt ("), "{}", stdout);
+    assert!(!stdout.contains("(missing)"), "{}", stdout);
+}
+
+#[test]
+fn show_unknown_pipeline_or_step_fails() {
+    let dir = TempDir::new().unwrap();
+    assert!(cronclaw(dir.path(), &["init"]).status.success());
+
+    assert!(
+        !cronclaw(dir.path(), &["show", "nope", "gather"])
+            .status
+            .success()
+    );
+    let output = cronclaw(dir.path(), &["show", "example", "nope"]);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("No step 'nope'"), "{}", stderr);
+}
+
+// ─── strict config ───
+
+#[test]
+fn unknown_config_setting_warns_or_fails_with_strict() {
+    let dir = TempDir::new().unwrap();
+    assert!(
+        cronclaw(dir.path(), &["init", "--no-example"])
+            .status
+            .success()
+    );
+    std::fs::write(dir.path().join(".cronclaw/config.yaml"), "timout: 600\n").unwrap();
+
+    let output = cronclaw(dir.path(), &["run"]);
+    assert!(output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("warning:"), "{}", stderr);
+    assert!(stderr.contains("unknown setting 'timout'"), "{}", stderr);
+
+    let output = cronclaw(dir.path(), &["run", "--strict"]);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("error:"), "{}", stderr);
+}
+
+#[test]
+fn invalid_config_fails_even_without_strict() {
+    let dir = TempDir::new().unwrap();
+    assert!(
+        cronclaw(dir.path(), &["init", "--no-example"])
+            .status
+            .success()
+    );
+    std::fs::write(dir.path().join(".cronclaw/config.yaml"), "timeout: soon\n").unwrap();
+
+    let output = cronclaw(dir.path(), &["run"]);
+    assert_eq!(output.status.code(), Some(3));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("error: failed to parse"), "{}", stderr);
+    assert!(stderr.contains("timeout"), "{}", stderr);
+}
+
+// ─── step descriptions ───
+
+#[test]
+fn step_description_is_shown_when_running_and_in_status() {
+    let dir = TempDir::new().unwrap();
+    assert!(
+        cronclaw(dir.path(), &["init", "--no-example"])
+            .status
+            .success()
+    );
+    let pipeline = dir.path().join(".cronclaw/pipelines/described");
+    std::fs::create_dir_all(&pipeline).unwrap();
+    std::fs::write(
+        pipeline.join("pipeline.yaml"),
+        "version: 1\nworkspace: workspace\nsteps:\n  - id: fetch\n    description: Download the latest prices\n    type: bash\n    bash: echo fetched\n",
+    )
+    .unwrap();
+
+    let output = cronclaw(dir.path(), &["run", "described"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout.contains("running step 1/1: 'fetch' (bash) — Download the latest prices"),
+        "{}",
+        stdout
+    );
+
+    let output = cronclaw(dir.path(), &["status", "described", "-v"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Download the latest prices"), "{}", stdout);
+}
+
+// ─── tags ───
+
+#[test]
+fn run_tag_only_runs_tagged_pipelines() {
+    let dir = TempDir::new().unwrap();
+    assert!(
+        cronclaw(dir.path(), &["init", "--no-example"])
+            .status
+            .success()
+    );
+    let pipelines = dir.path().join(".cronclaw/pipelines");
+    for (name, tags) in [("a", "[nightly]"), ("b", "[hourly]"), ("c", "[]")] {
+        std::fs::create_dir_all(pipelines.join(name)).unwrap();
+        std::fs::write(
+            pipelines.join(name).join("pipeline.yaml"),
+            format!(
+                "version: 1\nworkspace: workspace\ntags: {}\nsteps:\n  - id: only\n    type: bash\n    bash: echo hi\n",
+                tags
+            ),
+        )
+        .unwrap();
+    }
+
+    let output = cronclaw(dir.path(), &["run", "--tag", "nightly", "--tag", "hourly"]);
+    assert!(output.status.success());
+    assert!(pipelines.join("a/state.json").exists());
+    assert!(pipelines.join("b/state.json").exists());
+    assert!(!pipelines.join("c/state.json").exists());
+
+    let output = cronclaw(dir.path(), &["list", "--tag", "hourly"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.lines().any(|l| l.starts_with("b ")), "{}", stdout);
+    assert!(!stdout.lines().any(|l| l.starts_with("a ")), "{}", stdout);
+
+    // A name and tags don't mix
+    assert!(
+        !cronclaw(dir.path(), &["run", "a", "--tag", "nightly"])
+            .status
+            .success()
+    );
+}
+
+// ─── history ───
+
+#[test]
+fn history_lists_completed_runs() {
+    let dir = TempDir::new().unwrap();
+    assert!(cronclaw(dir.path(), &["init"]).status.success());
+
+    let output = cronclaw(dir.path(), &["history", "example"]);
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout).contains("No completed runs"));
+
+    // gather, then summarise (skipped without openclaw)
+    assert!(cronclaw(dir.path(), &["run", "example"]).status.success());
+    assert!(cronclaw(dir.path(), &["run", "example"]).status.success());
+
+    let output = cronclaw(dir.path(), &["history", "example", "--since", "1h"]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains(", summarise skipped"), "{}", stdout);
+    assert!(stdout.contains("1 run, "), "{}", stdout);
+
+    assert!(
+        !cronclaw(dir.path(), &["history", "example", "--since", "soon"])
+            .status
+            .success()
+    );
+}
+
+// ─── exit codes ───
+
+#[test]
+fn run_exit_code_tells_failures_apart() {
+    let dir = TempDir::new().unwrap();
+    assert!(
+        cronclaw(dir.path(), &["init", "--no-example"])
+            .status
+            .success()
+    );
+    let pipelines = dir.path().join(".cronclaw/pipelines");
+    let write = |name: &str, yaml: &str| {
+        std::fs::create_dir_all(pipelines.join(name)).unwrap();
+        std::fs::write(pipelines.join(name).join("pipeline.yaml"), yaml).unwrap();
+    };
+    let code = |args: &[&str]| cronclaw(dir.path(), args).status.code();
+
+    write(
+        "ok",
+        "version: 1\nworkspace: workspace\nsteps:\n  - id: a\n    type: bash\n    bash: 'true'\n",
+    );
+    assert_eq!(code(&["run", "ok"]), Some(0));
+
+    write(
+        "failing",
+        "version: 1\nworkspace: workspace\nsteps:\n  - id: a\n    type: bash\n    bash: exit 1\n",
+    );
+    assert_eq!(code(&["run", "failing"]), Some(2));
+
+    write("broken", "steps: [");
+    assert_eq!(code(&["run", "broken"]), Some(3));
+    // A failed step outranks a broken pipeline
+    std::fs::remove_file(pipelines.join("failing/state.json")).unwrap();
+    assert_eq!(code(&["run"]), Some(2));
+
+    // A step claimed by a live process elsewhere
+    write(
+        "busy",
+        "version: 1\nworkspace: workspace\nsteps:\n  - id: a\n    type: bash\n    bash: 'true'\n",
+    );
+    std::fs::write(
+        pipelines.join("busy/state.json"),
+        format!(
+            r#"{{"steps": {{"a": {{"status": "running", "pid": {}}}}}}}"#,
+            std::process::id()
+        ),
+    )
+    .unwrap();
+    assert_eq!(code(&["run", "busy"]), Some(4));
+}
+
+#[test]
+fn run_step_timeout_override_cuts_a_step_short_for_one_run() {
+    let dir = TempDir::new().unwrap();
+    assert!(
+        cronclaw(dir.path(), &["init", "--no-example"])
+            .status
+            .success()
+    );
+    let pipeline_dir = dir.path().join(".cronclaw/pipelines/slow");
+    std::fs::create_dir_all(&pipeline_dir).unwrap();
+    std::fs::write(
+        pipeline_dir.join("pipeline.yaml"),
+        "version: 1\nworkspace: workspace\nsteps:\n  - id: nap\n    type: bash\n    timeout: 1h\n    bash: sleep 5\n",
+    )
+    .unwrap();
+
+    let rejected = cronclaw(dir.path(), &["run", "slow", "--step-timeout-override", "0"]);
+    assert!(!rejected.status.success());
+    assert!(!pipeline_dir.join("state.json").exists());
+
+    let output = cronclaw(
+        dir.path(),
+        &["--verbose", "run", "slow", "--step-timeout-override", "1s"],
+    );
+    assert_eq!(output.status.code(), Some(2));
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stdout.contains("step 'nap': timeout overridden to 1s"),
+        "{}",
+        stdout
+    );
+    assert!(stderr.contains("timed out after 1s"), "{}", stderr);
+}
+
+#[test]
+fn run_var_fills_in_pipeline_vars() {
+    let dir = TempDir::new().unwrap();
+    assert!(
+        cronclaw(dir.path(), &["init", "--no-example"])
+            .status
+            .success()
+    );
+    let pipeline_dir = dir.path().join(".cronclaw/pipelines/deploy");
+    std::fs::create_dir_all(&pipeline_dir).unwrap();
+    std::fs::write(
+        pipeline_dir.join("pipeline.yaml"),
+        "version: 1\nworkspace: workspace\nvars:\n  host: null\nsteps:\n  - id: ship\n    type: bash\n    bash: echo \"$HOST\" > host.txt\n    env:\n      HOST: \"{{ var:host }}\"\n",
+    )
+    .unwrap();
+
+    // --var only makes sense for one pipeline
+    assert!(
+        !cronclaw(dir.path(), &["run", "--var", "host=caladan"])
+            .status
+            .success()
+    );
+    assert!(
+        !cronclaw(dir.path(), &["run", "deploy", "--var", "host"])
+            .status
+            .success()
+    );
+
+    let output = cronclaw(dir.path(), &["run", "deploy"]);
+    assert_eq!(output.status.code(), Some(3));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("--var host=VALUE"));
+
+    let output = cronclaw(dir.path(), &["run", "deploy", "--var", "host=caladan"]);
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let host = std::fs::read_to_string(pipeline_dir.join("workspace/host.txt")).unwrap();
+    assert_eq!(host.trim(), "caladan");
+}
+
+#[test]
+fn run_interrupted_by_sigint_exits_130_and_leaves_step_pending() {
+    let dir = TempDir::new().unwrap();
+    assert!(
+        cronclaw(dir.path(), &["init", "--no-example"])
+            .status
+            .success()
+    );
+    let pipeline_dir = dir.path().join(".cronclaw/pipelines/slow");
+    std::fs::create_dir_all(&pipeline_dir).unwrap();
+    std::fs::write(
+        pipeline_dir.join("pipeline.yaml"),
+        "version: 1\nworkspace: workspace\nsteps:\n  - id: nap\n    type: bash\n    bash: touch started && sleep 30\n",
+    )
+    .unwrap();
+
+    let child = Command::new(env!("CARGO_BIN_EXE_cronclaw"))
+        .args(["run", "slow"])
+        .env("HOME", dir.path())
+        .stderr(std::process::Stdio::piped())
+        .spawn()
+        .unwrap();
+    let started = pipeline_dir.join("workspace/started");
+    for _ in 0..100 {
+        if started.exists() {
+            break;
+        }
+        std::thread::sleep(std::time::Duration::from_millis(50));
+    }
+    assert!(started.exists());
+    unsafe { libc::kill(child.id() as libc::pid_t, libc::SIGINT) };
+
+    let output = child.wait_with_output().unwrap();
+    assert_eq!(output.status.code(), Some(130));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("step 'nap' interrupted (terminated), left pending"),
+        "{}",
+        stderr
+    );
+    let state = std::fs::read_to_string(pipeline_dir.join("state.json")).unwrap();
+    assert!(state.contains("\"status\": \"pending\""), "{}", state);
+}
+
+// ─── retry ───
+
+#[test]
+fn retry_resets_failed_step_and_status_shows_attempts_left() {
+    let dir = TempDir::new().unwrap();
+    assert!(
+        cronclaw(dir.path(), &["init", "--no-example"])
+            .status
+            .success()
+    );
+    let pipeline = dir.path().join(".cronclaw/pipelines/flaky");
+    std::fs::create_dir_all(&pipeline).unwrap();
+    std::fs::write(
+        pipeline.join("pipeline.yaml"),
+        "version: 1\nworkspace: workspace\nsteps:\n  - id: a\n    type: bash\n    bash: exit 1\n    retries: 1\n    retry_delay_secs: 0\n",
+    )
+    .unwrap();
+    // One attempt per tick
+    cronclaw(dir.path(), &["run", "flaky"]);
+    let output = cronclaw(dir.path(), &["status", "flaky"]);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("1 of 2 attempts left"));
+    cronclaw(dir.path(), &["run", "flaky"]);
+
+    let output = cronclaw(dir.path(), &["status", "flaky"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("failed"), "{}", stdout);
+    assert!(stdout.contains("0 of 2 attempts left"), "{}", stdout);
+
+    let output = cronclaw(dir.path(), &["retry", "flaky", "a"]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout.contains("failed (2 of 2 attempts used) -> pending"),
+        "{}",
+        stdout
+    );
+
+    let output = cronclaw(dir.path(), &["status", "flaky"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("2 of 2 attempts left"), "{}", stdout);
+
+    let output = cronclaw(dir.path(), &["retry", "flaky", "a"]);
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Nothing to retry"));
+    assert!(
+        !cronclaw(dir.path(), &["retry", "flaky", "missing"])
+            .status
+            .success()
+    );
+}
+
+// ─── pause ───
+
+#[test]
+fn paused_pipeline_keeps_its_progress_until_resumed() {
+    let dir = TempDir::new().unwrap();
+    assert!(
+        cronclaw(dir.path(), &["init", "--no-example"])
+            .status
+            .success()
+    );
+    let pipeline = dir.path().join(".cronclaw/pipelines/two");
+    std::fs::create_dir_all(&pipeline).unwrap();
+    std::fs::write(
+        pipeline.join("pipeline.yaml"),
+        "version: 1\nworkspace: workspace\nsteps:\n  - id: a\n    type: bash\n    bash: echo a\n  - id: b\n    type: bash\n    bash: echo b\n",
+    )
+    .unwrap();
+    assert!(cronclaw(dir.path(), &["run", "two"]).status.success());
+
+    let output = cronclaw(dir.path(), &["pause", "two"]);
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Paused pipeline 'two'"));
+    let output = cronclaw(dir.path(), &["pause", "two"]);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("already paused"));
+
+    assert!(cronclaw(dir.path(), &["run", "two"]).status.success());
+    let output = cronclaw(dir.path(), &["status", "two"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout.contains("two: in progress, paused (1/2"),
+        "{}",
+        stdout
+    );
+    let output = cronclaw(dir.path(), &["list"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("in progress, paused"), "{}", stdout);
+
+    let output = cronclaw(dir.path(), &["resume", "two"]);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Resumed pipeline 'two'"));
+    assert!(cronclaw(dir.path(), &["run", "two"]).status.success());
+    let output = cronclaw(dir.path(), &["status", "two"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("two: completed (2/2"), "{}", stdout);
+
+    let output = cronclaw(dir.path(), &["resume", "two"]);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("isn't paused"));
+    assert!(!cronclaw(dir.path(), &["pause", "nope"]).status.success());
+}
+
+// ─── check ───
+
+#[test]
+fn check_names_steps_that_drifted_from_state() {
+    let dir = TempDir::new().unwrap();
+    assert!(
+        cronclaw(dir.path(), &["init", "--no-example"])
+            .status
+            .success()
+    );
+    let pipeline = dir.path().join(".cronclaw/pipelines/two");
+    std::fs::create_dir_all(&pipeline).unwrap();
+    let yaml = "version: 1\nworkspace: workspace\nsteps:\n  - id: a\n    type: bash\n    bash: echo a\n  - id: b\n    type: bash\n    bash: echo b\n";
+    std::fs::write(pipeline.join("pipeline.yaml"), yaml).unwrap();
+
+    let output = cronclaw(dir.path(), &["check", "two"]);
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout).contains("no state yet"));
+
+    assert!(cronclaw(dir.path(), &["run", "two"]).status.success());
+    let output = cronclaw(dir.path(), &["check", "two"]);
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout).contains("matches pipeline.yaml"));
+
+    std::fs::write(
+        pipeline.join("pipeline.yaml"),
+        yaml.replace("id: b", "id: c"),
+    )
+    .unwrap();
+    let output = cronclaw(dir.path(), &["check", "two"]);
+    assert_eq!(output.status.code(), Some(1));
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("added:   c"), "{}", stdout);
+    assert!(stdout.contains("removed: b"), "{}", stdout);
+    assert!(stdout.contains("cronclaw reset two"), "{}", stdout);
+    // Checking touches nothing
+    assert!(
+        std::fs::read_to_string(pipeline.join("state.json"))
+            .unwrap()
+            .contains("\"b\"")
+    );
+}
+
+// ─── verbose ───
+
+#[test]
+fn verbose_run_prints_the_openclaw_command() {
+    let dir = TempDir::new().unwrap();
+    assert!(
+        cronclaw(dir.path(), &["init", "--no-example"])
+            .status
+            .success()
+    );
+    let pipeline = dir.path().join(".cronclaw/pipelines/agent");
+    std::fs::create_dir_all(&pipeline).unwrap();
+    std::fs::write(
+        pipeline.join("pipeline.yaml"),
+        "version: 1\nworkspace: workspace\nsteps:\n  - id: ask\n    type: agent\n    agent: worker\n    prompt: |\n      Summarise the report\n      {{ env:HOME }}\n",
+    )
+    .unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_cronclaw"))
+        .args(["-v", "run", "agent"])
+        .env("HOME", dir.path())
+        .env("OPENCLAW_BIN", "true")
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout.contains("openclaw command: true agent --message 'Summarise the report... ("),
+        "{}",
+        stdout
+    );
+    assert!(
+        stdout.contains("--to worker --local --timeout 300"),
+        "{}",
+        stdout
+    );
+
+    // Not without --verbose
+    std::fs::remove_file(pipeline.join("state.json")).unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_cronclaw"))
+        .args(["run", "agent"])
+        .env("HOME", dir.path())
+        .env("OPENCLAW_BIN", "true")
+        .output()
+        .unwrap();
+    assert!(!String::from_utf8_lossy(&output.stdout).contains("openclaw command"));
+}
+
+// ─── quiet ───
+
+#[test]
+fn quiet_run_only_reports_failures() {
+    let dir = TempDir::new().unwrap();
+    assert!(cronclaw(dir.path(), &["init"]).status.success());
+
+    let output = cronclaw(dir.path(), &["run", "--quiet"]);
+    assert!(output.status.success());
+    assert!(
+        output.stdout.is_empty(),
+        "{}",
+        String::from_utf8_lossy(&output.stdout)
+    );
+
+    // Without --quiet the same tick is reported
+    let output = cronclaw(dir.path(), &["reset", "example"]);
+    assert!(output.status.success());
+    let output = cronclaw(dir.path(), &["run"]);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("running step 1/2"));
+
+    let pipeline = dir.path().join(".cronclaw/pipelines/failing");
+    std::fs::create_dir_all(&pipeline).unwrap();
+    std::fs::write(
+        pipeline.join("pipeline.yaml"),
+        "version: 1\nworkspace: workspace\nsteps:\n  - id: a\n    type: bash\n    bash: exit 1\n",
+    )
+    .unwrap();
+    let output = cronclaw(dir.path(), &["run", "failing", "-q"]);
+    assert_eq!(output.status.code(), Some(2));
+    assert!(output.stdout.is_empty());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("exited with code 1"));
+}
+
+// ─── stream routing ───
+
+#[test]
+fn step_output_goes_only_where_the_step_routes_it() {
+    let dir = TempDir::new().unwrap();
+    assert!(
+        cronclaw(dir.path(), &["init", "--no-example"])
+            .status
+            .success()
+    );
+    let pipeline = dir.path().join(".cronclaw/pipelines/routed");
+    std::fs::create_dir_all(pipeline.join("workspace")).unwrap();
+    std::fs::write(
+        pipeline.join("pipeline.yaml"),
+        r#"version: 1
+workspace: workspace
+steps:
+  - id: shown
+    type: bash
+    bash: echo to-terminal
+  - id: silenced
+    type: bash
+    bash: echo to-void; echo void-err >&2
+    output: null
+    error: null
+  - id: filed
+    type: bash
+    bash: echo to-file; echo file-err >&2
+    output: out.txt
+    error: err.txt
+"#,
+    )
+    .unwrap();
+
+    let mut stdout = String::new();
+    let mut stderr = String::new();
+    for _ in 0..3 {
+        let output = cronclaw(dir.path(), &["run", "routed"]);
+        assert!(output.status.success());
+        stdout.push_str(&String::from_utf8_lossy(&output.stdout));
+        stderr.push_str(&String::from_utf8_lossy(&output.stderr));
+    }
+
+    assert!(stdout.contains("to-terminal"), "{}", stdout);
+    for hidden in ["to-void", "void-err", "to-file", "file-err"] {
+        assert!(!stdout.contains(hidden), "{}", stdout);
+        assert!(!stderr.contains(hidden), "{}", stderr);
+    }
+    let workspace = pipeline.join("workspace");
+    assert_eq!(
+        std::fs::read_to_string(workspace.join("out.txt")).unwrap(),
+        "to-file\n"
+    );
+    assert_eq!(
+        std::fs::read_to_string(workspace.join("err.txt")).unwrap(),
+        "file-err\n"
+    );
+}
+
+// ─── colour ───
+
+#[test]
+fn status_is_coloured_only_when_asked_for() {
+    let dir = TempDir::new().unwrap();
+    assert!(cronclaw(dir.path(), &["init"]).status.success());
+    assert!(cronclaw(dir.path(), &["run"]).status.success());
+    let status = |env: &[(&str, &str)]| {
+        let output = Command::new(env!("CARGO_BIN_EXE_cronclaw"))
+            .arg("status")
+            .env("HOME", dir.path())
+            .env_remove("NO_COLOR")
+            .env_remove("CLICOLOR_FORCE")
+            .envs(env.iter().copied())
+            .output()
+            .unwrap();
+        String::from_utf8_lossy(&output.stdout).into_owned()
+    };
+
+    // Piped output stays plain
+    assert!(!status(&[]).contains('\x1b'));
+    let forced = status(&[("CLICOLOR_FORCE", "1")]);
+    assert!(forced.contains("\x1b[32mcompleted"), "{}", forced);
+    assert!(!status(&[("CLICOLOR_FORCE", "1"), ("NO_COLOR", "1")]).contains('\x1b'));
+}
+
+#[test]
+fn status_json_lists_steps_in_pipeline_order() {
+    let dir = TempDir::new().unwrap();
+    assert!(
+        cronclaw(dir.path(), &["init", "--no-example"])
+            .status
+            .success()
+    );
+    let pipeline = dir.path().join(".cronclaw/pipelines/two");
+    std::fs::create_dir_all(&pipeline).unwrap();
+    let yaml = "version: 1\nworkspace: workspace\nsteps:\n  - id: zeta\n    type: bash\n    bash: echo z\n  - id: alpha\n    type: bash\n    bash: exit 3\n";
+    std::fs::write(pipeline.join("pipeline.yaml"), yaml).unwrap();
+    assert!(cronclaw(dir.path(), &["run", "two"]).status.success());
+    cronclaw(dir.path(), &["run", "two"]);
+
+    let output = cronclaw(dir.path(), &["status", "--json"]);
+    assert_eq!(output.status.code(), Some(1));
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let pipelines = report.as_array().unwrap();
+    assert_eq!(pipelines.len(), 1);
+    assert_eq!(pipelines[0]["name"], "two");
+    assert_eq!(pipelines[0]["status"], "failed");
+    assert_eq!(pipelines[0]["paused"], false);
+    let steps = pipelines[0]["steps"].as_array().unwrap();
+    let ids: Vec<&str> = steps.iter().map(|s| s["id"].as_str().unwrap()).collect();
+    assert_eq!(ids, ["zeta", "alpha"]);
+    assert_eq!(steps[0]["type"], "bash");
+    assert_eq!(steps[0]["status"], "completed");
+    assert!(steps[0]["finished_at"].is_string());
+    assert_eq!(steps[1]["status"], "failed");
+    assert_eq!(steps[1]["attempts"], 1);
+    assert_eq!(steps[1]["exit_code"], 3);
+}
+
+// ─── tail ───
+
+#[test]
+fn tail_follows_steps_until_pipeline_completes() {
+    let dir = TempDir::new().unwrap();
+    assert!(
+        cronclaw(dir.path(), &["init", "--no-example"])
+            .status
+            .success()
+    );
+    let pipeline = dir.path().join(".cronclaw/pipelines/two");
+    std::fs::create_dir_all(&pipeline).unwrap();
+    std::fs::write(
+        pipeline.join("pipeline.yaml"),
+        "version: 1\nworkspace: workspace\nsteps:\n  - id: first\n    type: bash\n    bash: 'true'\n  - id: second\n    type: bash\n    bash: 'true'\n",
+    )
+    .unwrap();
+
+    let child = Command::new(env!("CARGO_BIN_EXE_cronclaw"))
+        .args(["tail", "two", "--interval", "1"])
+        .env("HOME", dir.path())
+        .stdout(std::process::Stdio::piped())
+        .spawn()
+        .unwrap();
+    for _ in 0..2 {
+        std::thread::sleep(std::time::Duration::from_millis(1500));
+        assert!(cronclaw(dir.path(), &["run", "two"]).status.success());
+    }
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success());
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.starts_with("two: not started"), "{}", stdout);
+    assert!(stdout.contains("first: pending -> completed"), "{}", stdout);
+    assert!(
+        stdout.contains("second: pending -> completed"),
+        "{}",
+        stdout
+    );
+    assert!(
+        stdout.contains("two: completed (2/2 steps completed)"),
+        "{}",
+        stdout
+    );
+
+    // Already done: print once and stop; nothing is run
+    let output = cronclaw(dir.path(), &["tail", "two"]);
+    assert!(output.status.success());
+    assert!(!String::from_utf8_lossy(&output.stdout).contains("->"));
+}
@@ -5,7 +5,7 @@ use tempfile::TempDir;
 #[test]
 fn config_defaults_when_missing() {
     let dir = TempDir::new().unwrap();
-    let cfg = config::load(&dir.path().join("nope.yaml"));
+    let cfg = config::load(&dir.path().join("nope.yaml")).unwrap();
     assert_eq!(cfg.timeout, 300);
 }
 
@@ -14,7 +14,7 @@ fn config_defaults_when_empty_file() {
     let dir = TempDir::new().unwrap();
     let path = dir.path().join("config.yaml");
     fs::write(&path, "").unwrap();
-    let cfg = config::load(&path);
+    let cfg = config::load(&path).unwrap();
     assert_eq!(cfg.timeout, 300);
 }
 
@@ -23,7 +23,7 @@ fn config_defaults_when_comment_only() {
     let dir = TempDir::new().unwrap();
     let path = dir.path().join("config.yaml");
     fs::write(&path, "# cronclaw configuration\n").unwrap();
-    let cfg = config::load(&path);
+    let cfg = config::load(&path).unwrap();
     assert_eq!(cfg.timeout, 300);
 }
 
@@ -32,6 +32,247 @@ fn config_custom_timeout() {
     let dir = TempDir::new().unwrap();
     let path = dir.path().join("config.yaml");
     fs::write(&path, "timeout: 600\n").unwrap();
-    let cfg = config::load(&path);
+    let cfg = config::load(&path).unwrap();
     assert_eq!(cfg.timeout, 600);
 }
+
+#[test]
+fn config_timeout_as_duration() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("config.yaml");
+    fs::write(&path, "timeout: 10m\n").unwrap();
+    assert_eq!(config::load(&path).unwrap().timeout, 600);
+
+    config::set(&path, "timeout", "2h").unwrap();
+    assert_eq!(config::load(&path).unwrap().timeout, 7200);
+
+    fs::write(&path, "max_timeout: 1d\n").unwrap();
+    assert_eq!(config::load(&path).unwrap().max_timeout, Some(86400));
+
+    fs::write(&path, "timeout: 10 minutes\n").unwrap();
+    assert!(config::load(&path).is_err());
+}
+
+#[test]
+fn parse_duration_units() {
+    assert_eq!(config::parse_duration("90").unwrap(), 90);
+    assert_eq!(config::parse_duration("90s").unwrap(), 90);
+    assert_eq!(config::parse_duration("30m").unwrap(), 1800);
+    assert_eq!(config::parse_duration("2h").unwrap(), 7200);
+    assert!(config::parse_duration("2 hours").is_err());
+    assert!(config::parse_duration("m").is_err());
+    assert!(config::parse_duration("").is_err());
+}
+
+#[test]
+fn config_default_shell() {
+    let dir = TempDir::new().unwrap();
+    let cfg = config::load(&dir.path().join("nope.yaml")).unwrap();
+    assert_eq!(cfg.shell, "sh");
+}
+
+#[test]
+fn config_custom_shell() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("config.yaml");
+    fs::write(&path, "shell: bash\n").unwrap();
+    let cfg = config::load(&path).unwrap();
+    assert_eq!(cfg.shell, "bash");
+    assert_eq!(cfg.timeout, 300);
+}
+
+#[test]
+fn config_kill_grace_default_and_override() {
+    let dir = TempDir::new().unwrap();
+    let cfg = config::load(&dir.path().join("nope.yaml")).unwrap();
+    assert_eq!(cfg.kill_grace_secs, 5);
+
+    let path = dir.path().join("config.yaml");
+    fs::write(&path, "kill_grace_secs: 30\n").unwrap();
+    let cfg = config::load(&path).unwrap();
+    assert_eq!(cfg.kill_grace_secs, 30);
+}
+
+#[test]
+fn config_max_log_bytes() {
+    let dir = TempDir::new().unwrap();
+    let cfg = config::load(&dir.path().join("nope.yaml")).unwrap();
+    assert_eq!(cfg.max_log_bytes, 1024 * 1024);
+
+    let path = dir.path().join("config.yaml");
+    fs::write(&path, "max_log_bytes: 4096\n").unwrap();
+    let cfg = config::load(&path).unwrap();
+    assert_eq!(cfg.max_log_bytes, 4096);
+}
+
+#[test]
+fn config_max_concurrent_pipelines() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("config.yaml");
+    assert_eq!(config::load(&path).unwrap().max_concurrent_pipelines, 1);
+
+    fs::write(&path, "max_concurrent_pipelines: 4\n").unwrap();
+    assert_eq!(config::load(&path).unwrap().max_concurrent_pipelines, 4);
+}
+
+#[test]
+fn config_max_parallel() {
+    let dir = TempDir::new().unwrap();
+    let cfg = config::load(&dir.path().join("nope.yaml")).unwrap();
+    assert_eq!(cfg.max_parallel, 1);
+
+    let path = dir.path().join("config.yaml");
+    fs::write(&path, "max_parallel: 4\n").unwrap();
+    let cfg = config::load(&path).unwrap();
+    assert_eq!(cfg.max_parallel, 4);
+}
+
+#[test]
+fn config_max_output_bytes() {
+    let dir = TempDir::new().unwrap();
+    let cfg = config::load(&dir.path().join("nope.yaml")).unwrap();
+    assert_eq!(cfg.max_output_bytes, 10 * 1024 * 1024);
+
+    let path = dir.path().join("config.yaml");
+    fs::write(&path, "max_output_bytes: 0\n").unwrap();
+    let cfg = config::load(&path).unwrap();
+    assert_eq!(cfg.max_output_bytes, 0);
+}
+
+#[test]
+fn config_max_timeout() {
+    let dir = TempDir::new().unwrap();
+    let cfg = config::load(&dir.path().join("nope.yaml")).unwrap();
+    assert_eq!(cfg.max_timeout, None);
+
+    let path = dir.path().join("config.yaml");
+    fs::write(&path, "max_timeout: 3600\n").unwrap();
+    let cfg = config::load(&path).unwrap();
+    assert_eq!(cfg.max_timeout, Some(3600));
+}
+
+#[test]
+fn config_missing_inputs() {
+    let dir = TempDir::new().unwrap();
+    let cfg = config::load(&dir.path().join("nope.yaml")).unwrap();
+    assert_eq!(cfg.missing_inputs, config::MissingInputs::Wait);
+
+    let path = dir.path().join("config.yaml");
+    fs::write(&path, "missing_inputs: fail\n").unwrap();
+    let cfg = config::load(&path).unwrap();
+    assert_eq!(cfg.missing_inputs, config::MissingInputs::Fail);
+}
+
+#[test]
+fn config_min_free_bytes() {
+    let dir = TempDir::new().unwrap();
+    let cfg = config::load(&dir.path().join("nope.yaml")).unwrap();
+    assert_eq!(cfg.min_free_bytes, None);
+
+    let path = dir.path().join("config.yaml");
+    config::set(&path, "min_free_bytes", "1073741824").unwrap();
+    let cfg = config::load(&path).unwrap();
+    assert_eq!(cfg.min_free_bytes, Some(1073741824));
+}
+
+#[test]
+fn config_get_prints_effective_value() {
+    let cfg = config::Config::default();
+    assert_eq!(config::get(&cfg, "timeout").unwrap(), "300");
+    assert_eq!(config::get(&cfg, "shell").unwrap(), "sh");
+    assert_eq!(config::get(&cfg, "max_timeout").unwrap(), "null");
+    assert!(
+        config::get(&cfg, "timeot")
+            .unwrap_err()
+            .contains("unknown setting")
+    );
+}
+
+#[test]
+fn config_set_replaces_commented_default_and_keeps_comments() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("config.yaml");
+    fs::write(
+        &path,
+        "# Step timeout\n# timeout: 300\n\nshell: sh # for bash steps\n",
+    )
+    .unwrap();
+
+    config::set(&path, "timeout", "600").unwrap();
+    config::set(&path, "shell", "bash").unwrap();
+    config::set(&path, "max_parallel", "2").unwrap();
+
+    assert_eq!(
+        fs::read_to_string(&path).unwrap(),
+        "# Step timeout\ntimeout: 600\n\nshell: bash\nmax_parallel: 2\n"
+    );
+    let cfg = config::load(&path).unwrap();
+    assert_eq!(cfg.timeout, 600);
+    assert_eq!(cfg.shell, "bash");
+    assert_eq!(cfg.max_parallel, 2);
+}
+
+#[test]
+fn config_set_takes_commands_as_text() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("config.yaml");
+
+    config::set(&path, "on_failure", "echo \"failed: $CRONCLAW_STEP\"").unwrap();
+    let cfg = config::load(&path).unwrap();
+    assert_eq!(
+        cfg.on_failure.as_deref(),
+        Some("echo \"failed: $CRONCLAW_STEP\"")
+    );
+}
+
+#[test]
+fn config_set_rejects_unknown_keys_and_bad_values() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("config.yaml");
+    fs::write(&path, "timeout: 60\n").unwrap();
+
+    let err = config::set(&path, "timout", "600").unwrap_err();
+    assert!(err.contains("unknown setting 'timout'"), "got: {}", err);
+    let err = config::set(&path, "timeout", "soon").unwrap_err();
+    assert!(err.contains("invalid value for 'timeout'"), "got: {}", err);
+    let err = config::set(&path, "missing_inputs", "maybe").unwrap_err();
+    assert!(err.contains("missing_inputs"), "got: {}", err);
+
+    assert_eq!(fs::read_to_string(&path).unwrap(), "timeout: 60\n");
+}
+
+#[test]
+fn config_problems_reports_unknown_settings() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("config.yaml");
+    assert!(config::problems(&path).is_empty());
+
+    fs::write(&path, "# all defaults\n").unwrap();
+    assert!(config::problems(&path).is_empty());
+
+    fs::write(&path, "timout: 600\nshell: bash\n").unwrap();
+    assert_eq!(
+        config::problems(&path),
+        ["unknown setting 'timout' is ignored"]
+    );
+    // Still loads, with the typo'd setting at its default
+    assert_eq!(config::load(&path).unwrap().timeout, 300);
+}
+
+#[test]
+fn config_load_errors_on_invalid_file() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("config.yaml");
+    fs::write(&path, "shell: bash\ntimeout: soon\n").unwrap();
+
+    let err = config::load(&path).unwrap_err();
+    assert!(err.contains("config.yaml"), "got: {}", err);
+    assert!(err.contains("timeout"), "got: {}", err);
+    // Reported by load, not again as an ignored setting
+    assert!(config::problems(&path).is_empty());
+
+    fs::write(&path, "timeout: [\n").unwrap();
+    assert!(config::load(&path).is_err());
+    fs::write(&path, "just some text\n").unwrap();
+    assert!(config::load(&path).is_err());
+}
@@ -0,0 +1,157 @@
+use cronclaw::config::Config;
+use cronclaw::engine;
+use cronclaw::error::{CronclawError, ErrorKind};
+use cronclaw::runner::RunOptions;
+use cronclaw::state::{self, StepStatus};
+use std::fs;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use tempfile::TempDir;
+
+fn write_pipeline(home: &Path, name: &str, yaml: &str) {
+    let dir = home.join("pipelines").join(name);
+    fs::create_dir_all(dir.join("workspace")).unwrap();
+    fs::write(dir.join("pipeline.yaml"), yaml).unwrap();
+}
+
+const ECHO: &str =
+    "version: 1\nworkspace: workspace\nsteps:\n  - id: hello\n    type: bash\n    bash: echo hi\n";
+
+#[test]
+fn pipeline_dirs_lists_only_pipelines_sorted() {
+    let home = TempDir::new().unwrap();
+    write_pipeline(home.path(), "b", ECHO);
+    write_pipeline(home.path(), "a", ECHO);
+    fs::create_dir_all(home.path().join("pipelines/not-a-pipeline")).unwrap();
+
+    let dirs = engine::pipeline_dirs(home.path()).unwrap();
+    let names: Vec<_> = dirs.iter().map(|d| d.file_name().unwrap()).collect();
+    assert_eq!(names, ["a", "b"]);
+}
+
+#[test]
+fn pipeline_dirs_errors_without_pipelines_dir() {
+    let home = TempDir::new().unwrap();
+    assert!(matches!(
+        engine::pipeline_dirs(home.path()),
+        Err(CronclawError::Io(_))
+    ));
+    let err = engine::run_all(home.path(), &Config::default(), &RunOptions::default()).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::Other);
+}
+
+#[test]
+fn run_all_reports_each_pipeline() {
+    let home = TempDir::new().unwrap();
+    write_pipeline(home.path(), "good", ECHO);
+    write_pipeline(home.path(), "broken", "steps: [");
+
+    let report = engine::run_all(home.path(), &Config::default(), &RunOptions::default()).unwrap();
+    assert_eq!(report.pipelines.len(), 2);
+    assert!(!report.is_ok());
+
+    let failures: Vec<_> = report.failures().map(|p| p.name.as_str()).collect();
+    assert_eq!(failures, ["broken"]);
+
+    let good = &report.pipelines[1];
+    assert_eq!(good.name, "good");
+    assert!(good.error.is_none());
+    let state = state::load(&good.dir.join("state.json")).unwrap().unwrap();
+    assert_eq!(state.steps["hello"].status, StepStatus::Completed);
+}
+
+#[test]
+fn run_all_with_no_pipelines_is_ok() {
+    let home = TempDir::new().unwrap();
+    fs::create_dir_all(home.path().join("pipelines")).unwrap();
+
+    let report = engine::run_all(home.path(), &Config::default(), &RunOptions::default()).unwrap();
+    assert!(report.pipelines.is_empty());
+    assert!(report.is_ok());
+}
+
+#[test]
+fn daemon_ticks_until_stopped() {
+    let home = TempDir::new().unwrap();
+    let two = "version: 1\nworkspace: workspace\nsteps:\n  - id: a\n    type: bash\n    bash: echo a\n  - id: b\n    type: bash\n    bash: echo b\n";
+    write_pipeline(home.path(), "two", two);
+
+    let cfg = Config {
+        tick_interval_secs: 1,
+        ..Config::default()
+    };
+    let stop = AtomicBool::new(false);
+    let mut ticks = 0;
+    engine::daemon(home.path(), &cfg, &RunOptions::default(), &stop, |report| {
+        assert!(report.unwrap().is_ok());
+        ticks += 1;
+        if ticks == 2 {
+            stop.store(true, Ordering::SeqCst);
+        }
+    });
+
+    assert_eq!(ticks, 2);
+    let state = state::load(&home.path().join("pipelines/two/state.json"))
+        .unwrap()
+        .unwrap();
+    assert_eq!(state.steps["a"].status, StepStatus::Completed);
+    assert_eq!(state.steps["b"].status, StepStatus::Completed);
+}
+
+#[test]
+fn run_pipelines_ticks_concurrently_up_to_limit() {
+    let home = TempDir::new().unwrap();
+    let slow = "version: 1\nworkspace: workspace\nsteps:\n  - id: nap\n    type: bash\n    bash: sleep 1\n";
+    for name in ["a", "b", "c", "d"] {
+        write_pipeline(home.path(), name, slow);
+    }
+    write_pipeline(home.path(), "broken", "steps: [");
+    let dirs = engine::pipeline_dirs(home.path()).unwrap();
+
+    let cfg = Config {
+        max_concurrent_pipelines: 4,
+        ..Config::default()
+    };
+    let started = std::time::Instant::now();
+    let report = engine::run_pipelines(&dirs, &cfg, &RunOptions::default());
+    // Serially this would take at least 4s
+    assert!(started.elapsed() < std::time::Duration::from_millis(3500));
+
+    // Results keep the order of the directories they were given
+    let names: Vec<_> = report.pipelines.iter().map(|p| p.name.as_str()).collect();
+    assert_eq!(names, ["a", "b", "broken", "c", "d"]);
+    let failures: Vec<_> = report.failures().map(|p| p.name.as_str()).collect();
+    assert_eq!(failures, ["broken"]);
+    for name in ["a", "b", "c", "d"] {
+        let state = state::load(&home.path().join("pipelines").join(name).join("state.json"))
+            .unwrap()
+            .unwrap();
+        assert_eq!(state.steps["nap"].status, StepStatus::Completed);
+    }
+}
+
+#[test]
+fn has_any_tag_matches_union_of_tags() {
+    let home = TempDir::new().unwrap();
+    write_pipeline(
+        home.path(),
+        "nightly",
+        "version: 1\nworkspace: workspace\ntags: [nightly, reports]\nsteps: []\n",
+    );
+    write_pipeline(home.path(), "untagged", ECHO);
+    write_pipeline(home.path(), "broken", "steps: [");
+    let dir = |name: &str| home.path().join("pipelines").join(name);
+    let tags = |tags: &[&str]| tags.iter().map(|t| t.to_string()).collect::<Vec<_>>();
+
+    assert!(engine::has_any_tag(&dir("nightly"), &tags(&["nightly"])));
+    assert!(engine::has_any_tag(
+        &dir("nightly"),
+        &tags(&["hourly", "reports"])
+    ));
+    assert!(!engine::has_any_tag(&dir("nightly"), &tags(&["hourly"])));
+    assert!(!engine::has_any_tag(&dir("untagged"), &tags(&["nightly"])));
+    assert!(!engine::has_any_tag(&dir("broken"), &tags(&["nightly"])));
+
+    // No filter matches everything
+    assert!(engine::has_any_tag(&dir("untagged"), &[]));
+}
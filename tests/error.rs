@@ -0,0 +1,65 @@
+use cronclaw::error::{CronclawError, ErrorKind};
+
+#[test]
+fn error_kinds() {
+    let failed = CronclawError::StepFailed {
+        id: "build".to_string(),
+        code: Some(2),
+        message: "step 'build' failed".to_string(),
+    };
+    assert_eq!(failed.kind(), ErrorKind::Step);
+    let timeout = CronclawError::Timeout {
+        id: "slow".to_string(),
+        message: "timed out".to_string(),
+    };
+    assert_eq!(timeout.kind(), ErrorKind::Step);
+    assert_eq!(
+        CronclawError::Parse("bad".into()).kind(),
+        ErrorKind::Invalid
+    );
+    assert_eq!(
+        CronclawError::StateMismatch("mismatch".into()).kind(),
+        ErrorKind::Invalid
+    );
+    assert_eq!(CronclawError::Io("disk".into()).kind(), ErrorKind::Other);
+    assert_eq!(
+        CronclawError::Template("typo".into()).kind(),
+        ErrorKind::Other
+    );
+}
+
+#[test]
+fn several_errors_rank_a_failed_step_first_and_print_one_per_line() {
+    let several = CronclawError::Several(vec![
+        CronclawError::Io("disk full".to_string()),
+        CronclawError::StepFailed {
+            id: "bad".to_string(),
+            code: Some(1),
+            message: "step 'bad' failed".to_string(),
+        },
+    ]);
+    assert_eq!(several.kind(), ErrorKind::Step);
+    assert_eq!(several.to_string(), "disk full\nstep 'bad' failed");
+
+    let several = CronclawError::Several(vec![
+        CronclawError::Parse("bad yaml".to_string()),
+        CronclawError::Io("disk full".to_string()),
+    ]);
+    assert_eq!(several.kind(), ErrorKind::Invalid);
+}
+
+#[test]
+fn several_errors_rank_an_invalid_file_over_other_problems_in_any_order() {
+    let several = CronclawError::Several(vec![
+        CronclawError::Io("disk full".to_string()),
+        CronclawError::Parse("bad yaml".to_string()),
+    ]);
+    assert_eq!(several.kind(), ErrorKind::Invalid);
+
+    let several = CronclawError::Several(vec![
+        CronclawError::Template("typo".to_string()),
+        CronclawError::Io("disk full".to_string()),
+    ]);
+    assert_eq!(several.kind(), ErrorKind::Other);
+    assert_eq!(CronclawError::Several(Vec::new()).kind(), ErrorKind::Other);
+}
@@ -0,0 +1,76 @@
+use cronclaw::graph;
+use cronclaw::pipeline;
+use cronclaw::state::{State, StepStatus};
+
+fn fan_in() -> pipeline::Pipeline {
+    pipeline::parse(
+        r#"
+version: 1
+workspace: workspace
+steps:
+  - id: fetch-a
+    type: bash
+    bash: ./fetch.sh a
+  - id: fetch-b
+    type: bash
+    bash: ./fetch.sh b
+    needs: []
+  - id: merge
+    type: agent
+    agent: writer
+    prompt: merge them
+    needs: [fetch-a, fetch-b]
+"#,
+    )
+    .unwrap()
+}
+
+#[test]
+fn dot_lists_steps_and_dependency_edges() {
+    let p = fan_in();
+    let dot = graph::to_dot("demo", &p, &State::from_pipeline(&p));
+
+    assert!(dot.starts_with("digraph \"demo\" {"));
+    assert!(
+        dot.contains(r#""merge" [label="merge\n(agent)""#),
+        "got:\n{}",
+        dot
+    );
+    assert!(dot.contains(r#""fetch-a" -> "merge";"#));
+    assert!(dot.contains(r#""fetch-b" -> "merge";"#));
+    // fetch-b opted out of the implicit dependency on fetch-a
+    assert!(!dot.contains(r#""fetch-a" -> "fetch-b""#));
+    assert!(dot.trim_end().ends_with('}'));
+}
+
+#[test]
+fn dot_colours_steps_by_status() {
+    let p = fan_in();
+    let mut s = State::from_pipeline(&p);
+    s.steps.get_mut("fetch-a").unwrap().status = StepStatus::Completed;
+    s.steps.get_mut("fetch-b").unwrap().status = StepStatus::Failed;
+
+    let dot = graph::to_dot("demo", &p, &s);
+    let fill = |id: &str| {
+        let line = dot
+            .lines()
+            .find(|l| l.trim_start().starts_with(&format!("\"{}\" [", id)))
+            .unwrap();
+        line.split("fillcolor=").nth(1).unwrap().to_string()
+    };
+    assert_ne!(fill("fetch-a"), fill("fetch-b"));
+    assert_ne!(fill("fetch-a"), fill("merge"));
+    assert_ne!(fill("fetch-b"), fill("merge"));
+}
+
+#[test]
+fn mermaid_uses_safe_node_ids() {
+    let p = fan_in();
+    let mermaid = graph::to_mermaid(&p, &State::from_pipeline(&p));
+
+    assert!(mermaid.starts_with("flowchart LR\n"));
+    assert!(mermaid.contains("s2[\"merge (agent)\"]"));
+    assert!(mermaid.contains("s0 --> s2"));
+    assert!(mermaid.contains("s1 --> s2"));
+    assert!(mermaid.contains("style s0 fill:"));
+}
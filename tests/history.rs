@@ -0,0 +1,89 @@
+use cronclaw::history::{self, Run, StepRun};
+use cronclaw::state::{State, StepState, StepStatus};
+use std::collections::BTreeMap;
+use std::fs;
+use tempfile::TempDir;
+
+fn completed(started_at: &str, duration_secs: u64) -> StepState {
+    let mut step = StepState::new();
+    step.status = StepStatus::Completed;
+    step.started_at = Some(started_at.to_string());
+    step.duration_secs = Some(duration_secs);
+    step
+}
+
+#[test]
+fn run_from_state_starts_at_first_step_in_order() {
+    let mut steps = BTreeMap::new();
+    steps.insert("b".to_string(), completed("2020-01-01T00:00:10Z", 5));
+    let mut a = completed("2020-01-01T00:00:00Z", 3);
+    // An earlier attempt counts towards the run
+    a.first_started_at = Some("2019-12-31T23:59:00Z".to_string());
+    steps.insert("a".to_string(), a);
+    let state = State {
+        steps,
+        order: vec!["b".to_string(), "a".to_string()],
+        last_tick: None,
+        watched: BTreeMap::new(),
+        tick_count: 0,
+        last_run_at: None,
+        cleanup: BTreeMap::new(),
+        run_workspace: None,
+    };
+
+    let run = Run::from_state(&state);
+    assert_eq!(run.started_at.as_deref(), Some("2019-12-31T23:59:00Z"));
+    assert!(run.duration_secs.unwrap() > 60);
+    let ids: Vec<_> = run.steps.iter().map(|s| s.id.as_str()).collect();
+    assert_eq!(ids, ["b", "a"]);
+    assert_eq!(run.steps[0].duration_secs, Some(5));
+}
+
+#[test]
+fn append_and_load_round_trip() {
+    let dir = TempDir::new().unwrap();
+    assert!(history::load(dir.path()).unwrap().is_empty());
+
+    for finished_at in ["2020-01-01T00:00:00Z", "2020-01-02T00:00:00Z"] {
+        let run = Run {
+            started_at: None,
+            finished_at: finished_at.to_string(),
+            duration_secs: Some(7),
+            steps: vec![StepRun {
+                id: "fetch".to_string(),
+                status: StepStatus::Skipped,
+                duration_secs: None,
+            }],
+        };
+        history::append(dir.path(), &run).unwrap();
+    }
+
+    let runs = history::load(dir.path()).unwrap();
+    assert_eq!(runs.len(), 2);
+    assert_eq!(runs[1].finished_at, "2020-01-02T00:00:00Z");
+    assert_eq!(runs[1].steps[0].status, StepStatus::Skipped);
+
+    let content = fs::read_to_string(history::history_path(dir.path())).unwrap();
+    assert_eq!(content.lines().count(), 2);
+}
+
+#[test]
+fn load_reports_bad_line() {
+    let dir = TempDir::new().unwrap();
+    fs::write(history::history_path(dir.path()), "{}\n").unwrap();
+    let err = history::load(dir.path()).unwrap_err();
+    assert!(err.contains("line 1"), "{}", err);
+}
+
+#[test]
+fn parse_age_units() {
+    assert_eq!(history::parse_age("90").unwrap(), 90);
+    assert_eq!(history::parse_age("90s").unwrap(), 90);
+    assert_eq!(history::parse_age("30m").unwrap(), 1800);
+    assert_eq!(history::parse_age("12h").unwrap(), 43200);
+    assert_eq!(history::parse_age("7d").unwrap(), 604800);
+    assert_eq!(history::parse_age("2w").unwrap(), 1209600);
+    assert!(history::parse_age("5x").is_err());
+    assert!(history::parse_age("h").is_err());
+    assert!(history::parse_age("").is_err());
+}
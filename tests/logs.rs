@@ -0,0 +1,47 @@
+use cronclaw::logs::{self, StepLog};
+use std::fs;
+use tempfile::TempDir;
+
+#[test]
+fn step_log_appends_timestamped_lines() {
+    let dir = TempDir::new().unwrap();
+    let log = StepLog::new(dir.path(), "fetch", 0);
+
+    log.line("started (attempt 1)");
+    log.line("completed");
+
+    let content = fs::read_to_string(logs::log_path(dir.path(), "fetch")).unwrap();
+    let lines: Vec<&str> = content.lines().collect();
+    assert_eq!(lines.len(), 2);
+    assert!(lines[0].starts_with('[') && lines[0].ends_with("] started (attempt 1)"));
+    assert!(lines[1].ends_with("] completed"));
+}
+
+#[test]
+fn step_log_writes_streams_and_skips_empty_ones() {
+    let dir = TempDir::new().unwrap();
+    let log = StepLog::new(dir.path(), "fetch", 0);
+
+    log.stream("stdout", b"no trailing newline");
+    log.stream("stderr", b"");
+
+    let content = fs::read_to_string(logs::log_path(dir.path(), "fetch")).unwrap();
+    assert!(content.contains("] stdout:\nno trailing newline\n"));
+    assert!(!content.contains("stderr"));
+}
+
+#[test]
+fn step_log_rotates_past_max_bytes() {
+    let dir = TempDir::new().unwrap();
+    let log = StepLog::new(dir.path(), "fetch", 100);
+    let path = logs::log_path(dir.path(), "fetch");
+
+    log.stream("stdout", &[b'a'; 60]);
+    assert!(!logs::rotated_path(&path).exists());
+
+    log.stream("stdout", &[b'b'; 60]);
+    let rotated = fs::read_to_string(logs::rotated_path(&path)).unwrap();
+    let current = fs::read_to_string(&path).unwrap();
+    assert!(rotated.contains("aaa") && !rotated.contains("bbb"));
+    assert!(current.contains("bbb") && !current.contains("aaa"));
+}
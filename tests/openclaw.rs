@@ -1,9 +1,15 @@
-use cronclaw::openclaw;
+use cronclaw::openclaw::{self, AgentOptions};
 use std::path::Path;
 
 #[test]
 fn build_command_has_correct_args() {
-    let cmd = openclaw::build_command("pro-worker", "analyse this data", Path::new("/tmp/ws"), 300);
+    let cmd = openclaw::build_command(
+        "pro-worker",
+        "analyse this data",
+        Path::new("/tmp/ws"),
+        300,
+        &AgentOptions::default(),
+    );
     let prog = cmd.get_program();
     let args: Vec<&std::ffi::OsStr> = cmd.get_args().collect();
 
@@ -25,14 +31,26 @@ fn build_command_has_correct_args() {
 
 #[test]
 fn build_command_sets_working_directory() {
-    let cmd = openclaw::build_command("worker", "do stuff", Path::new("/my/workspace"), 60);
+    let cmd = openclaw::build_command(
+        "worker",
+        "do stuff",
+        Path::new("/my/workspace"),
+        60,
+        &AgentOptions::default(),
+    );
     assert_eq!(cmd.get_current_dir(), Some(Path::new("/my/workspace")));
 }
 
 #[test]
 fn build_command_handles_multiline_prompt() {
     let prompt = "Line one\nLine two\nLine three";
-    let cmd = openclaw::build_command("agent", prompt, Path::new("/tmp"), 300);
+    let cmd = openclaw::build_command(
+        "agent",
+        prompt,
+        Path::new("/tmp"),
+        300,
+        &AgentOptions::default(),
+    );
     let args: Vec<&std::ffi::OsStr> = cmd.get_args().collect();
 
     // The full multiline prompt should be passed as a single argument
@@ -43,15 +61,144 @@ fn build_command_handles_multiline_prompt() {
 #[test]
 fn build_command_handles_special_characters_in_prompt() {
     let prompt = r#"Analyse "this" & that's $data"#;
-    let cmd = openclaw::build_command("agent", prompt, Path::new("/tmp"), 300);
+    let cmd = openclaw::build_command(
+        "agent",
+        prompt,
+        Path::new("/tmp"),
+        300,
+        &AgentOptions::default(),
+    );
     let args: Vec<&std::ffi::OsStr> = cmd.get_args().collect();
     assert_eq!(args[2], prompt);
 }
 
 #[test]
 fn build_command_passes_timeout() {
-    let cmd = openclaw::build_command("agent", "hello", Path::new("/tmp"), 3600);
+    let cmd = openclaw::build_command(
+        "agent",
+        "hello",
+        Path::new("/tmp"),
+        3600,
+        &AgentOptions::default(),
+    );
     let args: Vec<&std::ffi::OsStr> = cmd.get_args().collect();
     assert_eq!(args[6], "--timeout");
     assert_eq!(args[7], "3600");
 }
+
+#[test]
+fn build_command_appends_model_and_extra_args() {
+    let extra_args = vec!["--thinking".to_string(), "low".to_string()];
+    let options = AgentOptions {
+        model: Some("small-model"),
+        extra_args: &extra_args,
+        ..Default::default()
+    };
+    let cmd = openclaw::build_command("agent", "hello", Path::new("/tmp"), 300, &options);
+    let args: Vec<&std::ffi::OsStr> = cmd.get_args().collect();
+    assert_eq!(
+        args,
+        &[
+            "agent",
+            "--message",
+            "hello",
+            "--to",
+            "agent",
+            "--local",
+            "--timeout",
+            "300",
+            "--model",
+            "small-model",
+            "--thinking",
+            "low",
+        ]
+    );
+}
+
+#[test]
+fn build_command_uses_gateway_when_not_local() {
+    let options = AgentOptions {
+        local: false,
+        gateway: Some("https://gateway.example.com"),
+        ..Default::default()
+    };
+    let cmd = openclaw::build_command("agent", "hello", Path::new("/tmp"), 300, &options);
+    let args: Vec<&std::ffi::OsStr> = cmd.get_args().collect();
+    assert_eq!(
+        args,
+        &[
+            "agent",
+            "--message",
+            "hello",
+            "--to",
+            "agent",
+            "--gateway",
+            "https://gateway.example.com",
+            "--timeout",
+            "300",
+        ]
+    );
+}
+
+#[test]
+fn build_command_without_local_or_gateway() {
+    let options = AgentOptions {
+        local: false,
+        ..Default::default()
+    };
+    let cmd = openclaw::build_command("agent", "hello", Path::new("/tmp"), 300, &options);
+    let args: Vec<&std::ffi::OsStr> = cmd.get_args().collect();
+    assert!(!args.contains(&std::ffi::OsStr::new("--local")));
+    assert_eq!(args[5], "--timeout");
+}
+
+#[test]
+fn describe_command_quotes_args_and_cuts_the_prompt_short() {
+    let cmd = openclaw::build_command(
+        "worker",
+        "Summarise it",
+        Path::new("/tmp"),
+        300,
+        &AgentOptions {
+            model: Some("big model"),
+            ..AgentOptions::default()
+        },
+    );
+    assert_eq!(
+        openclaw::describe_command(&cmd),
+        "openclaw agent --message 'Summarise it' --to worker --local --timeout 300 --model 'big model'"
+    );
+
+    let prompt = format!("First line\n{}", "secret ".repeat(20));
+    let cmd = openclaw::build_command(
+        "worker",
+        &prompt,
+        Path::new("/tmp"),
+        300,
+        &AgentOptions::default(),
+    );
+    let line = openclaw::describe_command(&cmd);
+    assert!(
+        line.contains(&format!(
+            "--message 'First line... ({} bytes)'",
+            prompt.len()
+        )),
+        "{}",
+        line
+    );
+    assert!(!line.contains("secret"), "{}", line);
+
+    let cmd = openclaw::build_command(
+        "worker",
+        &"x".repeat(100),
+        Path::new("/tmp"),
+        300,
+        &AgentOptions::default(),
+    );
+    let line = openclaw::describe_command(&cmd);
+    assert!(
+        line.contains(&format!("--message '{}... (100 bytes)'", "x".repeat(60))),
+        "{}",
+        line
+    );
+}
@@ -1,4 +1,9 @@
-use cronclaw::pipeline::{self, StepType, StreamTarget};
+use cronclaw::error::CronclawError;
+use cronclaw::pipeline::{self, OutputSource, StepType, StreamTarget, WorkspaceMode};
+use cronclaw::state::State;
+use std::collections::BTreeMap;
+use std::fs;
+use tempfile::TempDir;
 
 // ─── Minimal valid pipelines ───
 
@@ -107,6 +112,83 @@ steps:
     assert_eq!(p.steps[2].outputs[0].name, "audio");
 }
 
+#[test]
+fn parse_python_step() {
+    let yaml = r#"
+version: 1
+workspace: workspace
+steps:
+  - id: crunch
+    type: python
+    python: |
+      import json
+      print(json.dumps({"ok": True}))
+"#;
+    let p = pipeline::parse(yaml).unwrap();
+    assert_eq!(p.steps[0].step_type, StepType::Python);
+    assert!(p.steps[0].python.as_ref().unwrap().contains("import json"));
+}
+
+#[test]
+fn parse_agent_model_and_extra_args() {
+    let yaml = r#"
+version: 1
+workspace: workspace
+steps:
+  - id: summarise
+    type: agent
+    agent: worker
+    prompt: summarise
+    model: small-model
+    extra_args: ["--thinking", "low"]
+"#;
+    let p = pipeline::parse(yaml).unwrap();
+    assert_eq!(p.steps[0].model.as_deref(), Some("small-model"));
+    assert_eq!(p.steps[0].extra_args, vec!["--thinking", "low"]);
+}
+
+#[test]
+fn parse_agent_local_defaults_to_true() {
+    let yaml = r#"
+version: 1
+workspace: workspace
+steps:
+  - id: local
+    type: agent
+    agent: worker
+    prompt: hi
+  - id: remote
+    type: agent
+    agent: worker
+    prompt: hi
+    local: false
+    gateway: https://gateway.example.com
+"#;
+    let p = pipeline::parse(yaml).unwrap();
+    assert!(p.steps[0].local);
+    assert!(!p.steps[1].local);
+    assert_eq!(
+        p.steps[1].gateway.as_deref(),
+        Some("https://gateway.example.com")
+    );
+}
+
+#[test]
+fn reject_gateway_in_local_mode() {
+    let yaml = r#"
+version: 1
+workspace: workspace
+steps:
+  - id: remote
+    type: agent
+    agent: worker
+    prompt: hi
+    gateway: https://gateway.example.com
+"#;
+    let err = pipeline::parse(yaml).unwrap_err().to_string();
+    assert!(err.contains("local: false"), "got: {}", err);
+}
+
 // ─── Multiline strings ───
 
 #[test]
@@ -177,6 +259,73 @@ steps:
     assert_eq!(p.steps[0].outputs[1].name, "data");
 }
 
+#[test]
+fn parse_output_from_stdout_defaults_tmp() {
+    let yaml = r#"
+version: 1
+workspace: workspace
+steps:
+  - id: fetch
+    type: bash
+    bash: curl -sf https://example.com/data.json
+    outputs:
+      - name: data
+        path: data.json
+        from: stdout
+"#;
+    let p = pipeline::parse(yaml).unwrap();
+    let output = &p.steps[0].outputs[0];
+    assert_eq!(output.from, Some(OutputSource::Stdout));
+    assert_eq!(output.tmp, "data.json.tmp");
+    assert_eq!(
+        p.steps[0].stdout_target(),
+        StreamTarget::File("data.json.tmp".into())
+    );
+}
+
+#[test]
+fn reject_output_without_tmp_or_source() {
+    let yaml = r#"
+version: 1
+workspace: workspace
+steps:
+  - id: gen
+    type: bash
+    bash: ./generate.sh
+    outputs:
+      - name: data
+        path: data.json
+"#;
+    let err = pipeline::parse(yaml).unwrap_err().to_string();
+    assert!(err.contains("missing 'tmp'"), "{}", err);
+}
+
+#[test]
+fn reject_stdout_output_alongside_output_file() {
+    let yaml = r#"
+version: 1
+workspace: workspace
+steps:
+  - id: gen
+    type: bash
+    bash: ./generate.sh
+    output: raw.txt
+    outputs:
+      - name: data
+        path: data.json
+        from: stdout
+"#;
+    let err = pipeline::parse(yaml).unwrap_err().to_string();
+    assert!(err.contains("not both"), "{}", err);
+
+    let two = yaml.replace("    output: raw.txt\n", "").replace(
+        "        from: stdout\n",
+        "        from: stdout\n      - name: copy\n        path: copy.json\n        from: stdout\n",
+    );
+    let err = pipeline::parse(&two).unwrap_err().to_string();
+    assert!(err.contains("only one output"), "{}", err);
+}
+
 // ─── Per-step timeout ───
 
 #[test]
@@ -198,6 +347,98 @@ steps:
     assert_eq!(p.steps[1].timeout, Some(3600));
 }
 
+#[test]
+fn parse_step_timeout_as_duration() {
+    let yaml = r#"
+version: 1
+workspace: workspace
+steps:
+  - id: slow
+    type: bash
+    timeout: 10m
+    bash: ./long-running.sh
+  - id: slower
+    type: bash
+    timeout: "2h"
+    bash: ./longer-running.sh
+"#;
+    let p = pipeline::parse(yaml).unwrap();
+    assert_eq!(p.steps[0].timeout, Some(600));
+    assert_eq!(p.steps[1].timeout, Some(7200));
+}
+
+#[test]
+fn reject_invalid_timeout_duration() {
+    for timeout in ["10 minutes", "soon", "-5", "1.5h"] {
+        let yaml = format!(
+            "version: 1\nworkspace: workspace\nsteps:\n  - id: slow\n    type: bash\n    bash: echo hi\n    timeout: {}\n",
+            timeout
+        );
+        assert!(pipeline::parse(&yaml).is_err(), "accepted {}", timeout);
+    }
+    let err = pipeline::parse(
+        "version: 1\nworkspace: workspace\nsteps:\n  - id: slow\n    type: bash\n    bash: echo hi\n    timeout: soon\n",
+    )
+    .unwrap_err().to_string();
+    assert!(err.contains("invalid duration 'soon'"), "{}", err);
+}
+
+#[test]
+fn parse_pipeline_default_timeout() {
+    let yaml = r#"
+version: 1
+workspace: workspace
+default_timeout: 2h
+steps:
+  - id: think
+    type: bash
+    bash: ./long-running.sh
+"#;
+    let p = pipeline::parse(yaml).unwrap();
+    assert_eq!(p.default_timeout, Some(7200));
+    assert!(p.steps[0].timeout.is_none());
+
+    let err = pipeline::parse(&yaml.replace("2h", "0"))
+        .unwrap_err()
+        .to_string();
+    assert!(
+        err.contains("default_timeout must be at least 1 second"),
+        "{}",
+        err
+    );
+}
+
+#[test]
+fn parse_workspace_mode() {
+    let yaml =
+        "version: 1\nworkspace: workspace\nsteps:\n  - id: a\n    type: bash\n    bash: echo a\n";
+    let p = pipeline::parse(yaml).unwrap();
+    assert_eq!(p.workspace_mode, WorkspaceMode::Shared);
+
+    let isolated = yaml.replace("steps:", "workspace_mode: isolated\nsteps:");
+    let p = pipeline::parse(&isolated).unwrap();
+    assert_eq!(p.workspace_mode, WorkspaceMode::Isolated);
+
+    let unknown = yaml.replace("steps:", "workspace_mode: private\nsteps:");
+    assert!(pipeline::parse(&unknown).is_err());
+}
+
+#[test]
+fn reject_zero_timeout() {
+    let yaml = r#"
+version: 1
+workspace: workspace
+steps:
+  - id: instant
+    type: bash
+    bash: echo hi
+    timeout: 0
+"#;
+    let err = pipeline::parse(yaml).unwrap_err().to_string();
+    assert!(err.contains("step 'instant'"), "got: {}", err);
+    assert!(err.contains("at least 1 second"), "got: {}", err);
+}
+
 // ─── Validation failures ───
 
 #[test]
@@ -209,11 +450,26 @@ steps:
   - id: broken
     type: bash
 "#;
-    let err = pipeline::parse(yaml).unwrap_err();
+    let err = pipeline::parse(yaml).unwrap_err().to_string();
     assert!(err.contains("broken"));
     assert!(err.contains("bash"));
 }
 
+#[test]
+fn reject_python_step_missing_python_field() {
+    let yaml = r#"
+version: 1
+workspace: workspace
+steps:
+  - id: broken
+    type: python
+    bash: echo hi
+"#;
+    let err = pipeline::parse(yaml).unwrap_err().to_string();
+    assert!(err.contains("broken"));
+    assert!(err.contains("python"));
+}
+
 #[test]
 fn reject_agent_step_missing_agent_field() {
     let yaml = r#"
@@ -225,7 +481,7 @@ steps:
     prompt: Do something.
     output: out.md
 "#;
-    let err = pipeline::parse(yaml).unwrap_err();
+    let err = pipeline::parse(yaml).unwrap_err().to_string();
     assert!(err.contains("broken"));
     assert!(err.contains("agent"));
 }
@@ -241,9 +497,57 @@ steps:
     agent: worker
     output: out.md
 "#;
-    let err = pipeline::parse(yaml).unwrap_err();
+    let err = pipeline::parse(yaml).unwrap_err().to_string();
+    assert!(err.contains("broken"));
+    assert!(err.contains("'prompt' nor 'prompt_file'"), "got: {}", err);
+}
+
+#[test]
+fn parse_agent_prompt_file() {
+    let yaml = r#"
+version: 1
+workspace: workspace
+steps:
+  - id: review
+    type: agent
+    agent: worker
+    prompt_file: prompts/review.md
+"#;
+    let p = pipeline::parse(yaml).unwrap();
+    assert_eq!(p.steps[0].prompt_file.as_deref(), Some("prompts/review.md"));
+    assert!(p.steps[0].prompt.is_none());
+}
+
+#[test]
+fn reject_agent_step_with_prompt_and_prompt_file() {
+    let yaml = r#"
+version: 1
+workspace: workspace
+steps:
+  - id: broken
+    type: agent
+    agent: worker
+    prompt: Do something.
+    prompt_file: prompts/review.md
+"#;
+    let err = pipeline::parse(yaml).unwrap_err().to_string();
     assert!(err.contains("broken"));
-    assert!(err.contains("prompt"));
+    assert!(err.contains("not both"), "got: {}", err);
+}
+
+#[test]
+fn reject_prompt_file_outside_workspace() {
+    let yaml = r#"
+version: 1
+workspace: workspace
+steps:
+  - id: review
+    type: agent
+    agent: worker
+    prompt_file: ../prompts/review.md
+"#;
+    let err = pipeline::parse(yaml).unwrap_err().to_string();
+    assert!(err.contains("prompt_file"), "got: {}", err);
 }
 
 #[test]
@@ -316,6 +620,36 @@ steps:
     assert!(pipeline::parse(yaml).is_err());
 }
 
+#[test]
+fn reject_duplicate_step_ids() {
+    let yaml = r#"
+version: 1
+workspace: workspace
+steps:
+  - id: fetch
+    type: bash
+    bash: echo one
+  - id: fetch
+    type: bash
+    bash: echo two
+"#;
+    let err = pipeline::parse(yaml).unwrap_err().to_string();
+    assert!(err.contains("duplicate"));
+    assert!(err.contains("'fetch'"));
+}
+
+#[test]
+fn reject_step_ids_that_are_paths() {
+    for id in ["../x", "a/b", "a\\b", "..", "\"\""] {
+        let yaml = format!(
+            "version: 1\nworkspace: workspace\nsteps:\n  - id: {}\n    type: bash\n    bash: echo hi\n",
+            id
+        );
+        let err = pipeline::parse(&yaml).unwrap_err().to_string();
+        assert!(err.contains("step id"), "{}: {}", id, err);
+    }
+}
+
 #[test]
 fn reject_empty_steps_array() {
     // Empty steps should parse (it's a valid Vec), but this tests the schema allows it.
@@ -329,98 +663,399 @@ steps: []
     assert!(p.steps.is_empty());
 }
 
-// ─── Stream target (output/error) fields ───
-
 #[test]
-fn output_missing_defaults_to_terminal() {
+fn reject_http_missing_url() {
     let yaml = r#"
 version: 1
 workspace: workspace
 steps:
-  - id: hello
-    type: bash
-    bash: echo hi
+  - id: hook
+    type: http
+    method: POST
 "#;
-    let p = pipeline::parse(yaml).unwrap();
-    assert_eq!(p.steps[0].output, StreamTarget::Terminal);
-    assert_eq!(p.steps[0].error, StreamTarget::Terminal);
+    let err = pipeline::parse(yaml).unwrap_err().to_string();
+    assert!(err.contains("'url'"), "got: {}", err);
 }
 
 #[test]
-fn output_null_parses_as_void() {
+fn parse_noop_step_needs_no_fields() {
     let yaml = r#"
 version: 1
 workspace: workspace
 steps:
-  - id: hello
+  - id: a
     type: bash
-    bash: echo hi
-    output: null
-    error: null
+    bash: echo a
+  - id: joined
+    type: noop
 "#;
     let p = pipeline::parse(yaml).unwrap();
-    assert_eq!(p.steps[0].output, StreamTarget::Void);
-    assert_eq!(p.steps[0].error, StreamTarget::Void);
+    assert_eq!(p.steps[1].step_type, StepType::Noop);
+    assert_eq!(p.steps[1].step_type.name(), "noop");
 }
 
 #[test]
-fn output_path_parses_as_file() {
+fn reject_noop_step_with_outputs() {
     let yaml = r#"
 version: 1
 workspace: workspace
 steps:
-  - id: hello
-    type: bash
-    bash: echo hi
-    output: result.txt
-    error: errors.log
+  - id: joined
+    type: noop
+    outputs:
+      - name: report
+        path: report.txt
 "#;
-    let p = pipeline::parse(yaml).unwrap();
-    assert_eq!(
-        p.steps[0].output,
-        StreamTarget::File("result.txt".to_string())
-    );
-    assert_eq!(
-        p.steps[0].error,
-        StreamTarget::File("errors.log".to_string())
-    );
+    let err = pipeline::parse(yaml).unwrap_err().to_string();
+    assert!(err.contains("noop"), "got: {}", err);
 }
 
 #[test]
-fn parse_agent_step_with_custom_error() {
-    let yaml = r#"
+fn reject_working_dir_outside_workspace() {
+    for dir in ["../elsewhere", "src/../../elsewhere", "/tmp"] {
+        let yaml = format!(
+            r#"
 version: 1
 workspace: workspace
 steps:
-  - id: research
-    type: agent
-    agent: worker
-    prompt: Do research.
-    output: result.md
-    error: research.log
-"#;
-    let p = pipeline::parse(yaml).unwrap();
-    assert_eq!(
-        p.steps[0].output,
-        StreamTarget::File("result.md".to_string())
-    );
-    assert_eq!(
-        p.steps[0].error,
-        StreamTarget::File("research.log".to_string())
-    );
+  - id: build
+    type: bash
+    bash: make
+    working_dir: {}
+"#,
+            dir
+        );
+        let err = pipeline::parse(&yaml).unwrap_err().to_string();
+        assert!(err.contains("working_dir"), "got: {}", err);
+    }
 }
 
-// ─── Optional fields don't interfere ───
+#[test]
+fn reject_inputs_outside_workspace() {
+    for input in ["../elsewhere.txt", "/etc/passwd"] {
+        let yaml = format!(
+            r#"
+version: 1
+workspace: workspace
+steps:
+  - id: build
+    type: bash
+    bash: make
+    inputs: [data.json, "{}"]
+"#,
+            input
+        );
+        let err = pipeline::parse(&yaml).unwrap_err().to_string();
+        assert!(err.contains("input"), "got: {}", err);
+    }
+}
 
 #[test]
-fn bash_step_ignores_agent_fields() {
+fn reject_output_paths_outside_workspace() {
+    for (field, yaml) in [
+        ("output", "    output: /tmp/out.txt\n"),
+        ("error", "    error: ../err.txt\n"),
+        ("stdin_file", "    stdin_file: /etc/passwd\n"),
+        (
+            "output 'out' path",
+            "    outputs:\n      - name: out\n        path: /tmp/out.txt\n        tmp: out.txt.tmp\n",
+        ),
+        (
+            "output 'out' tmp",
+            "    outputs:\n      - name: out\n        path: out.txt\n        tmp: ../out.txt.tmp\n",
+        ),
+    ] {
+        let yaml = format!(
+            "version: 1\nworkspace: workspace\nsteps:\n  - id: build\n    type: bash\n    bash: make\n{}",
+            yaml
+        );
+        let err = pipeline::parse(&yaml).unwrap_err().to_string();
+        assert!(
+            err.contains(&format!("{} '", field)) && err.contains("inside the workspace"),
+            "got: {}",
+            err
+        );
+    }
+}
+
+#[test]
+fn reject_template_paths_outside_workspace() {
     let yaml = r#"
 version: 1
 workspace: workspace
 steps:
-  - id: hello
-    type: bash
-    bash: echo hi
+  - id: think
+    type: agent
+    agent: main
+    prompt: "Summarise {{ file:notes.md }} and {{ file:../escape }}"
+"#;
+    let err = pipeline::parse(yaml).unwrap_err().to_string();
+    assert!(
+        err.contains("prompt template path '../escape'"),
+        "got: {}",
+        err
+    );
+
+    let yaml = r#"
+version: 1
+workspace: workspace
+env:
+  SECRET: "{{ file:/etc/shadow }}"
+steps:
+  - id: fetch
+    type: http
+    url: https://example.com/{{ glob:data/*.json }}
+"#;
+    let err = pipeline::parse(yaml).unwrap_err().to_string();
+    assert!(err.contains("env 'SECRET' template path"), "got: {}", err);
+
+    // Inside the workspace, and other template kinds, are fine
+    let yaml = r#"
+version: 1
+workspace: workspace
+steps:
+  - id: think
+    type: agent
+    agent: main
+    prompt: "{{ file:./notes/a.md }} {{ glob:notes/*.md }} {{ env:HOME }}"
+"#;
+    pipeline::parse(yaml).unwrap();
+}
+
+#[test]
+fn parse_step_description() {
+    let yaml = r#"
+version: 1
+workspace: workspace
+steps:
+  - id: fetch
+    description: Download the latest prices
+    type: bash
+    bash: echo fetched
+  - id: plain
+    type: bash
+    bash: echo plain
+"#;
+    let p = pipeline::parse(yaml).unwrap();
+    assert_eq!(
+        p.steps[0].description.as_deref(),
+        Some("Download the latest prices")
+    );
+    assert_eq!(p.steps[1].description, None);
+}
+
+#[test]
+fn reject_unknown_fields() {
+    let step = r#"
+version: 1
+workspace: workspace
+steps:
+  - id: slow
+    type: bash
+    bash: sleep 100
+    timout: 600
+"#;
+    let err = pipeline::parse(step).unwrap_err().to_string();
+    assert!(err.contains("unknown field `timout`"), "got: {}", err);
+
+    let top = r#"
+version: 1
+workspace: workspace
+schedul: "0 * * * *"
+steps: []
+"#;
+    let err = pipeline::parse(top).unwrap_err().to_string();
+    assert!(err.contains("unknown field `schedul`"), "got: {}", err);
+
+    let output = r#"
+version: 1
+workspace: workspace
+steps:
+  - id: build
+    type: bash
+    bash: make
+    outputs:
+      - name: bin
+        path: app
+        temp: app.tmp
+"#;
+    let err = pipeline::parse(output).unwrap_err().to_string();
+    assert!(err.contains("unknown field `temp`"), "got: {}", err);
+}
+
+#[test]
+fn reject_newer_version() {
+    let yaml = r#"
+version: 2
+workspace: workspace
+steps:
+  - id: a
+    type: bash
+    bash: echo a
+"#;
+    let err = pipeline::parse(yaml).unwrap_err().to_string();
+    assert!(err.contains("version 2"), "got: {}", err);
+    assert!(err.contains("upgrade cronclaw"), "got: {}", err);
+}
+
+#[test]
+fn reject_version_zero() {
+    let yaml = r#"
+version: 0
+workspace: workspace
+steps:
+  - id: a
+    type: bash
+    bash: echo a
+"#;
+    let err = pipeline::parse(yaml).unwrap_err().to_string();
+    assert!(
+        err.contains("unsupported pipeline version 0"),
+        "got: {}",
+        err
+    );
+}
+
+#[test]
+fn reject_both_stdin_and_stdin_file() {
+    let yaml = r#"
+version: 1
+workspace: workspace
+steps:
+  - id: a
+    type: bash
+    bash: cat
+    stdin: hello
+    stdin_file: input.txt
+"#;
+    let err = pipeline::parse(yaml).unwrap_err().to_string();
+    assert!(err.contains("'stdin' or 'stdin_file'"), "got: {}", err);
+}
+
+// ─── Stream target (output/error) fields ───
+
+#[test]
+fn output_missing_defaults_to_terminal() {
+    let yaml = r#"
+version: 1
+workspace: workspace
+steps:
+  - id: hello
+    type: bash
+    bash: echo hi
+"#;
+    let p = pipeline::parse(yaml).unwrap();
+    assert_eq!(p.steps[0].output, StreamTarget::Terminal);
+    assert_eq!(p.steps[0].error, StreamTarget::Terminal);
+}
+
+#[test]
+fn output_null_parses_as_void() {
+    let yaml = r#"
+version: 1
+workspace: workspace
+steps:
+  - id: hello
+    type: bash
+    bash: echo hi
+    output: null
+    error: null
+"#;
+    let p = pipeline::parse(yaml).unwrap();
+    assert_eq!(p.steps[0].output, StreamTarget::Void);
+    assert_eq!(p.steps[0].error, StreamTarget::Void);
+}
+
+#[test]
+fn output_path_parses_as_file() {
+    let yaml = r#"
+version: 1
+workspace: workspace
+steps:
+  - id: hello
+    type: bash
+    bash: echo hi
+    output: result.txt
+    error: errors.log
+"#;
+    let p = pipeline::parse(yaml).unwrap();
+    assert_eq!(
+        p.steps[0].output,
+        StreamTarget::File("result.txt".to_string())
+    );
+    assert_eq!(
+        p.steps[0].error,
+        StreamTarget::File("errors.log".to_string())
+    );
+}
+
+#[test]
+fn output_file_with_append_parses_as_append() {
+    let yaml = r#"
+version: 1
+workspace: workspace
+steps:
+  - id: hello
+    type: bash
+    bash: echo hi
+    output: { file: runs.log, append: true }
+    error: { file: errors.log }
+"#;
+    let p = pipeline::parse(yaml).unwrap();
+    assert_eq!(
+        p.steps[0].output,
+        StreamTarget::Append("runs.log".to_string())
+    );
+    assert_eq!(
+        p.steps[0].error,
+        StreamTarget::File("errors.log".to_string())
+    );
+
+    let err = pipeline::parse(&yaml.replace("append: true", "apend: true"))
+        .unwrap_err()
+        .to_string();
+    assert!(err.contains("apend"), "got: {}", err);
+    let err = pipeline::parse(&yaml.replace("runs.log", "../runs.log"))
+        .unwrap_err()
+        .to_string();
+    assert!(err.contains("output"), "got: {}", err);
+}
+
+#[test]
+fn parse_agent_step_with_custom_error() {
+    let yaml = r#"
+version: 1
+workspace: workspace
+steps:
+  - id: research
+    type: agent
+    agent: worker
+    prompt: Do research.
+    output: result.md
+    error: research.log
+"#;
+    let p = pipeline::parse(yaml).unwrap();
+    assert_eq!(
+        p.steps[0].output,
+        StreamTarget::File("result.md".to_string())
+    );
+    assert_eq!(
+        p.steps[0].error,
+        StreamTarget::File("research.log".to_string())
+    );
+}
+
+// ─── Optional fields don't interfere ───
+
+#[test]
+fn bash_step_ignores_agent_fields() {
+    let yaml = r#"
+version: 1
+workspace: workspace
+steps:
+  - id: hello
+    type: bash
+    bash: echo hi
     agent: should-be-ignored
     prompt: also-ignored
     output: also-ignored.md
@@ -450,3 +1085,866 @@ steps:
     let p = pipeline::parse(yaml).unwrap();
     assert!(p.steps[0].outputs.is_empty());
 }
+
+// ─── Step dependencies (needs) ───
+
+#[test]
+fn needs_defaults_to_previous_step() {
+    let yaml = r#"
+version: 1
+workspace: workspace
+steps:
+  - id: a
+    type: bash
+    bash: echo a
+  - id: b
+    type: bash
+    bash: echo b
+"#;
+    let p = pipeline::parse(yaml).unwrap();
+    assert!(p.steps[0].needs.is_none());
+    assert!(p.needs_of(0).is_empty());
+    assert_eq!(p.needs_of(1), vec!["a"]);
+}
+
+#[test]
+fn parse_explicit_needs() {
+    let yaml = r#"
+version: 1
+workspace: workspace
+steps:
+  - id: a
+    type: bash
+    bash: echo a
+  - id: b
+    type: bash
+    bash: echo b
+    needs: []
+  - id: join
+    type: bash
+    bash: echo join
+    needs: [a, b]
+"#;
+    let p = pipeline::parse(yaml).unwrap();
+    assert!(p.needs_of(1).is_empty());
+    assert_eq!(p.needs_of(2), vec!["a", "b"]);
+}
+
+#[test]
+fn reject_needs_unknown_step() {
+    let yaml = r#"
+version: 1
+workspace: workspace
+steps:
+  - id: a
+    type: bash
+    bash: echo a
+    needs: [ghost]
+"#;
+    let err = pipeline::parse(yaml).unwrap_err().to_string();
+    assert!(err.contains("'a'"));
+    assert!(err.contains("ghost"));
+}
+
+#[test]
+fn reject_needs_cycle() {
+    let yaml = r#"
+version: 1
+workspace: workspace
+steps:
+  - id: a
+    type: bash
+    bash: echo a
+    needs: [b]
+  - id: b
+    type: bash
+    bash: echo b
+"#;
+    let err = pipeline::parse(yaml).unwrap_err().to_string();
+    assert!(err.contains("cycle"));
+    assert!(err.contains("a -> b -> a"), "got: {}", err);
+}
+
+#[test]
+fn reject_needs_self_reference() {
+    let yaml = r#"
+version: 1
+workspace: workspace
+steps:
+  - id: a
+    type: bash
+    bash: echo a
+    needs: [a]
+"#;
+    let err = pipeline::parse(yaml).unwrap_err().to_string();
+    assert!(err.contains("cycle"));
+}
+
+// ─── Retries ───
+
+#[test]
+fn parse_step_retries() {
+    let yaml = r#"
+version: 1
+workspace: workspace
+steps:
+  - id: flaky
+    type: bash
+    bash: ./flaky.sh
+    retries: 3
+    retry_delay_secs: 60
+    allow_failure: true
+  - id: steady
+    type: bash
+    bash: echo ok
+"#;
+    let p = pipeline::parse(yaml).unwrap();
+    assert_eq!(p.steps[0].retries, 3);
+    assert_eq!(p.steps[0].retry_delay_secs, 60);
+    assert_eq!(p.steps[1].retries, 0);
+    assert_eq!(p.steps[1].retry_delay_secs, 0);
+    assert!(p.steps[0].allow_failure);
+    assert!(!p.steps[1].allow_failure);
+}
+
+#[test]
+fn parse_step_deadline() {
+    let yaml = r#"
+version: 1
+workspace: workspace
+steps:
+  - id: flaky
+    type: bash
+    bash: ./flaky.sh
+    retries: 3
+    deadline_secs: 600
+  - id: steady
+    type: bash
+    bash: echo ok
+"#;
+    let p = pipeline::parse(yaml).unwrap();
+    assert_eq!(p.steps[0].deadline_secs, Some(600));
+    assert_eq!(p.steps[1].deadline_secs, None);
+
+    let zero = yaml.replace("deadline_secs: 600", "deadline_secs: 0");
+    let err = pipeline::parse(&zero).unwrap_err().to_string();
+    assert!(err.contains("deadline_secs"), "{}", err);
+}
+
+// ─── Cross-field checks ───
+
+#[test]
+fn check_clean_pipeline_has_no_problems() {
+    let yaml = r#"
+version: 1
+workspace: workspace
+steps:
+  - id: a
+    type: bash
+    bash: echo a
+    outputs:
+      - name: out
+        path: out.txt
+        tmp: out.txt.tmp
+"#;
+    let p = pipeline::parse(yaml).unwrap();
+    assert!(pipeline::check(&p).is_empty());
+}
+
+#[test]
+fn reject_duplicate_output_names() {
+    let yaml = r#"
+version: 1
+workspace: workspace
+steps:
+  - id: a
+    type: bash
+    bash: echo a
+    outputs:
+      - name: out
+        path: one.txt
+        tmp: one.txt.tmp
+      - name: out
+        path: two.txt
+        tmp: two.txt.tmp
+"#;
+    let err = pipeline::parse(yaml).unwrap_err().to_string();
+    assert_eq!(err.lines().count(), 1);
+    assert!(err.contains("duplicate output name 'out'"), "got: {}", err);
+}
+
+#[test]
+fn reject_output_path_collisions() {
+    let yaml = r#"
+version: 1
+workspace: workspace
+steps:
+  - id: a
+    type: bash
+    bash: echo a
+    outputs:
+      - name: first
+        path: data.json
+        tmp: data.json.tmp
+  - id: b
+    type: bash
+    bash: echo b
+    outputs:
+      - name: second
+        path: data.json
+        tmp: other.tmp
+"#;
+    let err = pipeline::parse(yaml).unwrap_err().to_string();
+    assert_eq!(err.lines().count(), 1);
+    assert!(err.contains("data.json"));
+    assert!(err.contains("'b'"));
+    assert!(err.contains("'a'"));
+}
+
+#[test]
+fn reject_tmp_colliding_with_another_path() {
+    let yaml = r#"
+version: 1
+workspace: workspace
+steps:
+  - id: a
+    type: bash
+    bash: echo a
+    outputs:
+      - name: first
+        path: data.json
+        tmp: data.json.tmp
+      - name: second
+        path: summary.txt
+        tmp: data.json
+"#;
+    let err = pipeline::parse(yaml).unwrap_err().to_string();
+    assert!(
+        err.contains("output 'second' tmp 'data.json' collides with step 'a' output 'first' path"),
+        "got: {}",
+        err
+    );
+}
+
+#[test]
+fn reject_output_tmp_equal_to_path() {
+    let yaml = r#"
+version: 1
+workspace: workspace
+steps:
+  - id: a
+    type: bash
+    bash: echo a
+    outputs:
+      - name: out
+        path: out.txt
+        tmp: out.txt
+"#;
+    let err = pipeline::parse(yaml).unwrap_err().to_string();
+    assert!(
+        err.contains("tmp and path are both 'out.txt'"),
+        "got: {}",
+        err
+    );
+}
+
+#[test]
+fn reject_reports_every_output_problem() {
+    let yaml = r#"
+version: 1
+workspace: workspace
+steps:
+  - id: a
+    type: bash
+    bash: echo a
+    outputs:
+      - name: out
+        path: out.txt
+        tmp: out.txt
+      - name: out
+        path: other.txt
+        tmp: other.txt.tmp
+"#;
+    let err = pipeline::parse(yaml).unwrap_err().to_string();
+    assert_eq!(err.lines().count(), 2, "got: {}", err);
+}
+
+// ─── on_failure ───
+
+#[test]
+fn on_failure_is_a_command_or_cleanup_steps() {
+    let p =
+        pipeline::parse("version: 1\nworkspace: workspace\non_failure: echo failed\nsteps: []\n")
+            .unwrap();
+    assert_eq!(
+        p.on_failure_command().map(String::as_str),
+        Some("echo failed")
+    );
+    assert!(p.cleanup_steps().is_empty());
+
+    let yaml = r#"
+version: 1
+workspace: workspace
+on_failure:
+  - id: tidy
+    type: bash
+    bash: rm -f "{{ env:HOME }}.tmp"
+steps:
+  - id: build
+    type: bash
+    bash: make
+"#;
+    let p = pipeline::parse(yaml).unwrap();
+    assert!(p.on_failure_command().is_none());
+    let ids: Vec<_> = p.cleanup_steps().iter().map(|s| s.id.as_str()).collect();
+    assert_eq!(ids, ["tidy"]);
+    assert_eq!(p.steps.len(), 1);
+}
+
+#[test]
+fn reject_invalid_cleanup_steps() {
+    let cases = [
+        ("type: bash", "'bash' field is missing"),
+        (
+            "type: bash\n    bash: make\n    needs: [build]",
+            "'needs' isn't supported",
+        ),
+        (
+            "type: bash\n    bash: make\n    retries: 2",
+            "'retries' isn't supported",
+        ),
+        (
+            "type: bash\n    bash: make\n    workdir: out",
+            "on_failure steps",
+        ),
+    ];
+    for (cleanup, expected) in cases {
+        let yaml = format!(
+            "version: 1\nworkspace: workspace\non_failure:\n  - id: tidy\n    {}\nsteps:\n  - id: build\n    type: bash\n    bash: make\n",
+            cleanup
+        );
+        let err = pipeline::parse(&yaml).unwrap_err().to_string();
+        assert!(err.contains(expected), "{}", err);
+    }
+
+    let yaml = "version: 1\nworkspace: workspace\non_failure:\n  - id: build\n    type: bash\n    bash: rm -rf out\nsteps:\n  - id: build\n    type: bash\n    bash: make\n";
+    let err = pipeline::parse(yaml).unwrap_err().to_string();
+    assert!(err.contains("duplicate step id 'build'"), "{}", err);
+}
+
+// ─── Environment ───
+
+#[test]
+fn parse_pipeline_and_step_env() {
+    let yaml = r#"
+version: 1
+workspace: workspace
+env:
+  API_BASE: https://api.example.com
+  REGION: eu
+steps:
+  - id: fetch
+    type: bash
+    bash: curl "$API_BASE"
+    env:
+      REGION: us
+  - id: plain
+    type: bash
+    bash: echo hi
+"#;
+    let p = pipeline::parse(yaml).unwrap();
+    assert_eq!(p.env["API_BASE"], "https://api.example.com");
+
+    let env = p.env_for(&p.steps[0]);
+    assert_eq!(env["API_BASE"], "https://api.example.com");
+    assert_eq!(env["REGION"], "us");
+
+    let env = p.env_for(&p.steps[1]);
+    assert_eq!(env["REGION"], "eu");
+}
+
+// ─── Variables ───
+
+const VARS_PIPELINE: &str = r#"
+version: 1
+workspace: workspace
+vars:
+  host: null
+  region: eu
+env:
+  REGION: "{{ var:region }}"
+steps:
+  - id: deploy
+    type: bash
+    bash: ./deploy.sh "$HOST" "$REGION"
+    env:
+      HOST: "{{ var:host }}"
+"#;
+
+#[test]
+fn parse_vars_and_set_them() {
+    let mut p = pipeline::parse(VARS_PIPELINE).unwrap();
+    assert_eq!(p.vars["host"], None);
+    assert_eq!(p.vars["region"].as_deref(), Some("eu"));
+
+    let err = p.set_vars(&BTreeMap::new()).unwrap_err();
+    assert!(err.contains("variable 'host' has no default"), "{}", err);
+
+    let given = BTreeMap::from([("hots".to_string(), "caladan".to_string())]);
+    let err = p.set_vars(&given).unwrap_err();
+    assert!(err.contains("unknown variable 'hots'"), "{}", err);
+
+    // A value given on the command line wins over the default
+    let given = BTreeMap::from([
+        ("host".to_string(), "caladan".to_string()),
+        ("region".to_string(), "us".to_string()),
+    ]);
+    p.set_vars(&given).unwrap();
+    assert_eq!(p.vars["host"].as_deref(), Some("caladan"));
+    assert_eq!(p.vars["region"].as_deref(), Some("us"));
+}
+
+#[test]
+fn parse_rejects_vars_used_but_undeclared_or_declared_but_unused() {
+    let err = pipeline::parse(&VARS_PIPELINE.replace("  host: null\n", ""))
+        .unwrap_err()
+        .to_string();
+    assert!(
+        err.contains(
+            "step 'deploy': env 'HOST' uses variable 'host', which isn't declared in 'vars'"
+        ),
+        "{}",
+        err
+    );
+
+    let err =
+        pipeline::parse(&VARS_PIPELINE.replace("  region: eu\n", "  region: eu\n  stage: prod\n"))
+            .unwrap_err()
+            .to_string();
+    assert!(
+        err.contains("variable 'stage' is declared in 'vars' but no step uses {{ var:stage }}"),
+        "{}",
+        err
+    );
+}
+
+#[test]
+fn parse_allows_vars_used_only_from_a_prompt_file() {
+    let yaml = r#"
+version: 1
+workspace: workspace
+vars:
+  host: caladan
+steps:
+  - id: review
+    type: agent
+    agent: reviewer
+    prompt_file: prompt.md
+"#;
+    let p = pipeline::parse(yaml).unwrap();
+    assert_eq!(p.vars["host"].as_deref(), Some("caladan"));
+}
+
+// ─── Schedule ───
+
+#[test]
+fn parse_pipeline_schedule() {
+    let yaml = r#"
+version: 1
+workspace: workspace
+schedule: "*/15 * * * *"
+steps:
+  - id: a
+    type: bash
+    bash: echo a
+"#;
+    let p = pipeline::parse(yaml).unwrap();
+    assert_eq!(p.schedule.as_deref(), Some("*/15 * * * *"));
+}
+
+#[test]
+fn parse_pipeline_watch() {
+    let yaml = r#"
+version: 1
+workspace: workspace
+watch: [inbox/data.csv, /srv/drop/ready]
+steps:
+  - id: a
+    type: bash
+    bash: echo a
+"#;
+    let p = pipeline::parse(yaml).unwrap();
+    assert_eq!(p.watch, ["inbox/data.csv", "/srv/drop/ready"]);
+    assert_eq!(p.watch_missing, pipeline::WatchMissing::Ignore);
+
+    let yaml = yaml.replace("steps:", "watch_missing: fail\nsteps:");
+    let p = pipeline::parse(&yaml).unwrap();
+    assert_eq!(p.watch_missing, pipeline::WatchMissing::Fail);
+}
+
+#[test]
+fn reject_invalid_schedule() {
+    for schedule in ["* * * *", "61 * * * *", "not a schedule at all"] {
+        let yaml = format!(
+            r#"
+version: 1
+workspace: workspace
+schedule: "{}"
+steps:
+  - id: a
+    type: bash
+    bash: echo a
+"#,
+            schedule
+        );
+        let err = pipeline::parse(&yaml).unwrap_err().to_string();
+        assert!(err.contains("schedule"), "got: {}", err);
+    }
+}
+
+#[test]
+fn schedule_weekdays_count_from_sunday_zero() {
+    use chrono::{Datelike, Local, TimeZone, Weekday};
+
+    // Saturday 2025-01-04, 12:00
+    let saturday = Local.with_ymd_and_hms(2025, 1, 4, 12, 0, 0).unwrap();
+
+    let weekdays = pipeline::parse_schedule("0 9 * * 1-5").unwrap();
+    let next = weekdays.after(&saturday).next().unwrap();
+    assert_eq!(next.weekday(), Weekday::Mon);
+
+    let sundays = pipeline::parse_schedule("0 9 * * 0").unwrap();
+    assert_eq!(
+        sundays.after(&saturday).next().unwrap().weekday(),
+        Weekday::Sun
+    );
+    let sundays = pipeline::parse_schedule("0 9 * * 7").unwrap();
+    assert_eq!(
+        sundays.after(&saturday).next().unwrap().weekday(),
+        Weekday::Sun
+    );
+}
+
+// ─── HTTP steps ───
+
+#[test]
+fn parse_http_step() {
+    let yaml = r#"
+version: 1
+workspace: workspace
+steps:
+  - id: hook
+    type: http
+    method: POST
+    url: https://example.com/hook
+    headers:
+      Authorization: "Bearer {{ env:TOKEN }}"
+    body: '{"ok": true}'
+    expect_status: [200, 202]
+"#;
+    let p = pipeline::parse(yaml).unwrap();
+    let step = &p.steps[0];
+    assert_eq!(step.step_type, StepType::Http);
+    assert_eq!(step.method.as_deref(), Some("POST"));
+    assert_eq!(step.url.as_deref(), Some("https://example.com/hook"));
+    assert_eq!(step.headers["Authorization"], "Bearer {{ env:TOKEN }}");
+    assert_eq!(step.body.as_deref(), Some(r#"{"ok": true}"#));
+    assert_eq!(step.expect_status, vec![200, 202]);
+}
+
+// ─── Anchors and aliases ───
+
+#[test]
+fn parse_aliases_give_each_step_its_own_copy() {
+    let yaml = r#"
+version: 1
+workspace: workspace
+steps:
+  - id: fetch
+    type: bash
+    bash: curl -o data.json.tmp example.com
+    timeout: &slow 2h
+    env: &env
+      REGION: eu
+  - id: summarise
+    type: bash
+    bash: jq . data.json
+    timeout: *slow
+    env: *env
+"#;
+    let mut p = pipeline::parse(yaml).unwrap();
+    assert_eq!(p.steps[0].timeout, Some(7200));
+    assert_eq!(p.steps[1].timeout, Some(7200));
+    assert_eq!(p.steps[0].env, p.steps[1].env);
+
+    p.steps[0]
+        .env
+        .insert("REGION".to_string(), "us".to_string());
+    assert_eq!(p.steps[1].env["REGION"], "eu");
+}
+
+#[test]
+fn parse_applies_merge_keys_from_x_anchors() {
+    let yaml = r#"
+version: 1
+workspace: workspace
+x-bash: &bash
+  type: bash
+  timeout: 10m
+  env:
+    LANG: C
+steps:
+  - <<: *bash
+    id: first
+    bash: echo one
+  - <<: *bash
+    id: second
+    timeout: 30s
+    bash: echo two
+"#;
+    let p = pipeline::parse(yaml).unwrap();
+    let ids: Vec<_> = p.steps.iter().map(|s| s.id.as_str()).collect();
+    assert_eq!(ids, ["first", "second"]);
+    assert_eq!(p.steps[0].step_type, StepType::Bash);
+    assert_eq!(p.steps[0].timeout, Some(600));
+    // A key of the step's own wins over the merged one
+    assert_eq!(p.steps[1].timeout, Some(30));
+    assert_eq!(p.steps[0].env["LANG"], "C");
+    assert_eq!(p.steps[1].env["LANG"], "C");
+
+    // Only x- keys hold anchors; other unknown keys are still rejected
+    let err = pipeline::parse(&yaml.replace("x-bash", "defaults"))
+        .unwrap_err()
+        .to_string();
+    assert!(err.contains("unknown field `defaults`"), "{}", err);
+}
+
+#[test]
+fn parse_rejects_alias_that_duplicates_a_step_id() {
+    let yaml = r#"
+version: 1
+workspace: workspace
+steps:
+  - &build
+    id: build
+    type: bash
+    bash: make
+  - *build
+"#;
+    let err = pipeline::parse(yaml).unwrap_err().to_string();
+    assert!(err.contains("duplicate step id 'build'"), "{}", err);
+}
+
+// ─── Includes ───
+
+const WAKE: &str = r#"
+steps:
+  - id: wake
+    type: bash
+    bash: wakeonlan host
+"#;
+
+/// A pipeline dir next to a shared `common` dir, as in `include: [../common/x.yaml]`.
+fn include_setup(pipeline_yaml: &str, fragments: &[(&str, &str)]) -> (TempDir, std::path::PathBuf) {
+    let dir = TempDir::new().unwrap();
+    fs::create_dir_all(dir.path().join("common")).unwrap();
+    fs::create_dir_all(dir.path().join("nightly")).unwrap();
+    for (name, content) in fragments {
+        fs::write(dir.path().join("common").join(name), content).unwrap();
+    }
+    let path = dir.path().join("nightly/pipeline.yaml");
+    fs::write(&path, pipeline_yaml).unwrap();
+    (dir, path)
+}
+
+#[test]
+fn load_splices_included_steps_ahead_of_own() {
+    let nested = r#"
+include: [wake.yaml]
+steps:
+  - id: mount
+    type: bash
+    bash: mount /data
+"#;
+    let (_dir, path) = include_setup(
+        r#"
+version: 1
+workspace: workspace
+include: [../common/mount.yaml]
+steps:
+  - id: backup
+    type: bash
+    bash: rsync -a /data backup
+    needs: [mount]
+"#,
+        &[("wake.yaml", WAKE), ("mount.yaml", nested)],
+    );
+
+    let p = pipeline::load(&path).unwrap();
+    let ids: Vec<_> = p.steps.iter().map(|s| s.id.as_str()).collect();
+    assert_eq!(ids, ["wake", "mount", "backup"]);
+}
+
+#[test]
+fn load_rejects_included_id_collision() {
+    let (_dir, path) = include_setup(
+        r#"
+version: 1
+workspace: workspace
+include: [../common/wake.yaml]
+steps:
+  - id: wake
+    type: bash
+    bash: echo again
+"#,
+        &[("wake.yaml", WAKE)],
+    );
+
+    let err = pipeline::load(&path).unwrap_err().to_string();
+    assert!(
+        err.contains("duplicate step id 'wake' (from ../common/wake.yaml and pipeline.yaml)"),
+        "{}",
+        err
+    );
+}
+
+#[test]
+fn load_rejects_include_cycle() {
+    let (_dir, path) = include_setup(
+        r#"
+version: 1
+workspace: workspace
+include: [../common/a.yaml]
+steps: []
+"#,
+        &[
+            ("a.yaml", "include: [b.yaml]\nsteps: []\n"),
+            ("b.yaml", "include: [a.yaml]\nsteps: []\n"),
+        ],
+    );
+
+    let err = pipeline::load(&path).unwrap_err().to_string();
+    assert!(
+        err.contains(
+            "include '../common/a.yaml': include 'b.yaml': include 'a.yaml': include cycle"
+        ),
+        "{}",
+        err
+    );
+}
+
+#[test]
+fn load_rejects_missing_include() {
+    let (_dir, path) = include_setup(
+        "version: 1\nworkspace: workspace\ninclude: [../common/gone.yaml]\nsteps: []\n",
+        &[],
+    );
+    let err = pipeline::load(&path).unwrap_err();
+    assert!(matches!(err, CronclawError::Parse(_)), "{:?}", err);
+    let err = err.to_string();
+    assert!(
+        err.contains("include '../common/gone.yaml': failed to read"),
+        "{}",
+        err
+    );
+}
+
+#[test]
+fn parse_rejects_include_without_a_file() {
+    let err = pipeline::parse(
+        "version: 1\nworkspace: workspace\ninclude: [../common/wake.yaml]\nsteps: []\n",
+    )
+    .unwrap_err()
+    .to_string();
+    assert!(err.contains("'include' needs a pipeline file"), "{}", err);
+}
+
+// ─── Matrix ───
+
+#[test]
+fn matrix_expands_step_per_value() {
+    let yaml = r#"
+version: 1
+workspace: workspace
+steps:
+  - id: fetch
+    type: bash
+    bash: echo fetch
+  - id: analyse
+    type: agent
+    agent: researcher
+    prompt: "Analyse {{ matrix }} using {{file: data.json}}"
+    matrix: [rust, go]
+    outputs:
+      - name: report
+        path: "{{matrix}}.md"
+        tmp: "{{matrix}}.md.tmp"
+  - id: summarise
+    type: bash
+    bash: cat rust.md go.md
+    needs: [analyse]
+"#;
+    let p = pipeline::parse(yaml).unwrap();
+    let ids: Vec<_> = p.steps.iter().map(|s| s.id.as_str()).collect();
+    assert_eq!(ids, ["fetch", "analyse-rust", "analyse-go", "summarise"]);
+    // Each copy is tracked in state on its own
+    let state = State::from_pipeline(&p);
+    assert_eq!(state.order, ids);
+    assert!(!state.steps.contains_key("analyse"));
+    assert_eq!(
+        p.steps[1].prompt.as_deref(),
+        Some("Analyse rust using {{file: data.json}}")
+    );
+    assert_eq!(p.steps[2].outputs[0].path, "go.md");
+    assert_eq!(p.steps[2].outputs[0].tmp, "go.md.tmp");
+    assert!(p.steps[1].matrix.is_empty());
+    assert_eq!(
+        p.steps[3].needs.as_deref(),
+        Some(&["analyse-rust".to_string(), "analyse-go".to_string()][..])
+    );
+}
+
+#[test]
+fn matrix_rejects_duplicate_generated_ids() {
+    let yaml = r#"
+version: 1
+workspace: workspace
+steps:
+  - id: analyse
+    type: bash
+    bash: echo {{ matrix }}
+    matrix: [a, a]
+"#;
+    let err = pipeline::parse(yaml).unwrap_err().to_string();
+    assert!(err.contains("duplicate step id 'analyse-a'"), "{}", err);
+
+    let yaml = r#"
+version: 1
+workspace: workspace
+steps:
+  - id: analyse-a
+    type: bash
+    bash: echo a
+  - id: analyse
+    type: bash
+    bash: echo {{ matrix }}
+    matrix: [a]
+"#;
+    let err = pipeline::parse(yaml).unwrap_err().to_string();
+    assert!(err.contains("duplicate step id 'analyse-a'"), "{}", err);
+}
+
+#[test]
+fn matrix_rejects_values_unfit_for_ids() {
+    let yaml = r#"
+version: 1
+workspace: workspace
+steps:
+  - id: analyse
+    type: bash
+    bash: echo {{ matrix }}
+    matrix: ["two words"]
+"#;
+    let err = pipeline::parse(yaml).unwrap_err().to_string();
+    assert!(err.contains("matrix value 'two words'"), "{}", err);
+}
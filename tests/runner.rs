@@ -1,15 +1,21 @@
 use cronclaw::config::Config;
+use cronclaw::error::{CronclawError, ErrorKind};
+use cronclaw::history;
 use cronclaw::pipeline;
-use cronclaw::runner;
+use cronclaw::runner::{self, RunOptions, Ticked};
 use cronclaw::state::{self, State, StepStatus};
 use std::fs;
 use std::os::unix::fs::PermissionsExt;
 use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
 use tempfile::TempDir;
 
 /// Mutex to serialize agent tests that mutate OPENCLAW_BIN env var.
 static OPENCLAW_BIN_LOCK: Mutex<()> = Mutex::new(());
 
+/// Mutex to serialize template tests that set environment variables.
+static TEMPLATE_ENV_LOCK: Mutex<()> = Mutex::new(());
+
 // ─── Template resolution ───
 
 #[test]
@@ -33,6 +39,27 @@ fn resolve_multiple_templates() {
     assert_eq!(result, "First: AAA Second: BBB");
 }
 
+#[test]
+fn resolve_repeated_template_each_occurrence() {
+    let dir = TempDir::new().unwrap();
+    fs::write(dir.path().join("a.txt"), "A").unwrap();
+
+    let input = "{{ file:a.txt }}, {{file:a.txt}} and {{ file: a.txt }}";
+    let result = runner::resolve_templates(input, dir.path()).unwrap();
+    assert_eq!(result, "A, A and A");
+}
+
+#[test]
+fn resolve_template_content_is_not_expanded_again() {
+    let dir = TempDir::new().unwrap();
+    fs::write(dir.path().join("a.txt"), "see {{ file:b.txt }}").unwrap();
+    fs::write(dir.path().join("b.txt"), "B").unwrap();
+
+    let input = "{{ file:a.txt }} / {{ file:b.txt }}";
+    let result = runner::resolve_templates(input, dir.path()).unwrap();
+    assert_eq!(result, "see {{ file:b.txt }} / B");
+}
+
 #[test]
 fn resolve_template_with_spaces() {
     let dir = TempDir::new().unwrap();
@@ -54,7 +81,33 @@ fn resolve_template_missing_file_errors() {
     let dir = TempDir::new().unwrap();
     let result = runner::resolve_templates("{{ file:missing.txt }}", dir.path());
     assert!(result.is_err());
-    assert!(result.unwrap_err().contains("missing.txt"));
+    assert!(result.unwrap_err().to_string().contains("missing.txt"));
+}
+
+#[test]
+fn resolve_template_error_names_line_in_multiline_input() {
+    let dir = TempDir::new().unwrap();
+    fs::write(dir.path().join("notes.md"), "notes").unwrap();
+
+    let input = "Summarise:\n{{ file:notes.md }}\n\nAnd compare with {{ file:typo.md }}\n";
+    let err = runner::resolve_templates(input, dir.path())
+        .unwrap_err()
+        .to_string();
+    assert!(
+        err.starts_with("template '{{ file:typo.md }}' on line 4: failed to read"),
+        "{}",
+        err
+    );
+
+    // A one-line input has no line to point at
+    let err = runner::resolve_templates("{{ file:typo.md }}", dir.path())
+        .unwrap_err()
+        .to_string();
+    assert!(
+        err.starts_with("template '{{ file:typo.md }}': "),
+        "{}",
+        err
+    );
 }
 
 #[test]
@@ -74,6 +127,340 @@ fn resolve_template_multiline_content() {
     assert!(result.contains("line 1\nline 2\nline 3"));
 }
 
+#[test]
+fn resolve_env_template() {
+    let dir = TempDir::new().unwrap();
+    let _guard = TEMPLATE_ENV_LOCK.lock().unwrap();
+
+    // SAFETY: serialized by mutex — no concurrent env mutation.
+    unsafe { std::env::set_var("CRONCLAW_TEST_HOST", "caladan") };
+    let result = runner::resolve_templates("host={{  env: CRONCLAW_TEST_HOST  }}", dir.path());
+    unsafe { std::env::remove_var("CRONCLAW_TEST_HOST") };
+
+    assert_eq!(result.unwrap(), "host=caladan");
+}
+
+#[test]
+fn resolve_env_template_unset_errors() {
+    let dir = TempDir::new().unwrap();
+    let result = runner::resolve_templates("{{ env:CRONCLAW_TEST_NEVER_SET }}", dir.path());
+    let err = result.unwrap_err().to_string();
+    assert!(err.contains("CRONCLAW_TEST_NEVER_SET"));
+    assert!(err.contains("not set"));
+}
+
+#[test]
+fn resolve_env_template_value_is_inserted_verbatim() {
+    let dir = TempDir::new().unwrap();
+    fs::write(dir.path().join("secret.txt"), "hunter2").unwrap();
+    let _guard = TEMPLATE_ENV_LOCK.lock().unwrap();
+
+    // SAFETY: serialized by mutex — no concurrent env mutation.
+    unsafe { std::env::set_var("CRONCLAW_TEST_MARKER", "{{ file:secret.txt }}") };
+    let result = runner::resolve_templates("value={{ env:CRONCLAW_TEST_MARKER }}", dir.path());
+    unsafe { std::env::remove_var("CRONCLAW_TEST_MARKER") };
+
+    assert_eq!(result.unwrap(), "value={{ file:secret.txt }}");
+}
+
+#[test]
+fn resolve_mixed_file_and_env_templates() {
+    let dir = TempDir::new().unwrap();
+    fs::write(dir.path().join("notes.md"), "some notes").unwrap();
+    let _guard = TEMPLATE_ENV_LOCK.lock().unwrap();
+
+    // SAFETY: serialized by mutex — no concurrent env mutation.
+    unsafe { std::env::set_var("CRONCLAW_TEST_USER", "albin") };
+    let result = runner::resolve_templates(
+        "For {{ env:CRONCLAW_TEST_USER }}: {{ file:notes.md }}",
+        dir.path(),
+    );
+    unsafe { std::env::remove_var("CRONCLAW_TEST_USER") };
+
+    assert_eq!(result.unwrap(), "For albin: some notes");
+}
+
+fn step_template_pipeline() -> pipeline::Pipeline {
+    pipeline::parse(
+        r#"
+version: 1
+workspace: workspace
+steps:
+  - id: research
+    type: bash
+    bash: echo findings
+    output: research.md
+  - id: quiet
+    type: bash
+    bash: echo hi
+"#,
+    )
+    .unwrap()
+}
+
+#[test]
+fn resolve_step_template_reads_output_file() {
+    let dir = TempDir::new().unwrap();
+    fs::write(dir.path().join("research.md"), "findings").unwrap();
+    let p = step_template_pipeline();
+    let ctx = runner::TemplateContext { pipeline: Some(&p) };
+
+    let result = runner::resolve_templates_with("Summarise: {{ step:research }}", dir.path(), &ctx);
+    assert_eq!(result.unwrap(), "Summarise: findings");
+}
+
+#[test]
+fn run_resolves_var_templates_from_run_options() {
+    let dir = TempDir::new().unwrap();
+    setup_pipeline(
+        dir.path(),
+        r#"
+version: 1
+workspace: workspace
+vars:
+  host: null
+  greeting: hello
+steps:
+  - id: greet
+    type: bash
+    bash: echo "$GREETING $HOST" > greeting.txt
+    env:
+      HOST: "{{ var:host }}"
+      GREETING: "{{ var:greeting }}"
+"#,
+    );
+    let pd = pipeline_dir(dir.path());
+
+    let err = runner::run_pipeline(&pd, &Config::default(), &RunOptions::default()).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::Invalid);
+    assert!(
+        err.to_string().contains("variable 'host' has no default"),
+        "{}",
+        err
+    );
+    assert!(!pd.join("state.json").exists());
+
+    let opts = RunOptions {
+        vars: [("host".to_string(), "caladan".to_string())].into(),
+        ..RunOptions::default()
+    };
+    runner::run_pipeline(&pd, &Config::default(), &opts).unwrap();
+    let greeting = fs::read_to_string(pd.join("workspace/greeting.txt")).unwrap();
+    assert_eq!(greeting.trim(), "hello caladan");
+}
+
+#[test]
+fn resolve_step_template_errors() {
+    let dir = TempDir::new().unwrap();
+    let p = step_template_pipeline();
+    let ctx = runner::TemplateContext { pipeline: Some(&p) };
+
+    let err = runner::resolve_templates_with("{{ step:nope }}", dir.path(), &ctx)
+        .unwrap_err()
+        .to_string();
+    assert!(err.contains("unknown step 'nope'"));
+
+    let err = runner::resolve_templates_with("{{ step:quiet }}", dir.path(), &ctx)
+        .unwrap_err()
+        .to_string();
+    assert!(err.contains("no output file"));
+
+    let err = runner::resolve_templates_with("{{ step:research }}", dir.path(), &ctx)
+        .unwrap_err()
+        .to_string();
+    assert!(err.contains("not produced"));
+    assert!(err.contains("research.md"));
+
+    // Outside a pipeline there is nothing to look up
+    let err = runner::resolve_templates("{{ step:research }}", dir.path())
+        .unwrap_err()
+        .to_string();
+    assert!(err.contains("only available inside a pipeline"));
+}
+
+#[test]
+fn run_agent_prompt_resolves_step_template() {
+    let dir = TempDir::new().unwrap();
+
+    let fake_bin = install_fake_openclaw(
+        dir.path(),
+        r#"
+while [ "$#" -gt 0 ]; do
+    case "$1" in
+        --message) shift; echo "$1" > "$PWD/received_prompt.txt"; break;;
+        *) shift;;
+    esac
+done
+exit 0
+"#,
+    );
+
+    let pd = pipeline_dir(dir.path());
+    setup_pipeline(
+        dir.path(),
+        r#"
+version: 1
+workspace: workspace
+steps:
+  - id: gather
+    type: bash
+    bash: echo "gathered 7 items"
+    output: gathered.txt
+  - id: analyse
+    type: agent
+    agent: worker
+    prompt: "Analyse: {{ step:gather }}"
+"#,
+    );
+
+    let cfg = Config::default();
+    run_with_fake_openclaw(&pd, &fake_bin, &cfg).unwrap();
+    run_with_fake_openclaw(&pd, &fake_bin, &cfg).unwrap();
+
+    let received = fs::read_to_string(pd.join("workspace").join("received_prompt.txt")).unwrap();
+    assert!(received.contains("Analyse: gathered 7 items"));
+}
+
+#[test]
+fn run_agent_prompt_file_is_read_and_resolved() {
+    let dir = TempDir::new().unwrap();
+
+    let fake_bin = install_fake_openclaw(
+        dir.path(),
+        r#"
+while [ "$#" -gt 0 ]; do
+    case "$1" in
+        --message) shift; echo "$1" > "$PWD/received_prompt.txt"; break;;
+        *) shift;;
+    esac
+done
+exit 0
+"#,
+    );
+
+    let pd = pipeline_dir(dir.path());
+    setup_pipeline(
+        dir.path(),
+        r#"
+version: 1
+workspace: workspace
+steps:
+  - id: gather
+    type: bash
+    bash: echo "gathered 7 items"
+    output: gathered.txt
+  - id: analyse
+    type: agent
+    agent: worker
+    prompt_file: prompts/analyse.md
+"#,
+    );
+    fs::create_dir_all(pd.join("workspace/prompts")).unwrap();
+    fs::write(
+        pd.join("workspace/prompts/analyse.md"),
+        "Analyse: {{ step:gather }}\n",
+    )
+    .unwrap();
+
+    let cfg = Config::default();
+    run_with_fake_openclaw(&pd, &fake_bin, &cfg).unwrap();
+    run_with_fake_openclaw(&pd, &fake_bin, &cfg).unwrap();
+
+    let received = fs::read_to_string(pd.join("workspace").join("received_prompt.txt")).unwrap();
+    assert!(received.contains("Analyse: gathered 7 items"));
+}
+
+#[test]
+fn resolve_template_rejects_parent_dir_escape() {
+    let dir = TempDir::new().unwrap();
+    let workspace = dir.path().join("workspace");
+    fs::create_dir_all(&workspace).unwrap();
+    fs::write(dir.path().join("secret.txt"), "secret").unwrap();
+
+    let err = runner::resolve_templates("{{ file:../secret.txt }}", &workspace)
+        .unwrap_err()
+        .to_string();
+    assert!(err.contains("outside the workspace"), "got: {}", err);
+}
+
+#[test]
+fn resolve_template_rejects_symlink_escape() {
+    let dir = TempDir::new().unwrap();
+    let workspace = dir.path().join("workspace");
+    fs::create_dir_all(&workspace).unwrap();
+    fs::write(dir.path().join("secret.txt"), "secret").unwrap();
+    std::os::unix::fs::symlink(dir.path().join("secret.txt"), workspace.join("link.txt")).unwrap();
+
+    let err = runner::resolve_templates("{{ file:link.txt }}", &workspace)
+        .unwrap_err()
+        .to_string();
+    assert!(err.contains("outside the workspace"), "got: {}", err);
+}
+
+#[test]
+fn resolve_template_allows_symlink_within_workspace() {
+    let dir = TempDir::new().unwrap();
+    fs::create_dir_all(dir.path().join("data")).unwrap();
+    fs::write(dir.path().join("data/real.txt"), "inside").unwrap();
+    std::os::unix::fs::symlink("data/real.txt", dir.path().join("link.txt")).unwrap();
+
+    let result = runner::resolve_templates("{{ file:link.txt }}", dir.path()).unwrap();
+    assert_eq!(result, "inside");
+}
+
+#[test]
+fn resolve_glob_template_concatenates_sorted_matches() {
+    let dir = TempDir::new().unwrap();
+    fs::create_dir_all(dir.path().join("notes")).unwrap();
+    fs::write(dir.path().join("notes/b.md"), "second\n").unwrap();
+    fs::write(dir.path().join("notes/a.md"), "first\n").unwrap();
+    fs::write(dir.path().join("notes/skip.txt"), "not markdown\n").unwrap();
+
+    let result = runner::resolve_templates("{{ glob:notes/*.md }}", dir.path()).unwrap();
+    assert_eq!(result, "first\nsecond\n");
+}
+
+#[test]
+fn resolve_glob_template_named() {
+    let dir = TempDir::new().unwrap();
+    fs::create_dir_all(dir.path().join("notes")).unwrap();
+    fs::write(dir.path().join("notes/a.md"), "first").unwrap();
+    fs::write(dir.path().join("notes/b.md"), "second\n").unwrap();
+
+    let result = runner::resolve_templates("{{ glob:notes/*.md | named }}", dir.path()).unwrap();
+    assert_eq!(
+        result,
+        "--- notes/a.md ---\nfirst\n--- notes/b.md ---\nsecond\n"
+    );
+}
+
+#[test]
+fn resolve_glob_template_no_matches() {
+    let dir = TempDir::new().unwrap();
+
+    let err = runner::resolve_templates("{{ glob:notes/*.md }}", dir.path())
+        .unwrap_err()
+        .to_string();
+    assert!(err.contains("no files match"), "got: {}", err);
+
+    let result =
+        runner::resolve_templates("[{{ glob:notes/*.md | optional }}]", dir.path()).unwrap();
+    assert_eq!(result, "[]");
+}
+
+#[test]
+fn resolve_glob_template_rejects_escape() {
+    let dir = TempDir::new().unwrap();
+    let workspace = dir.path().join("workspace");
+    fs::create_dir_all(&workspace).unwrap();
+    fs::write(dir.path().join("secret.md"), "secret").unwrap();
+
+    let err = runner::resolve_templates("{{ glob:../*.md }}", &workspace)
+        .unwrap_err()
+        .to_string();
+    assert!(err.contains("outside the workspace"), "got: {}", err);
+}
+
 // ─── Output promotion ───
 
 #[test]
@@ -94,13 +481,59 @@ steps:
         tmp: out.txt.tmp
 "#;
     let p = pipeline::parse(yaml).unwrap();
-    runner::promote_outputs(&p.steps[0], dir.path()).unwrap();
+    let hashes = runner::promote_outputs(&p.steps[0], dir.path()).unwrap();
 
     assert!(!dir.path().join("out.txt.tmp").exists());
     assert_eq!(
         fs::read_to_string(dir.path().join("out.txt")).unwrap(),
         "data"
     );
+    assert_eq!(
+        hashes["out"],
+        "3a6eb0790f39ac87c94f3856b2dd2c5d110e6811602261a9a923d3bb23adc8b7"
+    );
+}
+
+#[test]
+fn promote_outputs_rejects_tmp_under_min_bytes() {
+    let dir = TempDir::new().unwrap();
+    fs::write(dir.path().join("summary.md.tmp"), "").unwrap();
+    fs::write(dir.path().join("raw.txt.tmp"), "").unwrap();
+
+    let yaml = r#"
+version: 1
+workspace: workspace
+steps:
+  - id: s
+    type: bash
+    bash: echo
+    outputs:
+      - name: raw
+        path: raw.txt
+        tmp: raw.txt.tmp
+      - name: summary
+        path: summary.md
+        tmp: summary.md.tmp
+        min_bytes: 1
+"#;
+    let p = pipeline::parse(yaml).unwrap();
+    let err = runner::promote_outputs(&p.steps[0], dir.path())
+        .unwrap_err()
+        .to_string();
+    assert!(
+        err.contains(
+            "output 'summary': tmp file 'summary.md.tmp' is 0 bytes, under its min_bytes of 1"
+        ),
+        "{}",
+        err
+    );
+    // Nothing is promoted, not even the output without a minimum
+    assert!(!dir.path().join("raw.txt").exists());
+    assert!(!dir.path().join("summary.md").exists());
+
+    fs::write(dir.path().join("summary.md.tmp"), "done").unwrap();
+    runner::promote_outputs(&p.steps[0], dir.path()).unwrap();
+    assert!(dir.path().join("summary.md").exists());
 }
 
 #[test]
@@ -120,14 +553,19 @@ steps:
         tmp: result.txt.tmp
 "#;
     let p = pipeline::parse(yaml).unwrap();
-    let err = runner::promote_outputs(&p.steps[0], dir.path()).unwrap_err();
+    let err = runner::promote_outputs(&p.steps[0], dir.path())
+        .unwrap_err()
+        .to_string();
     assert!(err.contains("result"));
     assert!(err.contains("not found"));
 }
 
 #[test]
-fn promote_no_outputs_succeeds() {
+fn promote_outputs_missing_tmp_promotes_none() {
     let dir = TempDir::new().unwrap();
+    fs::write(dir.path().join("a.txt.tmp"), "new a").unwrap();
+    fs::write(dir.path().join("a.txt"), "old a").unwrap();
+    fs::write(dir.path().join("c.txt.tmp"), "new c").unwrap();
 
     let yaml = r#"
 version: 1
@@ -135,41 +573,138 @@ workspace: workspace
 steps:
   - id: s
     type: bash
-    bash: echo hi
+    bash: echo
+    outputs:
+      - name: a
+        path: a.txt
+        tmp: a.txt.tmp
+      - name: b
+        path: b.txt
+        tmp: b.txt.tmp
+      - name: c
+        path: c.txt
+        tmp: c.txt.tmp
 "#;
     let p = pipeline::parse(yaml).unwrap();
-    runner::promote_outputs(&p.steps[0], dir.path()).unwrap();
-}
-
-// ─── Full pipeline tick behavior ───
-
-fn setup_pipeline(dir: &std::path::Path, yaml: &str) {
-    let pipeline_dir = dir.join("pipelines").join("test");
-    fs::create_dir_all(&pipeline_dir).unwrap();
-    fs::write(pipeline_dir.join("pipeline.yaml"), yaml).unwrap();
-}
+    let err = runner::promote_outputs(&p.steps[0], dir.path())
+        .unwrap_err()
+        .to_string();
+    assert!(err.contains("output 'b'"), "got: {}", err);
 
-fn pipeline_dir(dir: &std::path::Path) -> std::path::PathBuf {
-    dir.join("pipelines").join("test")
+    // Nothing moved, not even the outputs ahead of the missing one
+    assert_eq!(
+        fs::read_to_string(dir.path().join("a.txt")).unwrap(),
+        "old a"
+    );
+    assert!(dir.path().join("a.txt.tmp").exists());
+    assert!(!dir.path().join("c.txt").exists());
 }
 
 #[test]
-fn run_single_bash_step_completes() {
+fn promote_outputs_rejects_paths_outside_workspace() {
     let dir = TempDir::new().unwrap();
-    setup_pipeline(
-        dir.path(),
-        r#"
+    let workspace = dir.path().join("workspace");
+    fs::create_dir_all(&workspace).unwrap();
+    fs::write(workspace.join("out.txt.tmp"), "data").unwrap();
+    // `..` is rejected when parsing, so escape through a symlink instead
+    std::os::unix::fs::symlink(dir.path(), workspace.join("up")).unwrap();
+
+    let yaml = r#"
 version: 1
 workspace: workspace
 steps:
-  - id: hello
+  - id: s
+    type: bash
+    bash: echo
+    outputs:
+      - name: out
+        path: up/out.txt
+        tmp: out.txt.tmp
+"#;
+    let p = pipeline::parse(yaml).unwrap();
+    let err = runner::promote_outputs(&p.steps[0], &workspace)
+        .unwrap_err()
+        .to_string();
+    assert!(err.contains("outside the workspace"), "got: {}", err);
+    assert!(!dir.path().join("out.txt").exists());
+    assert!(workspace.join("out.txt.tmp").exists());
+}
+
+#[test]
+fn promote_no_outputs_succeeds() {
+    let dir = TempDir::new().unwrap();
+
+    let yaml = r#"
+version: 1
+workspace: workspace
+steps:
+  - id: s
+    type: bash
+    bash: echo hi
+"#;
+    let p = pipeline::parse(yaml).unwrap();
+    let hashes = runner::promote_outputs(&p.steps[0], dir.path()).unwrap();
+    assert!(hashes.is_empty());
+}
+
+#[test]
+fn run_records_output_hashes_in_state() {
+    let dir = TempDir::new().unwrap();
+    setup_pipeline(
+        dir.path(),
+        r#"
+version: 1
+workspace: workspace
+steps:
+  - id: produce
+    type: bash
+    bash: echo result > result.txt.tmp
+    outputs:
+      - name: result
+        path: result.txt
+        tmp: result.txt.tmp
+"#,
+    );
+
+    let pd = pipeline_dir(dir.path());
+    runner::run_pipeline(&pd, &Config::default(), &RunOptions::default()).unwrap();
+
+    let s = state::load(&pd.join("state.json")).unwrap().unwrap();
+    assert_eq!(
+        s.steps["produce"].output_hashes["result"],
+        "5656fafa00d4f294bcb606cf4f7d4fa877390e46f583e8b3c8744ace104a31d1"
+    );
+}
+
+// ─── Full pipeline tick behavior ───
+
+fn setup_pipeline(dir: &std::path::Path, yaml: &str) {
+    let pipeline_dir = dir.join("pipelines").join("test");
+    fs::create_dir_all(&pipeline_dir).unwrap();
+    fs::write(pipeline_dir.join("pipeline.yaml"), yaml).unwrap();
+}
+
+fn pipeline_dir(dir: &std::path::Path) -> std::path::PathBuf {
+    dir.join("pipelines").join("test")
+}
+
+#[test]
+fn run_single_bash_step_completes() {
+    let dir = TempDir::new().unwrap();
+    setup_pipeline(
+        dir.path(),
+        r#"
+version: 1
+workspace: workspace
+steps:
+  - id: hello
     type: bash
     bash: echo "hi"
 "#,
     );
 
     let cfg = Config::default();
-    runner::run_pipeline(&pipeline_dir(dir.path()), &cfg, false).unwrap();
+    runner::run_pipeline(&pipeline_dir(dir.path()), &cfg, &RunOptions::default()).unwrap();
 
     let state = state::load(&pipeline_dir(dir.path()).join("state.json"))
         .unwrap()
@@ -177,6 +712,38 @@ steps:
     assert_eq!(state.steps["hello"].status, StepStatus::Completed);
 }
 
+#[test]
+fn run_records_step_timestamps() {
+    let dir = TempDir::new().unwrap();
+    setup_pipeline(
+        dir.path(),
+        r#"
+version: 1
+workspace: workspace
+steps:
+  - id: hello
+    type: bash
+    bash: echo hi
+  - id: later
+    type: bash
+    bash: echo later
+"#,
+    );
+
+    let cfg = Config::default();
+    let pd = pipeline_dir(dir.path());
+    runner::run_pipeline(&pd, &cfg, &RunOptions::default()).unwrap();
+
+    let s = state::load(&pd.join("state.json")).unwrap().unwrap();
+    let hello = &s.steps["hello"];
+    assert!(hello.started_at.as_deref().unwrap().ends_with('Z'));
+    assert!(hello.finished_at.is_some());
+    assert!(hello.duration_secs.is_some());
+
+    // Steps that haven't run yet carry no timing
+    assert!(s.steps["later"].started_at.is_none());
+}
+
 #[test]
 fn run_advances_one_step_per_tick() {
     let dir = TempDir::new().unwrap();
@@ -194,37 +761,2691 @@ steps:
     bash: echo 2
   - id: third
     type: bash
-    bash: echo 3
+    bash: echo 3
+"#,
+    );
+
+    let cfg = Config::default();
+    let pd = pipeline_dir(dir.path());
+
+    // Tick 1
+    runner::run_pipeline(&pd, &cfg, &RunOptions::default()).unwrap();
+    let s = state::load(&pd.join("state.json")).unwrap().unwrap();
+    assert_eq!(s.steps["first"].status, StepStatus::Completed);
+    assert_eq!(s.steps["second"].status, StepStatus::Pending);
+    assert_eq!(s.steps["third"].status, StepStatus::Pending);
+
+    // Tick 2
+    runner::run_pipeline(&pd, &cfg, &RunOptions::default()).unwrap();
+    let s = state::load(&pd.join("state.json")).unwrap().unwrap();
+    assert_eq!(s.steps["first"].status, StepStatus::Completed);
+    assert_eq!(s.steps["second"].status, StepStatus::Completed);
+    assert_eq!(s.steps["third"].status, StepStatus::Pending);
+
+    // Tick 3
+    runner::run_pipeline(&pd, &cfg, &RunOptions::default()).unwrap();
+    let s = state::load(&pd.join("state.json")).unwrap().unwrap();
+    assert_eq!(s.steps["first"].status, StepStatus::Completed);
+    assert_eq!(s.steps["second"].status, StepStatus::Completed);
+    assert_eq!(s.steps["third"].status, StepStatus::Completed);
+}
+
+#[test]
+fn run_failed_step_blocks_pipeline() {
+    let dir = TempDir::new().unwrap();
+    setup_pipeline(
+        dir.path(),
+        r#"
+version: 1
+workspace: workspace
+steps:
+  - id: fail
+    type: bash
+    bash: exit 1
+  - id: after
+    type: bash
+    bash: echo "should not run"
+"#,
+    );
+
+    let cfg = Config::default();
+    let pd = pipeline_dir(dir.path());
+
+    // Tick 1 — step fails
+    let result = runner::run_pipeline(&pd, &cfg, &RunOptions::default());
+    assert_eq!(result.unwrap_err().kind(), ErrorKind::Step);
+
+    let s = state::load(&pd.join("state.json")).unwrap().unwrap();
+    assert_eq!(s.steps["fail"].status, StepStatus::Failed);
+    assert_eq!(s.steps["after"].status, StepStatus::Pending);
+
+    // Tick 2 — pipeline is blocked, no progress
+    runner::run_pipeline(&pd, &cfg, &RunOptions::default()).unwrap();
+    let s = state::load(&pd.join("state.json")).unwrap().unwrap();
+    assert_eq!(s.steps["fail"].status, StepStatus::Failed);
+    assert_eq!(s.steps["after"].status, StepStatus::Pending);
+}
+
+#[test]
+fn run_allowed_failure_does_not_block_pipeline() {
+    let dir = TempDir::new().unwrap();
+    setup_pipeline(
+        dir.path(),
+        r#"
+version: 1
+workspace: workspace
+steps:
+  - id: notify
+    type: bash
+    bash: exit 1
+    allow_failure: true
+  - id: after
+    type: bash
+    bash: touch done.txt
+"#,
+    );
+
+    let cfg = Config::default();
+    let pd = pipeline_dir(dir.path());
+
+    // Tick 1 — step fails, but the tick itself succeeds
+    runner::run_pipeline(&pd, &cfg, &RunOptions::default()).unwrap();
+    let s = state::load(&pd.join("state.json")).unwrap().unwrap();
+    assert_eq!(s.steps["notify"].status, StepStatus::Completed);
+    assert_eq!(s.steps["notify"].exit_code, Some(1));
+    assert!(s.steps["notify"].error_message.is_some());
+
+    // Tick 2 — the final step still runs
+    runner::run_pipeline(&pd, &cfg, &RunOptions::default()).unwrap();
+    let s = state::load(&pd.join("state.json")).unwrap().unwrap();
+    assert_eq!(s.steps["after"].status, StepStatus::Completed);
+    assert!(pd.join("workspace").join("done.txt").exists());
+}
+
+#[test]
+fn run_failed_step_records_reason_and_exit_code() {
+    let dir = TempDir::new().unwrap();
+    setup_pipeline(
+        dir.path(),
+        r#"
+version: 1
+workspace: workspace
+steps:
+  - id: fail
+    type: bash
+    bash: exit 3
+"#,
+    );
+
+    let cfg = Config::default();
+    let pd = pipeline_dir(dir.path());
+    runner::run_pipeline(&pd, &cfg, &RunOptions::default()).unwrap_err();
+
+    let s = state::load(&pd.join("state.json")).unwrap().unwrap();
+    assert_eq!(s.steps["fail"].exit_code, Some(3));
+    assert!(
+        s.steps["fail"]
+            .error_message
+            .as_deref()
+            .unwrap()
+            .contains("exited with code 3")
+    );
+}
+
+#[test]
+fn run_timed_out_step_records_reason_without_exit_code() {
+    let dir = TempDir::new().unwrap();
+    setup_pipeline(
+        dir.path(),
+        r#"
+version: 1
+workspace: workspace
+steps:
+  - id: slow
+    type: bash
+    timeout: 1
+    bash: sleep 5
+"#,
+    );
+
+    let cfg = Config::default();
+    let pd = pipeline_dir(dir.path());
+    let err = runner::run_pipeline(&pd, &cfg, &RunOptions::default()).unwrap_err();
+    assert!(
+        matches!(&err, CronclawError::Timeout { id, .. } if id == "slow"),
+        "{:?}",
+        err
+    );
+
+    let s = state::load(&pd.join("state.json")).unwrap().unwrap();
+    assert_eq!(s.steps["slow"].status, StepStatus::Failed);
+    assert!(s.steps["slow"].exit_code.is_none());
+    assert!(
+        s.steps["slow"]
+            .error_message
+            .as_deref()
+            .unwrap()
+            .contains("timed out")
+    );
+}
+
+#[test]
+fn run_interrupt_stops_step_and_leaves_it_pending() {
+    static STOP: AtomicBool = AtomicBool::new(false);
+    let dir = TempDir::new().unwrap();
+    setup_pipeline(
+        dir.path(),
+        r#"
+version: 1
+workspace: workspace
+steps:
+  - id: slow
+    type: bash
+    bash: trap 'echo cleaned up > cleanup.txt; exit 1' TERM; sleep 30 & wait
+  - id: after
+    type: bash
+    bash: touch after.txt
+"#,
+    );
+
+    let pd = pipeline_dir(dir.path());
+    let opts = RunOptions {
+        stop: Some(&STOP),
+        ..RunOptions::default()
+    };
+    let started = std::time::Instant::now();
+    let err = std::thread::scope(|scope| {
+        scope.spawn(|| {
+            std::thread::sleep(std::time::Duration::from_millis(500));
+            STOP.store(true, Ordering::SeqCst);
+        });
+        runner::run_pipeline(&pd, &Config::default(), &opts).unwrap_err()
+    });
+    assert!(started.elapsed().as_secs() < 10);
+    assert!(
+        matches!(&err, CronclawError::Interrupted { id, .. } if id == "slow"),
+        "{:?}",
+        err
+    );
+    assert!(
+        err.to_string().contains("interrupted (terminated)"),
+        "{}",
+        err
+    );
+
+    // The step's process group got the same SIGTERM as on a timeout
+    let workspace = pd.join("workspace");
+    let cleanup = fs::read_to_string(workspace.join("cleanup.txt")).unwrap();
+    assert_eq!(cleanup.trim(), "cleaned up");
+
+    let s = state::load(&pd.join("state.json")).unwrap().unwrap();
+    assert_eq!(s.steps["slow"].status, StepStatus::Pending);
+    assert_eq!(s.steps["slow"].attempts, 0);
+    assert!(s.steps["slow"].pid.is_none());
+    assert!(!workspace.join("after.txt").exists());
+
+    // A tick that is already stopped starts nothing
+    runner::run_pipeline(&pd, &Config::default(), &opts).unwrap();
+    let s = state::load(&pd.join("state.json")).unwrap().unwrap();
+    assert_eq!(s.steps["slow"].status, StepStatus::Pending);
+}
+
+#[test]
+fn run_timeout_sends_sigterm_first() {
+    let dir = TempDir::new().unwrap();
+    setup_pipeline(
+        dir.path(),
+        r#"
+version: 1
+workspace: workspace
+steps:
+  - id: slow
+    type: bash
+    timeout: 1
+    bash: trap 'echo cleaned up > cleanup.txt; exit 1' TERM; sleep 5 & wait
+"#,
+    );
+
+    let cfg = Config::default();
+    let pd = pipeline_dir(dir.path());
+    let err = runner::run_pipeline(&pd, &cfg, &RunOptions::default()).unwrap_err();
+    assert!(
+        err.to_string().contains("timed out after 1s (terminated)"),
+        "got: {}",
+        err
+    );
+
+    // The TERM handler got to run
+    let cleanup = fs::read_to_string(pd.join("workspace").join("cleanup.txt")).unwrap();
+    assert_eq!(cleanup.trim(), "cleaned up");
+}
+
+#[test]
+fn run_timeout_kills_after_grace_period() {
+    let dir = TempDir::new().unwrap();
+    setup_pipeline(
+        dir.path(),
+        r#"
+version: 1
+workspace: workspace
+steps:
+  - id: stubborn
+    type: bash
+    timeout: 1
+    bash: trap '' TERM; sleep 10
+"#,
+    );
+
+    let cfg = Config {
+        kill_grace_secs: 1,
+        ..Config::default()
+    };
+    let pd = pipeline_dir(dir.path());
+    let start = std::time::Instant::now();
+    let err = runner::run_pipeline(&pd, &cfg, &RunOptions::default()).unwrap_err();
+    assert!(
+        err.to_string().contains("timed out after 1s (killed"),
+        "got: {}",
+        err
+    );
+    assert!(start.elapsed() < std::time::Duration::from_secs(5));
+}
+
+/// Whether a process with this pid is still alive, allowing a moment for it to
+/// die. Zombies count as dead: an orphan is only reaped whenever init gets to it.
+fn process_alive(pid: &str) -> bool {
+    for _ in 0..20 {
+        let alive = fs::read_to_string(format!("/proc/{}/stat", pid))
+            .map(|stat| {
+                // The state follows the parenthesised command name
+                let state = stat.rsplit(')').next().unwrap_or("").trim_start();
+                !state.starts_with('Z')
+            })
+            .unwrap_or(false);
+        if !alive {
+            return false;
+        }
+        std::thread::sleep(std::time::Duration::from_millis(100));
+    }
+    true
+}
+
+#[test]
+fn run_timeout_kills_background_processes() {
+    let dir = TempDir::new().unwrap();
+    setup_pipeline(
+        dir.path(),
+        r#"
+version: 1
+workspace: workspace
+steps:
+  - id: leaky
+    type: bash
+    timeout: 1
+    bash: sleep 100 & echo $! > bg.pid; sleep 10
+"#,
+    );
+
+    let cfg = Config::default();
+    let pd = pipeline_dir(dir.path());
+    runner::run_pipeline(&pd, &cfg, &RunOptions::default()).unwrap_err();
+
+    let pid = fs::read_to_string(pd.join("workspace").join("bg.pid")).unwrap();
+    assert!(
+        !process_alive(pid.trim()),
+        "background sleep survived the timeout"
+    );
+}
+
+#[test]
+fn run_step_exit_kills_leftover_background_processes() {
+    let dir = TempDir::new().unwrap();
+    setup_pipeline(
+        dir.path(),
+        r#"
+version: 1
+workspace: workspace
+steps:
+  - id: leaky
+    type: bash
+    timeout: 30
+    bash: sleep 100 & echo $! > bg.pid
+"#,
+    );
+
+    let cfg = Config::default();
+    let pd = pipeline_dir(dir.path());
+    let start = std::time::Instant::now();
+    runner::run_pipeline(&pd, &cfg, &RunOptions::default()).unwrap();
+
+    // The step finishes as soon as the shell does, not when the sleep would
+    assert!(start.elapsed() < std::time::Duration::from_secs(5));
+    let pid = fs::read_to_string(pd.join("workspace").join("bg.pid")).unwrap();
+    assert!(
+        !process_alive(pid.trim()),
+        "background sleep outlived the step"
+    );
+}
+
+#[test]
+fn run_failed_step_retries_until_budget_exhausted() {
+    let dir = TempDir::new().unwrap();
+    setup_pipeline(
+        dir.path(),
+        r#"
+version: 1
+workspace: workspace
+steps:
+  - id: flaky
+    type: bash
+    bash: exit 1
+    retries: 2
+"#,
+    );
+
+    let cfg = Config::default();
+    let pd = pipeline_dir(dir.path());
+
+    // Attempts 1 and 2 leave the step pending for another try
+    for attempt in 1..=2 {
+        runner::run_pipeline(&pd, &cfg, &RunOptions::default()).unwrap();
+        let s = state::load(&pd.join("state.json")).unwrap().unwrap();
+        assert_eq!(s.steps["flaky"].status, StepStatus::Pending);
+        assert_eq!(s.steps["flaky"].attempts, attempt);
+    }
+
+    // Attempt 3 exhausts the budget
+    runner::run_pipeline(&pd, &cfg, &RunOptions::default()).unwrap_err();
+    let s = state::load(&pd.join("state.json")).unwrap().unwrap();
+    assert_eq!(s.steps["flaky"].status, StepStatus::Failed);
+    assert_eq!(s.steps["flaky"].attempts, 3);
+}
+
+#[test]
+fn run_retry_waits_for_retry_delay() {
+    let dir = TempDir::new().unwrap();
+    setup_pipeline(
+        dir.path(),
+        r#"
+version: 1
+workspace: workspace
+steps:
+  - id: flaky
+    type: bash
+    bash: echo run >> runs.txt && exit 1
+    retries: 1
+    retry_delay_secs: 3600
+"#,
+    );
+
+    let cfg = Config::default();
+    let pd = pipeline_dir(dir.path());
+
+    runner::run_pipeline(&pd, &cfg, &RunOptions::default()).unwrap();
+    // Delay hasn't elapsed, so this tick is a no-op
+    runner::run_pipeline(&pd, &cfg, &RunOptions::default()).unwrap();
+
+    let s = state::load(&pd.join("state.json")).unwrap().unwrap();
+    assert_eq!(s.steps["flaky"].status, StepStatus::Pending);
+    assert_eq!(s.steps["flaky"].attempts, 1);
+    let runs = fs::read_to_string(pd.join("workspace").join("runs.txt")).unwrap();
+    assert_eq!(runs.lines().count(), 1);
+}
+
+#[test]
+fn run_deadline_stops_retries() {
+    let dir = TempDir::new().unwrap();
+    setup_pipeline(
+        dir.path(),
+        r#"
+version: 1
+workspace: workspace
+steps:
+  - id: slow
+    type: bash
+    bash: sleep 2; exit 1
+    retries: 5
+    deadline_secs: 2
+"#,
+    );
+
+    let pd = pipeline_dir(dir.path());
+    let err = runner::run_pipeline(&pd, &Config::default(), &RunOptions::default()).unwrap_err();
+    assert!(
+        err.to_string().contains("deadline of 2s reached"),
+        "{}",
+        err
+    );
+
+    // Retries were left, but the deadline had passed
+    let s = state::load(&pd.join("state.json")).unwrap().unwrap();
+    assert_eq!(s.steps["slow"].status, StepStatus::Failed);
+    assert_eq!(s.steps["slow"].attempts, 1);
+}
+
+#[test]
+fn run_deadline_caps_step_timeout() {
+    let dir = TempDir::new().unwrap();
+    setup_pipeline(
+        dir.path(),
+        r#"
+version: 1
+workspace: workspace
+steps:
+  - id: slow
+    type: bash
+    bash: sleep 10
+    timeout: 60
+    deadline_secs: 1
+"#,
+    );
+
+    let pd = pipeline_dir(dir.path());
+    let err = runner::run_pipeline(&pd, &Config::default(), &RunOptions::default()).unwrap_err();
+    assert!(err.to_string().contains("timed out after 1s"), "{}", err);
+
+    let s = state::load(&pd.join("state.json")).unwrap().unwrap();
+    assert!(s.steps["slow"].first_started_at.is_some());
+}
+
+#[test]
+fn run_appends_step_output_to_log_across_attempts() {
+    let dir = TempDir::new().unwrap();
+    setup_pipeline(
+        dir.path(),
+        r#"
+version: 1
+workspace: workspace
+steps:
+  - id: flaky
+    type: bash
+    bash: echo out; echo err >&2; exit 1
+    output: out.txt
+    retries: 1
+"#,
+    );
+
+    let cfg = Config::default();
+    let pd = pipeline_dir(dir.path());
+    runner::run_pipeline(&pd, &cfg, &RunOptions::default()).unwrap();
+    runner::run_pipeline(&pd, &cfg, &RunOptions::default()).unwrap_err();
+
+    let log = fs::read_to_string(pd.join("logs").join("flaky.log")).unwrap();
+    assert!(log.contains("started (attempt 1)"));
+    assert!(log.contains("started (attempt 2)"));
+    assert_eq!(log.matches("stdout:\nout\n").count(), 2);
+    assert_eq!(log.matches("stderr:\nerr\n").count(), 2);
+    assert_eq!(log.matches("failed: exited with code 1").count(), 2);
+
+    // The step's own output routing is unaffected
+    let out = fs::read_to_string(pd.join("workspace").join("out.txt")).unwrap();
+    assert_eq!(out, "out\n");
+}
+
+#[test]
+fn run_failed_step_does_not_promote_outputs() {
+    let dir = TempDir::new().unwrap();
+    setup_pipeline(
+        dir.path(),
+        r#"
+version: 1
+workspace: workspace
+steps:
+  - id: fail
+    type: bash
+    bash: echo "data" > out.txt.tmp && exit 1
+    outputs:
+      - name: out
+        path: out.txt
+        tmp: out.txt.tmp
+"#,
+    );
+
+    let cfg = Config::default();
+    let pd = pipeline_dir(dir.path());
+    let workspace = pd.join("workspace");
+
+    let _ = runner::run_pipeline(&pd, &cfg, &RunOptions::default());
+
+    // tmp should still exist (not promoted)
+    assert!(workspace.join("out.txt.tmp").exists());
+    // final should NOT exist
+    assert!(!workspace.join("out.txt").exists());
+}
+
+#[test]
+fn run_clears_stale_tmp_instead_of_promoting_it() {
+    let dir = TempDir::new().unwrap();
+    setup_pipeline(
+        dir.path(),
+        r#"
+version: 1
+workspace: workspace
+steps:
+  - id: forgetful
+    type: bash
+    bash: "true"
+    outputs:
+      - name: out
+        path: out.txt
+        tmp: out.txt.tmp
+"#,
+    );
+
+    let cfg = Config::default();
+    let pd = pipeline_dir(dir.path());
+    let workspace = pd.join("workspace");
+    // Left behind by a run that crashed before promoting it
+    fs::create_dir_all(&workspace).unwrap();
+    fs::write(workspace.join("out.txt.tmp"), "stale").unwrap();
+
+    let err = runner::run_pipeline(&pd, &cfg, &RunOptions::default()).unwrap_err();
+    assert!(
+        err.to_string().contains("not found after step completed"),
+        "{}",
+        err.to_string()
+    );
+    assert!(!workspace.join("out.txt").exists());
+    assert!(!workspace.join("out.txt.tmp").exists());
+
+    // The step failed rather than being left running
+    let s = state::load(&pd.join("state.json")).unwrap().unwrap();
+    assert_eq!(s.steps["forgetful"].status, StepStatus::Failed);
+}
+
+#[test]
+fn run_promotes_output_from_stdout() {
+    let dir = TempDir::new().unwrap();
+    setup_pipeline(
+        dir.path(),
+        r#"
+version: 1
+workspace: workspace
+steps:
+  - id: fetch
+    type: bash
+    bash: echo price=42
+    outputs:
+      - name: data
+        path: data.json
+        from: stdout
+  - id: use
+    type: bash
+    bash: cat
+    stdin: "got {{ step:fetch }}"
+    output: used.txt
+"#,
+    );
+
+    let cfg = Config::default();
+    let pd = pipeline_dir(dir.path());
+    let workspace = pd.join("workspace");
+
+    runner::run_pipeline(&pd, &cfg, &RunOptions::default()).unwrap();
+    assert_eq!(
+        fs::read_to_string(workspace.join("data.json")).unwrap(),
+        "price=42\n"
+    );
+    assert!(!workspace.join("data.json.tmp").exists());
+    let s = state::load(&pd.join("state.json")).unwrap().unwrap();
+    assert!(s.steps["fetch"].output_hashes.contains_key("data"));
+
+    // {{ step:fetch }} reads the promoted output
+    runner::run_pipeline(&pd, &cfg, &RunOptions::default()).unwrap();
+    assert_eq!(
+        fs::read_to_string(workspace.join("used.txt")).unwrap(),
+        "got price=42\n"
+    );
+}
+
+#[test]
+fn run_records_history_once_pipeline_completes() {
+    let dir = TempDir::new().unwrap();
+    setup_pipeline(
+        dir.path(),
+        r#"
+version: 1
+workspace: workspace
+steps:
+  - id: a
+    type: bash
+    bash: echo a
+  - id: b
+    type: bash
+    bash: echo b
+"#,
+    );
+
+    let cfg = Config::default();
+    let pd = pipeline_dir(dir.path());
+
+    runner::run_pipeline(&pd, &cfg, &RunOptions::default()).unwrap();
+    assert!(history::load(&pd).unwrap().is_empty());
+
+    runner::run_pipeline(&pd, &cfg, &RunOptions::default()).unwrap();
+    let runs = history::load(&pd).unwrap();
+    assert_eq!(runs.len(), 1);
+    assert!(runs[0].started_at.is_some());
+    let ids: Vec<_> = runs[0].steps.iter().map(|s| s.id.as_str()).collect();
+    assert_eq!(ids, ["a", "b"]);
+    assert!(
+        runs[0]
+            .steps
+            .iter()
+            .all(|s| s.status == StepStatus::Completed)
+    );
+
+    // Nothing left to run, so nothing more is recorded
+    runner::run_pipeline(&pd, &cfg, &RunOptions::default()).unwrap();
+    assert_eq!(history::load(&pd).unwrap().len(), 1);
+}
+
+#[test]
+fn run_skips_paused_pipeline_without_touching_steps() {
+    let dir = TempDir::new().unwrap();
+    setup_pipeline(
+        dir.path(),
+        r#"
+version: 1
+workspace: workspace
+schedule: "0 3 * * *"
+steps:
+  - id: hello
+    type: bash
+    bash: echo hi
+"#,
+    );
+    let cfg = Config::default();
+    let pd = pipeline_dir(dir.path());
+
+    assert!(runner::set_paused(&pd, true).unwrap());
+    assert!(!runner::set_paused(&pd, true).unwrap());
+    runner::run_pipeline(&pd, &cfg, &RunOptions::default()).unwrap();
+    // Nothing is recorded or created while paused, schedule slot included
+    assert!(state::load(&pd.join("state.json")).unwrap().is_none());
+    assert!(!pd.join("workspace").exists());
+
+    assert!(runner::set_paused(&pd, false).unwrap());
+    assert!(!runner::is_paused(&pd));
+    runner::run_pipeline(&pd, &cfg, &RunOptions::default()).unwrap();
+    let s = state::load(&pd.join("state.json")).unwrap().unwrap();
+    assert_eq!(s.steps["hello"].status, StepStatus::Completed);
+}
+
+#[test]
+fn run_isolated_workspace_starts_each_run_clean() {
+    let dir = TempDir::new().unwrap();
+    setup_pipeline(
+        dir.path(),
+        r#"
+version: 1
+workspace: workspace
+workspace_mode: isolated
+steps:
+  - id: mark
+    type: bash
+    bash: test ! -e seen.txt && echo fresh > seen.txt
+    output: mark.txt
+  - id: read
+    type: bash
+    bash: cat
+    stdin: "{{ step:mark }}"
+    output: read.txt
+"#,
+    );
+    let cfg = Config::default();
+    let pd = pipeline_dir(dir.path());
+    let workspace = pd.join("workspace");
+
+    let mut runs = Vec::new();
+    for _ in 0..2 {
+        runner::run_pipeline(&pd, &cfg, &RunOptions::default()).unwrap();
+        runner::run_pipeline(&pd, &cfg, &RunOptions::default()).unwrap();
+        let s = state::load(&pd.join("state.json")).unwrap().unwrap();
+        assert_eq!(s.steps["read"].status, StepStatus::Completed);
+        let run = s.run_workspace.unwrap();
+        assert!(workspace.join(&run).join("seen.txt").exists());
+        assert_eq!(
+            fs::read_link(workspace.join("latest")).unwrap(),
+            std::path::Path::new(&run)
+        );
+        runs.push(run);
+        // A full reset starts a new run
+        fs::remove_file(pd.join("state.json")).unwrap();
+    }
+
+    // The first run's files are kept
+    assert_ne!(runs[0], runs[1]);
+    assert!(workspace.join(&runs[0]).join("seen.txt").exists());
+    assert!(!workspace.join("seen.txt").exists());
+}
+
+#[test]
+fn run_state_mismatch_errors() {
+    let dir = TempDir::new().unwrap();
+    setup_pipeline(
+        dir.path(),
+        r#"
+version: 1
+workspace: workspace
+steps:
+  - id: step-a
+    type: bash
+    bash: echo a
+"#,
+    );
+
+    let cfg = Config::default();
+    let pd = pipeline_dir(dir.path());
+
+    // Run once to create state
+    runner::run_pipeline(&pd, &cfg, &RunOptions::default()).unwrap();
+
+    // Change pipeline to have different steps
+    fs::write(
+        pd.join("pipeline.yaml"),
+        r#"
+version: 1
+workspace: workspace
+steps:
+  - id: step-b
+    type: bash
+    bash: echo b
+"#,
+    )
+    .unwrap();
+
+    let result = runner::run_pipeline(&pd, &cfg, &RunOptions::default());
+    assert!(result.is_err());
+    let err = result.unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::Invalid);
+    assert!(matches!(err, CronclawError::StateMismatch(_)), "{:?}", err);
+    assert!(err.to_string().contains("mismatch"));
+    assert!(
+        err.to_string().contains("(added step-b; removed step-a)"),
+        "{}",
+        err
+    );
+    assert!(err.to_string().contains("reset"));
+}
+
+#[test]
+fn run_running_step_causes_early_exit() {
+    let dir = TempDir::new().unwrap();
+    setup_pipeline(
+        dir.path(),
+        r#"
+version: 1
+workspace: workspace
+steps:
+  - id: stuck
+    type: bash
+    bash: echo hi
+  - id: next
+    type: bash
+    bash: echo next
+"#,
+    );
+
+    let cfg = Config::default();
+    let pd = pipeline_dir(dir.path());
+
+    // Create state with 'stuck' as running (simulating a crashed previous run)
+    let p = pipeline::parse(&fs::read_to_string(pd.join("pipeline.yaml")).unwrap()).unwrap();
+    let mut s = State::from_pipeline(&p);
+    s.steps.get_mut("stuck").unwrap().status = StepStatus::Running;
+    fs::create_dir_all(pd.join("workspace")).unwrap();
+    state::save(&pd.join("state.json"), &s).unwrap();
+
+    // Tick should see 'running' and exit without error, without touching 'next'
+    let ticked = runner::run_pipeline(&pd, &cfg, &RunOptions::default()).unwrap();
+    assert_eq!(ticked, Ticked::Busy);
+
+    let s = state::load(&pd.join("state.json")).unwrap().unwrap();
+    assert_eq!(s.steps["stuck"].status, StepStatus::Running);
+    assert_eq!(s.steps["next"].status, StepStatus::Pending);
+}
+
+#[test]
+fn run_needs_picks_earliest_ready_step() {
+    let dir = TempDir::new().unwrap();
+    setup_pipeline(
+        dir.path(),
+        r#"
+version: 1
+workspace: workspace
+steps:
+  - id: report
+    type: bash
+    bash: echo report
+    needs: [fetch]
+  - id: fetch
+    type: bash
+    bash: echo fetch
+    needs: []
+"#,
+    );
+
+    let cfg = Config::default();
+    let pd = pipeline_dir(dir.path());
+
+    // Tick 1 — 'report' is declared first but waits for 'fetch'
+    runner::run_pipeline(&pd, &cfg, &RunOptions::default()).unwrap();
+    let s = state::load(&pd.join("state.json")).unwrap().unwrap();
+    assert_eq!(s.steps["fetch"].status, StepStatus::Completed);
+    assert_eq!(s.steps["report"].status, StepStatus::Pending);
+
+    // Tick 2
+    runner::run_pipeline(&pd, &cfg, &RunOptions::default()).unwrap();
+    let s = state::load(&pd.join("state.json")).unwrap().unwrap();
+    assert_eq!(s.steps["report"].status, StepStatus::Completed);
+}
+
+#[test]
+fn run_needs_fan_in_waits_for_all_branches() {
+    let dir = TempDir::new().unwrap();
+    setup_pipeline(
+        dir.path(),
+        r#"
+version: 1
+workspace: workspace
+steps:
+  - id: left
+    type: bash
+    bash: echo left > left.txt
+    needs: []
+  - id: right
+    type: bash
+    bash: echo right > right.txt
+    needs: []
+  - id: join
+    type: bash
+    bash: cat left.txt right.txt > joined.txt
+    needs: [left, right]
+"#,
+    );
+
+    let cfg = Config::default();
+    let pd = pipeline_dir(dir.path());
+
+    runner::run_pipeline(&pd, &cfg, &RunOptions::default()).unwrap();
+    runner::run_pipeline(&pd, &cfg, &RunOptions::default()).unwrap();
+    let s = state::load(&pd.join("state.json")).unwrap().unwrap();
+    assert_eq!(s.steps["left"].status, StepStatus::Completed);
+    assert_eq!(s.steps["right"].status, StepStatus::Completed);
+    assert_eq!(s.steps["join"].status, StepStatus::Pending);
+
+    runner::run_pipeline(&pd, &cfg, &RunOptions::default()).unwrap();
+    let joined = fs::read_to_string(pd.join("workspace").join("joined.txt")).unwrap();
+    assert_eq!(joined, "left\nright\n");
+}
+
+#[test]
+fn run_noop_step_joins_branches() {
+    let dir = TempDir::new().unwrap();
+    setup_pipeline(
+        dir.path(),
+        r#"
+version: 1
+workspace: workspace
+steps:
+  - id: left
+    type: bash
+    bash: echo left > left.txt
+    needs: []
+  - id: right
+    type: bash
+    bash: echo right > right.txt
+    needs: []
+  - id: both
+    type: noop
+    needs: [left, right]
+  - id: join
+    type: bash
+    bash: cat left.txt right.txt > joined.txt
+    needs: [both]
+"#,
+    );
+
+    let cfg = Config::default();
+    let pd = pipeline_dir(dir.path());
+
+    runner::run_pipeline(&pd, &cfg, &RunOptions::default()).unwrap();
+    runner::run_pipeline(&pd, &cfg, &RunOptions::default()).unwrap();
+    let s = state::load(&pd.join("state.json")).unwrap().unwrap();
+    assert_eq!(s.steps["both"].status, StepStatus::Pending);
+
+    runner::run_pipeline(&pd, &cfg, &RunOptions::default()).unwrap();
+    let s = state::load(&pd.join("state.json")).unwrap().unwrap();
+    assert_eq!(s.steps["both"].status, StepStatus::Completed);
+    assert_eq!(s.steps["join"].status, StepStatus::Pending);
+
+    runner::run_pipeline(&pd, &cfg, &RunOptions::default()).unwrap();
+    let joined = fs::read_to_string(pd.join("workspace").join("joined.txt")).unwrap();
+    assert_eq!(joined, "left\nright\n");
+}
+
+#[test]
+fn run_when_true_runs_step() {
+    let dir = TempDir::new().unwrap();
+    setup_pipeline(
+        dir.path(),
+        r#"
+version: 1
+workspace: workspace
+steps:
+  - id: gated
+    type: bash
+    bash: touch ran.txt
+    when: "true"
+"#,
+    );
+
+    let pd = pipeline_dir(dir.path());
+    runner::run_pipeline(&pd, &Config::default(), &RunOptions::default()).unwrap();
+
+    let s = state::load(&pd.join("state.json")).unwrap().unwrap();
+    assert_eq!(s.steps["gated"].status, StepStatus::Completed);
+    assert!(pd.join("workspace").join("ran.txt").exists());
+}
+
+#[test]
+fn run_when_false_skips_step_and_runs_next_in_same_tick() {
+    let dir = TempDir::new().unwrap();
+    setup_pipeline(
+        dir.path(),
+        r#"
+version: 1
+workspace: workspace
+env:
+  MODE: quiet
+steps:
+  - id: gated
+    type: bash
+    bash: touch ran.txt
+    when: test "$MODE" = loud
+  - id: after
+    type: bash
+    bash: touch after.txt
+"#,
+    );
+
+    let pd = pipeline_dir(dir.path());
+    runner::run_pipeline(&pd, &Config::default(), &RunOptions::default()).unwrap();
+
+    let s = state::load(&pd.join("state.json")).unwrap().unwrap();
+    assert_eq!(s.steps["gated"].status, StepStatus::Skipped);
+    assert_eq!(s.steps["after"].status, StepStatus::Completed);
+    assert!(!pd.join("workspace").join("ran.txt").exists());
+    assert!(pd.join("workspace").join("after.txt").exists());
+}
+
+#[test]
+fn run_skipped_step_satisfies_needs() {
+    let dir = TempDir::new().unwrap();
+    setup_pipeline(
+        dir.path(),
+        r#"
+version: 1
+workspace: workspace
+steps:
+  - id: optional
+    type: bash
+    bash: echo optional
+    when: "false"
+  - id: other
+    type: bash
+    bash: echo other
+    needs: []
+  - id: join
+    type: bash
+    bash: touch joined.txt
+    needs: [optional, other]
+"#,
+    );
+
+    let cfg = Config::default();
+    let pd = pipeline_dir(dir.path());
+
+    // Tick 1 skips 'optional' and runs 'other'; tick 2 runs 'join'
+    runner::run_pipeline(&pd, &cfg, &RunOptions::default()).unwrap();
+    runner::run_pipeline(&pd, &cfg, &RunOptions::default()).unwrap();
+
+    let s = state::load(&pd.join("state.json")).unwrap().unwrap();
+    assert_eq!(s.steps["optional"].status, StepStatus::Skipped);
+    assert_eq!(s.steps["join"].status, StepStatus::Completed);
+    assert!(pd.join("workspace").join("joined.txt").exists());
+}
+
+const FAN_OUT_PIPELINE: &str = r#"
+version: 1
+workspace: workspace
+steps:
+  - id: a
+    type: bash
+    bash: sleep 1; echo a > a.txt
+    needs: []
+  - id: b
+    type: bash
+    bash: sleep 1; echo b > b.txt
+    needs: []
+  - id: c
+    type: bash
+    bash: sleep 1; echo c > c.txt
+    needs: []
+  - id: join
+    type: bash
+    bash: cat a.txt b.txt c.txt > joined.txt
+    needs: [a, b, c]
+"#;
+
+#[test]
+fn run_parallel_runs_all_ready_steps_in_one_tick() {
+    let dir = TempDir::new().unwrap();
+    setup_pipeline(dir.path(), FAN_OUT_PIPELINE);
+
+    let cfg = Config {
+        max_parallel: 4,
+        ..Config::default()
+    };
+    let pd = pipeline_dir(dir.path());
+
+    let start = std::time::Instant::now();
+    runner::run_pipeline(&pd, &cfg, &RunOptions::default()).unwrap();
+    assert!(start.elapsed() < std::time::Duration::from_millis(2500));
+
+    // Every branch's result was recorded, none overwritten by another
+    let s = state::load(&pd.join("state.json")).unwrap().unwrap();
+    for id in ["a", "b", "c"] {
+        assert_eq!(s.steps[id].status, StepStatus::Completed, "step {}", id);
+    }
+    assert_eq!(s.steps["join"].status, StepStatus::Pending);
+
+    runner::run_pipeline(&pd, &cfg, &RunOptions::default()).unwrap();
+    let joined = fs::read_to_string(pd.join("workspace").join("joined.txt")).unwrap();
+    assert_eq!(joined, "a\nb\nc\n");
+}
+
+#[test]
+fn run_parallel_respects_max_parallel() {
+    let dir = TempDir::new().unwrap();
+    setup_pipeline(dir.path(), FAN_OUT_PIPELINE);
+
+    let cfg = Config {
+        max_parallel: 2,
+        ..Config::default()
+    };
+    let pd = pipeline_dir(dir.path());
+    runner::run_pipeline(&pd, &cfg, &RunOptions::default()).unwrap();
+
+    let s = state::load(&pd.join("state.json")).unwrap().unwrap();
+    assert_eq!(s.steps["a"].status, StepStatus::Completed);
+    assert_eq!(s.steps["b"].status, StepStatus::Completed);
+    assert_eq!(s.steps["c"].status, StepStatus::Pending);
+}
+
+#[test]
+fn run_parallel_reports_each_failure() {
+    let dir = TempDir::new().unwrap();
+    setup_pipeline(
+        dir.path(),
+        r#"
+version: 1
+workspace: workspace
+steps:
+  - id: ok
+    type: bash
+    bash: "true"
+    needs: []
+  - id: bad1
+    type: bash
+    bash: exit 1
+    needs: []
+  - id: bad2
+    type: bash
+    bash: exit 2
+    needs: []
+"#,
+    );
+
+    let cfg = Config {
+        max_parallel: 3,
+        ..Config::default()
+    };
+    let pd = pipeline_dir(dir.path());
+    let err = runner::run_pipeline(&pd, &cfg, &RunOptions::default()).unwrap_err();
+    assert!(err.to_string().contains("'bad1' failed"), "got: {}", err);
+    assert!(err.to_string().contains("'bad2' failed"), "got: {}", err);
+
+    let s = state::load(&pd.join("state.json")).unwrap().unwrap();
+    assert_eq!(s.steps["ok"].status, StepStatus::Completed);
+    assert_eq!(s.steps["bad1"].exit_code, Some(1));
+    assert_eq!(s.steps["bad2"].exit_code, Some(2));
+}
+
+#[test]
+fn next_step_follows_needs_and_blocking_states() {
+    let p = pipeline::parse(
+        r#"
+version: 1
+workspace: workspace
+steps:
+  - id: a
+    type: bash
+    bash: echo a
+  - id: b
+    type: bash
+    bash: echo b
+    needs: [a]
+  - id: c
+    type: bash
+    bash: echo c
+    needs: []
+"#,
+    )
+    .unwrap();
+    let mut s = State::from_pipeline(&p);
+    assert_eq!(runner::next_step(&p, &s).unwrap().id, "a");
+
+    s.steps.get_mut("a").unwrap().status = StepStatus::Completed;
+    assert_eq!(runner::next_step(&p, &s).unwrap().id, "b");
+
+    s.steps.get_mut("b").unwrap().status = StepStatus::Failed;
+    assert!(runner::next_step(&p, &s).is_none());
+
+    s.steps.get_mut("b").unwrap().status = StepStatus::Skipped;
+    assert_eq!(runner::next_step(&p, &s).unwrap().id, "c");
+
+    s.steps.get_mut("c").unwrap().status = StepStatus::Completed;
+    assert!(runner::next_step(&p, &s).is_none());
+}
+
+#[test]
+fn run_bash_step_uses_configured_shell() {
+    let dir = TempDir::new().unwrap();
+    setup_pipeline(
+        dir.path(),
+        r#"
+version: 1
+workspace: workspace
+steps:
+  - id: which
+    type: bash
+    bash: echo "$0"
+    output: shell.txt
+"#,
+    );
+
+    let cfg = Config {
+        shell: "bash".to_string(),
+        ..Config::default()
+    };
+    let pd = pipeline_dir(dir.path());
+    runner::run_pipeline(&pd, &cfg, &RunOptions::default()).unwrap();
+
+    let shell = fs::read_to_string(pd.join("workspace").join("shell.txt")).unwrap();
+    assert_eq!(shell.trim(), "bash");
+}
+
+#[test]
+fn run_step_shell_overrides_config() {
+    let dir = TempDir::new().unwrap();
+    setup_pipeline(
+        dir.path(),
+        r#"
+version: 1
+workspace: workspace
+steps:
+  - id: which
+    type: bash
+    shell: bash
+    bash: arr=(a b c); echo "${#arr[@]}"
+    output: count.txt
+"#,
+    );
+
+    let cfg = Config::default();
+    let pd = pipeline_dir(dir.path());
+    runner::run_pipeline(&pd, &cfg, &RunOptions::default()).unwrap();
+
+    let count = fs::read_to_string(pd.join("workspace").join("count.txt")).unwrap();
+    assert_eq!(count.trim(), "3");
+}
+
+#[test]
+fn run_step_receives_merged_env() {
+    let dir = TempDir::new().unwrap();
+    setup_pipeline(
+        dir.path(),
+        r#"
+version: 1
+workspace: workspace
+env:
+  GREETING: hello
+  TARGET: world
+steps:
+  - id: greet
+    type: bash
+    bash: echo "$GREETING $TARGET $TOKEN"
+    output: greeting.txt
+    env:
+      TARGET: cron
+      TOKEN: "{{ file:token.txt }}"
+"#,
+    );
+
+    let pd = pipeline_dir(dir.path());
+    let workspace = pd.join("workspace");
+    fs::create_dir_all(&workspace).unwrap();
+    fs::write(workspace.join("token.txt"), "s3cret").unwrap();
+
+    let cfg = Config::default();
+    runner::run_pipeline(&pd, &cfg, &RunOptions::default()).unwrap();
+
+    let greeting = fs::read_to_string(workspace.join("greeting.txt")).unwrap();
+    assert_eq!(greeting.trim(), "hello cron s3cret");
+}
+
+#[test]
+fn run_env_template_error_fails_step() {
+    let dir = TempDir::new().unwrap();
+    setup_pipeline(
+        dir.path(),
+        r#"
+version: 1
+workspace: workspace
+steps:
+  - id: greet
+    type: bash
+    bash: echo "$TOKEN"
+    env:
+      TOKEN: "{{ file:missing.txt }}"
+"#,
+    );
+
+    let cfg = Config::default();
+    let pd = pipeline_dir(dir.path());
+    let err = runner::run_pipeline(&pd, &cfg, &RunOptions::default()).unwrap_err();
+    assert!(err.to_string().contains("TOKEN"));
+    assert!(err.to_string().contains("missing.txt"));
+}
+
+#[test]
+fn run_python_step_in_workspace() {
+    let dir = TempDir::new().unwrap();
+    setup_pipeline(
+        dir.path(),
+        r#"
+version: 1
+workspace: workspace
+steps:
+  - id: crunch
+    type: python
+    python: |
+      total = sum(range(1, 5))
+      with open("total.txt", "w") as f:
+          f.write(str(total))
+"#,
+    );
+
+    let cfg = Config::default();
+    let pd = pipeline_dir(dir.path());
+    runner::run_pipeline(&pd, &cfg, &RunOptions::default()).unwrap();
+
+    let s = state::load(&pd.join("state.json")).unwrap().unwrap();
+    assert_eq!(s.steps["crunch"].status, StepStatus::Completed);
+    let total = fs::read_to_string(pd.join("workspace").join("total.txt")).unwrap();
+    assert_eq!(total, "10");
+}
+
+#[test]
+fn run_python_step_resolves_templates() {
+    let dir = TempDir::new().unwrap();
+    setup_pipeline(
+        dir.path(),
+        r#"
+version: 1
+workspace: workspace
+steps:
+  - id: echo
+    type: python
+    python: |
+      print("{{ file:name.txt }}".upper())
+    output: shout.txt
+"#,
+    );
+
+    let pd = pipeline_dir(dir.path());
+    let workspace = pd.join("workspace");
+    fs::create_dir_all(&workspace).unwrap();
+    fs::write(workspace.join("name.txt"), "cronclaw").unwrap();
+
+    let cfg = Config::default();
+    runner::run_pipeline(&pd, &cfg, &RunOptions::default()).unwrap();
+
+    let shout = fs::read_to_string(workspace.join("shout.txt")).unwrap();
+    assert_eq!(shout, "CRONCLAW\n");
+}
+
+#[test]
+fn run_python_step_failure_marks_failed() {
+    let dir = TempDir::new().unwrap();
+    setup_pipeline(
+        dir.path(),
+        r#"
+version: 1
+workspace: workspace
+steps:
+  - id: boom
+    type: python
+    python: |
+      import sys
+      sys.exit(4)
+"#,
+    );
+
+    let cfg = Config::default();
+    let pd = pipeline_dir(dir.path());
+    runner::run_pipeline(&pd, &cfg, &RunOptions::default()).unwrap_err();
+
+    let s = state::load(&pd.join("state.json")).unwrap().unwrap();
+    assert_eq!(s.steps["boom"].status, StepStatus::Failed);
+    assert_eq!(s.steps["boom"].exit_code, Some(4));
+}
+
+#[test]
+fn run_slow_pipeline_does_not_block_other_pipelines() {
+    let dir = TempDir::new().unwrap();
+    let slow = dir.path().join("pipelines").join("slow");
+    let fast = dir.path().join("pipelines").join("fast");
+    for (pd, bash) in [(&slow, "sleep 2"), (&fast, "echo fast")] {
+        fs::create_dir_all(pd).unwrap();
+        fs::write(
+            pd.join("pipeline.yaml"),
+            format!(
+                "version: 1\nworkspace: workspace\nsteps:\n  - id: only\n    type: bash\n    bash: {}\n",
+                bash
+            ),
+        )
+        .unwrap();
+    }
+
+    let slow_dir = slow.clone();
+    let handle = std::thread::spawn(move || {
+        runner::run_pipeline(&slow_dir, &Config::default(), &RunOptions::default()).unwrap();
+    });
+
+    // Wait for the slow step to be claimed
+    let state_file = slow.join("state.json");
+    let is_running = || {
+        state::load(&state_file)
+            .ok()
+            .flatten()
+            .is_some_and(|s| s.steps["only"].status == StepStatus::Running)
+    };
+    while !is_running() {
+        std::thread::sleep(std::time::Duration::from_millis(20));
+    }
+
+    // Another pipeline advances while the slow step is still running...
+    runner::run_pipeline(&fast, &Config::default(), &RunOptions::default()).unwrap();
+    let s = state::load(&fast.join("state.json")).unwrap().unwrap();
+    assert_eq!(s.steps["only"].status, StepStatus::Completed);
+
+    // ...and a second tick of the slow pipeline doesn't run its step again
+    runner::run_pipeline(&slow, &Config::default(), &RunOptions::default()).unwrap();
+    let s = state::load(&state_file).unwrap().unwrap();
+    assert_eq!(s.steps["only"].status, StepStatus::Running);
+
+    handle.join().unwrap();
+    let s = state::load(&state_file).unwrap().unwrap();
+    assert_eq!(s.steps["only"].status, StepStatus::Completed);
+}
+
+/// Leave `stuck` marked running as if claimed by `pid`, `started_secs_ago` seconds ago.
+fn setup_stuck_step(dir: &std::path::Path, pid: u32, started_secs_ago: i64) -> std::path::PathBuf {
+    setup_pipeline(
+        dir,
+        r#"
+version: 1
+workspace: workspace
+steps:
+  - id: stuck
+    type: bash
+    timeout: 10
+    bash: echo recovered > recovered.txt
+"#,
+    );
+    let pd = pipeline_dir(dir);
+    let p = pipeline::parse(&fs::read_to_string(pd.join("pipeline.yaml")).unwrap()).unwrap();
+    let mut s = State::from_pipeline(&p);
+    let started = chrono::Utc::now() - chrono::Duration::seconds(started_secs_ago);
+    let step = s.steps.get_mut("stuck").unwrap();
+    step.status = StepStatus::Running;
+    step.pid = Some(pid);
+    step.started_at = Some(started.to_rfc3339_opts(chrono::SecondsFormat::Secs, true));
+    fs::create_dir_all(pd.join("workspace")).unwrap();
+    state::save(&pd.join("state.json"), &s).unwrap();
+    pd
+}
+
+/// A pid that belonged to a process which has since exited.
+fn dead_pid() -> u32 {
+    let mut child = std::process::Command::new("true").spawn().unwrap();
+    let pid = child.id();
+    child.wait().unwrap();
+    pid
+}
+
+#[test]
+fn run_stale_step_left_alone_by_default() {
+    let dir = TempDir::new().unwrap();
+    let pd = setup_stuck_step(dir.path(), dead_pid(), 0);
+
+    runner::run_pipeline(&pd, &Config::default(), &RunOptions::default()).unwrap();
+
+    let s = state::load(&pd.join("state.json")).unwrap().unwrap();
+    assert_eq!(s.steps["stuck"].status, StepStatus::Running);
+}
+
+#[test]
+fn run_reclaims_step_whose_runner_died() {
+    let dir = TempDir::new().unwrap();
+    let pd = setup_stuck_step(dir.path(), dead_pid(), 0);
+
+    let cfg = Config {
+        reclaim_stale: true,
+        ..Config::default()
+    };
+    runner::run_pipeline(&pd, &cfg, &RunOptions::default()).unwrap();
+
+    let s = state::load(&pd.join("state.json")).unwrap().unwrap();
+    assert_eq!(s.steps["stuck"].status, StepStatus::Completed);
+    assert!(pd.join("workspace").join("recovered.txt").exists());
+}
+
+#[test]
+fn run_reclaims_step_running_far_past_timeout() {
+    let dir = TempDir::new().unwrap();
+    // Our own pid is alive, but the step started long before its timeout allows
+    let pd = setup_stuck_step(dir.path(), std::process::id(), 3600);
+
+    let cfg = Config {
+        reclaim_stale: true,
+        ..Config::default()
+    };
+    runner::run_pipeline(&pd, &cfg, &RunOptions::default()).unwrap();
+
+    let s = state::load(&pd.join("state.json")).unwrap().unwrap();
+    assert_eq!(s.steps["stuck"].status, StepStatus::Completed);
+}
+
+#[test]
+fn run_does_not_reclaim_live_step_within_timeout() {
+    let dir = TempDir::new().unwrap();
+    let pd = setup_stuck_step(dir.path(), std::process::id(), 0);
+
+    let cfg = Config {
+        reclaim_stale: true,
+        ..Config::default()
+    };
+    runner::run_pipeline(&pd, &cfg, &RunOptions::default()).unwrap();
+
+    let s = state::load(&pd.join("state.json")).unwrap().unwrap();
+    assert_eq!(s.steps["stuck"].status, StepStatus::Running);
+}
+
+#[test]
+fn run_step_in_working_dir() {
+    let dir = TempDir::new().unwrap();
+    setup_pipeline(
+        dir.path(),
+        r#"
+version: 1
+workspace: workspace
+steps:
+  - id: build
+    type: bash
+    bash: touch built && pwd
+    working_dir: src/app
+    output: pwd.txt
+"#,
+    );
+
+    let pd = pipeline_dir(dir.path());
+    runner::run_pipeline(&pd, &Config::default(), &RunOptions::default()).unwrap();
+
+    // The step runs in (and creates) its working_dir, but output files are
+    // still relative to the workspace
+    let workspace = pd.join("workspace");
+    assert!(workspace.join("src/app/built").exists());
+    let pwd = fs::read_to_string(workspace.join("pwd.txt")).unwrap();
+    assert!(pwd.trim().ends_with("workspace/src/app"), "got: {}", pwd);
+}
+
+#[test]
+fn run_rejects_output_file_outside_workspace() {
+    let dir = TempDir::new().unwrap();
+    setup_pipeline(
+        dir.path(),
+        r#"
+version: 1
+workspace: workspace
+steps:
+  - id: escape
+    type: bash
+    bash: echo hi
+    output: up/escaped.txt
+"#,
+    );
+
+    let pd = pipeline_dir(dir.path());
+    // `..` is rejected when parsing, so escape through a symlink instead
+    fs::create_dir_all(pd.join("workspace")).unwrap();
+    std::os::unix::fs::symlink(&pd, pd.join("workspace/up")).unwrap();
+    let err = runner::run_pipeline(&pd, &Config::default(), &RunOptions::default()).unwrap_err();
+    assert!(
+        err.to_string().contains("outside the workspace"),
+        "got: {}",
+        err
+    );
+    assert!(!pd.join("escaped.txt").exists());
+}
+
+#[test]
+fn run_step_truncates_large_output() {
+    let dir = TempDir::new().unwrap();
+    setup_pipeline(
+        dir.path(),
+        r#"
+version: 1
+workspace: workspace
+steps:
+  - id: noisy
+    type: bash
+    bash: yes | head -c 1000000
+    output: out.txt
+"#,
+    );
+
+    let cfg = Config {
+        max_output_bytes: 1000,
+        ..Config::default()
+    };
+    let pd = pipeline_dir(dir.path());
+    runner::run_pipeline(&pd, &cfg, &RunOptions::default()).unwrap();
+
+    let out = fs::read_to_string(pd.join("workspace/out.txt")).unwrap();
+    assert!(out.starts_with("y\ny\n"));
+    assert!(out.ends_with("\n[output truncated]\n"), "got: {}", out);
+    assert_eq!(out.len(), 1000 + "\n[output truncated]\n".len());
+
+    // The step still ran to completion
+    let s = state::load(&pd.join("state.json")).unwrap().unwrap();
+    assert_eq!(s.steps["noisy"].status, StepStatus::Completed);
+}
+
+#[test]
+fn run_step_keeps_full_output_written_to_an_artifact() {
+    let dir = TempDir::new().unwrap();
+    setup_pipeline(
+        dir.path(),
+        r#"
+version: 1
+workspace: workspace
+steps:
+  - id: produce
+    type: bash
+    bash: yes | head -c 100000
+    output: data.txt.tmp
+    outputs:
+      - name: data
+        path: data.txt
+        tmp: data.txt.tmp
+"#,
+    );
+
+    let cfg = Config {
+        max_output_bytes: 1000,
+        ..Config::default()
+    };
+    let pd = pipeline_dir(dir.path());
+    runner::run_pipeline(&pd, &cfg, &RunOptions::default()).unwrap();
+
+    let data = fs::read(pd.join("workspace/data.txt")).unwrap();
+    assert_eq!(data.len(), 100000);
+}
+
+#[test]
+fn run_step_reads_stdin() {
+    let dir = TempDir::new().unwrap();
+    setup_pipeline(
+        dir.path(),
+        r#"
+version: 1
+workspace: workspace
+steps:
+  - id: text
+    type: bash
+    bash: tr a-z A-Z
+    stdin: "hello {{ file:name.txt }}"
+    output: text.txt
+  - id: file
+    type: python
+    python: |
+      import sys
+      print(len(sys.stdin.read()))
+    stdin_file: name.txt
+    output: count.txt
+"#,
+    );
+
+    let pd = pipeline_dir(dir.path());
+    fs::create_dir_all(pd.join("workspace")).unwrap();
+    fs::write(pd.join("workspace/name.txt"), "world").unwrap();
+
+    let cfg = Config::default();
+    runner::run_pipeline(&pd, &cfg, &RunOptions::default()).unwrap();
+    runner::run_pipeline(&pd, &cfg, &RunOptions::default()).unwrap();
+
+    let text = fs::read_to_string(pd.join("workspace/text.txt")).unwrap();
+    assert_eq!(text, "HELLO WORLD");
+    let count = fs::read_to_string(pd.join("workspace/count.txt")).unwrap();
+    assert_eq!(count.trim(), "5");
+}
+
+#[test]
+fn run_step_ignoring_large_stdin_completes() {
+    let dir = TempDir::new().unwrap();
+    setup_pipeline(
+        dir.path(),
+        r#"
+version: 1
+workspace: workspace
+steps:
+  - id: ignore
+    type: bash
+    bash: echo done
+    stdin_file: big.txt
+"#,
+    );
+
+    let pd = pipeline_dir(dir.path());
+    fs::create_dir_all(pd.join("workspace")).unwrap();
+    fs::write(pd.join("workspace/big.txt"), vec![b'x'; 1_000_000]).unwrap();
+
+    runner::run_pipeline(&pd, &Config::default(), &RunOptions::default()).unwrap();
+    let s = state::load(&pd.join("state.json")).unwrap().unwrap();
+    assert_eq!(s.steps["ignore"].status, StepStatus::Completed);
+}
+
+#[test]
+fn run_step_timeout_capped_by_max_timeout() {
+    let dir = TempDir::new().unwrap();
+    setup_pipeline(
+        dir.path(),
+        r#"
+version: 1
+workspace: workspace
+steps:
+  - id: slow
+    type: bash
+    bash: sleep 30
+    timeout: 4000000000
+"#,
+    );
+
+    let cfg = Config {
+        max_timeout: Some(1),
+        kill_grace_secs: 1,
+        ..Config::default()
+    };
+    let pd = pipeline_dir(dir.path());
+    let err = runner::run_pipeline(&pd, &cfg, &RunOptions::default()).unwrap_err();
+    assert!(
+        err.to_string().contains("timed out after 1s"),
+        "got: {}",
+        err
+    );
+}
+
+#[test]
+fn run_step_inherits_pipeline_default_timeout() {
+    let dir = TempDir::new().unwrap();
+    setup_pipeline(
+        dir.path(),
+        r#"
+version: 1
+workspace: workspace
+default_timeout: 1
+steps:
+  - id: slow
+    type: bash
+    bash: sleep 30
+"#,
+    );
+
+    let cfg = Config {
+        timeout: 300,
+        kill_grace_secs: 1,
+        ..Config::default()
+    };
+    let pd = pipeline_dir(dir.path());
+    let err = runner::run_pipeline(&pd, &cfg, &RunOptions::default()).unwrap_err();
+    assert!(
+        err.to_string().contains("timed out after 1s"),
+        "got: {}",
+        err
+    );
+}
+
+// ─── Step reset ───
+
+#[test]
+fn reset_step_keeps_earlier_work() {
+    let dir = TempDir::new().unwrap();
+    setup_pipeline(
+        dir.path(),
+        r#"
+version: 1
+workspace: workspace
+steps:
+  - id: first
+    type: bash
+    bash: echo first
+  - id: second
+    type: bash
+    bash: exit 1
+"#,
+    );
+
+    let cfg = Config::default();
+    let pd = pipeline_dir(dir.path());
+    runner::run_pipeline(&pd, &cfg, &RunOptions::default()).unwrap();
+    runner::run_pipeline(&pd, &cfg, &RunOptions::default()).unwrap_err();
+
+    // Only failed steps are reset with failed_only
+    assert!(!runner::reset_step(&pd, "first", true).unwrap());
+    assert!(runner::reset_step(&pd, "second", true).unwrap());
+
+    let s = state::load(&pd.join("state.json")).unwrap().unwrap();
+    assert_eq!(s.steps["first"].status, StepStatus::Completed);
+    assert_eq!(s.steps["second"].status, StepStatus::Pending);
+    assert!(s.steps["second"].error_message.is_none());
+    assert_eq!(s.steps["second"].attempts, 0);
+
+    // Already pending
+    assert!(!runner::reset_step(&pd, "second", false).unwrap());
+    assert!(runner::reset_step(&pd, "first", false).unwrap());
+    let s = state::load(&pd.join("state.json")).unwrap().unwrap();
+    assert_eq!(s.steps["first"].status, StepStatus::Pending);
+}
+
+#[test]
+fn reset_step_without_state_does_nothing() {
+    let dir = TempDir::new().unwrap();
+    setup_pipeline(
+        dir.path(),
+        r#"
+version: 1
+workspace: workspace
+steps:
+  - id: only
+    type: bash
+    bash: echo only
+"#,
+    );
+
+    let pd = pipeline_dir(dir.path());
+    assert!(!runner::reset_step(&pd, "only", false).unwrap());
+    assert!(!pd.join("state.json").exists());
+}
+
+#[test]
+fn retry_step_gives_failed_step_fresh_attempts() {
+    let dir = TempDir::new().unwrap();
+    setup_pipeline(
+        dir.path(),
+        r#"
+version: 1
+workspace: workspace
+steps:
+  - id: first
+    type: bash
+    bash: echo first
+  - id: second
+    type: bash
+    bash: exit 1
+"#,
+    );
+
+    let cfg = Config::default();
+    let pd = pipeline_dir(dir.path());
+    runner::run_pipeline(&pd, &cfg, &RunOptions::default()).unwrap();
+    runner::run_pipeline(&pd, &cfg, &RunOptions::default()).unwrap_err();
+
+    // Completed steps have nothing to retry
+    assert!(runner::retry_step(&pd, "first").unwrap().is_none());
+    assert_eq!(
+        runner::retry_step(&pd, "second").unwrap(),
+        Some((StepStatus::Failed, 1))
+    );
+
+    let s = state::load(&pd.join("state.json")).unwrap().unwrap();
+    assert_eq!(s.steps["first"].status, StepStatus::Completed);
+    assert_eq!(s.steps["second"].status, StepStatus::Pending);
+    assert_eq!(s.steps["second"].attempts, 0);
+    // The last failure is kept for context
+    assert!(s.steps["second"].error_message.is_some());
+
+    // Already has a fresh budget
+    assert!(runner::retry_step(&pd, "second").unwrap().is_none());
+    assert!(runner::retry_step(&pd, "missing").is_err());
+}
+
+#[test]
+fn reset_from_rewinds_step_and_later_steps() {
+    let dir = TempDir::new().unwrap();
+    setup_pipeline(
+        dir.path(),
+        r#"
+version: 1
+workspace: workspace
+steps:
+  - id: fetch
+    type: bash
+    bash: echo fetch
+  - id: parse
+    type: bash
+    bash: echo parse
+  - id: report
+    type: bash
+    bash: echo report
+"#,
+    );
+
+    let cfg = Config::default();
+    let pd = pipeline_dir(dir.path());
+    for _ in 0..3 {
+        runner::run_pipeline(&pd, &cfg, &RunOptions::default()).unwrap();
+    }
+
+    let p = pipeline::load(&pd.join("pipeline.yaml")).unwrap();
+    let reset = runner::reset_from(&pd, &p, "parse").unwrap();
+    assert_eq!(reset, vec!["parse", "report"]);
+
+    let s = state::load(&pd.join("state.json")).unwrap().unwrap();
+    assert_eq!(s.steps["fetch"].status, StepStatus::Completed);
+    assert_eq!(s.steps["parse"].status, StepStatus::Pending);
+    assert_eq!(s.steps["report"].status, StepStatus::Pending);
+
+    // The next tick picks up from the rewound step
+    runner::run_pipeline(&pd, &cfg, &RunOptions::default()).unwrap();
+    let s = state::load(&pd.join("state.json")).unwrap().unwrap();
+    assert_eq!(s.steps["parse"].status, StepStatus::Completed);
+    assert_eq!(s.steps["report"].status, StepStatus::Pending);
+
+    let err = runner::reset_from(&pd, &p, "missing")
+        .unwrap_err()
+        .to_string();
+    assert!(err.contains("no step 'missing'"), "got: {}", err);
+}
+
+// ─── Schedule ───
+
+#[test]
+fn run_scheduled_pipeline_waits_until_due() {
+    let dir = TempDir::new().unwrap();
+    setup_pipeline(
+        dir.path(),
+        r#"
+version: 1
+workspace: workspace
+schedule: "0 0 1 1 *"
+steps:
+  - id: first
+    type: bash
+    bash: echo first
+  - id: second
+    type: bash
+    bash: echo second
+"#,
+    );
+
+    let cfg = Config::default();
+    let pd = pipeline_dir(dir.path());
+
+    // Never ticked before, so the first run is due
+    runner::run_pipeline(&pd, &cfg, &RunOptions::default()).unwrap();
+    let s = state::load(&pd.join("state.json")).unwrap().unwrap();
+    assert_eq!(s.steps["first"].status, StepStatus::Completed);
+    assert!(s.last_tick.is_some());
+
+    // The next scheduled time is months away
+    runner::run_pipeline(&pd, &cfg, &RunOptions::default()).unwrap();
+    let s = state::load(&pd.join("state.json")).unwrap().unwrap();
+    assert_eq!(s.steps["second"].status, StepStatus::Pending);
+}
+
+#[test]
+fn run_scheduled_pipeline_ticks_once_a_scheduled_time_has_passed() {
+    let dir = TempDir::new().unwrap();
+    setup_pipeline(
+        dir.path(),
+        r#"
+version: 1
+workspace: workspace
+schedule: "0 0 1 1 *"
+steps:
+  - id: first
+    type: bash
+    bash: echo first
+  - id: second
+    type: bash
+    bash: echo second
+"#,
+    );
+
+    let cfg = Config::default();
+    let pd = pipeline_dir(dir.path());
+    runner::run_pipeline(&pd, &cfg, &RunOptions::default()).unwrap();
+
+    // Pretend the last tick was long ago, before the last scheduled time
+    let mut s = state::load(&pd.join("state.json")).unwrap().unwrap();
+    s.last_tick = Some("2000-06-01T00:00:00Z".to_string());
+    state::save(&pd.join("state.json"), &s).unwrap();
+
+    runner::run_pipeline(&pd, &cfg, &RunOptions::default()).unwrap();
+    let s = state::load(&pd.join("state.json")).unwrap().unwrap();
+    assert_eq!(s.steps["second"].status, StepStatus::Completed);
+    assert!(!s.last_tick.unwrap().starts_with("2000"));
+}
+
+#[test]
+fn run_counts_every_tick() {
+    let dir = TempDir::new().unwrap();
+    setup_pipeline(
+        dir.path(),
+        r#"
+version: 1
+workspace: workspace
+schedule: "0 0 1 1 *"
+steps:
+  - id: only
+    type: bash
+    bash: echo only
+"#,
+    );
+
+    let cfg = Config::default();
+    let pd = pipeline_dir(dir.path());
+
+    // A run, a not-due run, and a dry run that doesn't count
+    runner::run_pipeline(&pd, &cfg, &RunOptions::default()).unwrap();
+    runner::run_pipeline(&pd, &cfg, &RunOptions::default()).unwrap();
+    let dry_run = RunOptions {
+        dry_run: true,
+        ..RunOptions::default()
+    };
+    runner::run_pipeline(&pd, &cfg, &dry_run).unwrap();
+
+    let s = state::load(&pd.join("state.json")).unwrap().unwrap();
+    assert_eq!(s.tick_count, 2);
+    assert!(s.last_run_at.is_some());
+    assert!(s.secs_since_last_run().unwrap() < 60);
+}
+
+#[test]
+fn run_watched_pipeline_ticks_only_when_a_watched_file_changes() {
+    let dir = TempDir::new().unwrap();
+    setup_pipeline(
+        dir.path(),
+        r#"
+version: 1
+workspace: workspace
+watch: [inbox/data.csv]
+steps:
+  - id: first
+    type: bash
+    bash: echo first
+  - id: second
+    type: bash
+    bash: echo second
+  - id: third
+    type: bash
+    bash: echo third
+"#,
+    );
+
+    let cfg = Config::default();
+    let pd = pipeline_dir(dir.path());
+    let data = pd.join("inbox/data.csv");
+    fs::create_dir_all(data.parent().unwrap()).unwrap();
+    fs::write(&data, "a,b\n").unwrap();
+
+    // Never ticked before, so the first run is due
+    runner::run_pipeline(&pd, &cfg, &RunOptions::default()).unwrap();
+    let s = state::load(&pd.join("state.json")).unwrap().unwrap();
+    assert_eq!(s.steps["first"].status, StepStatus::Completed);
+    assert!(s.watched.contains_key("inbox/data.csv"));
+
+    // Nothing has changed since
+    runner::run_pipeline(&pd, &cfg, &RunOptions::default()).unwrap();
+    let s = state::load(&pd.join("state.json")).unwrap().unwrap();
+    assert_eq!(s.steps["second"].status, StepStatus::Pending);
+
+    let modified = std::time::SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(86_400);
+    fs::File::options()
+        .write(true)
+        .open(&data)
+        .unwrap()
+        .set_modified(modified)
+        .unwrap();
+    runner::run_pipeline(&pd, &cfg, &RunOptions::default()).unwrap();
+    let s = state::load(&pd.join("state.json")).unwrap().unwrap();
+    assert_eq!(s.steps["second"].status, StepStatus::Completed);
+
+    // A missing file counts as unchanged
+    fs::remove_file(&data).unwrap();
+    runner::run_pipeline(&pd, &cfg, &RunOptions::default()).unwrap();
+    let s = state::load(&pd.join("state.json")).unwrap().unwrap();
+    assert_eq!(s.steps["third"].status, StepStatus::Pending);
+}
+
+#[test]
+fn run_watched_pipeline_can_fail_on_a_missing_file() {
+    let dir = TempDir::new().unwrap();
+    setup_pipeline(
+        dir.path(),
+        r#"
+version: 1
+workspace: workspace
+watch: [inbox/data.csv]
+watch_missing: fail
+steps:
+  - id: only
+    type: bash
+    bash: echo only
+"#,
+    );
+
+    let pd = pipeline_dir(dir.path());
+    let err = runner::run_pipeline(&pd, &Config::default(), &RunOptions::default()).unwrap_err();
+    assert!(
+        err.to_string()
+            .contains("[test] watched file 'inbox/data.csv' is missing"),
+        "got: {}",
+        err
+    );
+    let s = state::load(&pd.join("state.json")).unwrap().unwrap();
+    assert_eq!(s.steps["only"].status, StepStatus::Pending);
+}
+
+// ─── HTTP steps ───
+
+/// Answer a single HTTP request on a local port with `status` and `body`.
+/// Returns the URL to call and a handle yielding the raw request received.
+fn serve_once(status: &str, body: &str) -> (String, std::thread::JoinHandle<String>) {
+    use std::io::{Read, Write};
+
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    let response = format!(
+        "HTTP/1.1 {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    );
+    let handle = std::thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut request = Vec::new();
+        let mut buf = [0u8; 1024];
+        loop {
+            let n = stream.read(&mut buf).unwrap();
+            request.extend_from_slice(&buf[..n]);
+            let text = String::from_utf8_lossy(&request);
+            if let Some(end) = text.find("\r\n\r\n") {
+                let length = text[..end]
+                    .lines()
+                    .find_map(|l| {
+                        l.to_lowercase()
+                            .strip_prefix("content-length:")
+                            .map(|v| v.trim().parse::<usize>().unwrap())
+                    })
+                    .unwrap_or(0);
+                if request.len() >= end + 4 + length || n == 0 {
+                    break;
+                }
+            }
+        }
+        stream.write_all(response.as_bytes()).unwrap();
+        String::from_utf8(request).unwrap()
+    });
+    (url, handle)
+}
+
+#[test]
+fn run_http_step_writes_response_to_output() {
+    let dir = TempDir::new().unwrap();
+    let (url, server) = serve_once("200 OK", "{\"ok\":true}");
+    setup_pipeline(
+        dir.path(),
+        &format!(
+            r#"
+version: 1
+workspace: workspace
+steps:
+  - id: notify
+    type: http
+    method: post
+    url: {}/hook
+    headers:
+      Content-Type: application/json
+    body: '{{"text": "{{{{ file:msg.txt }}}}"}}'
+    output: response.json
+"#,
+            url
+        ),
+    );
+    let pd = pipeline_dir(dir.path());
+    fs::create_dir_all(pd.join("workspace")).unwrap();
+    fs::write(pd.join("workspace/msg.txt"), "hello").unwrap();
+
+    runner::run_pipeline(&pd, &Config::default(), &RunOptions::default()).unwrap();
+
+    let request = server.join().unwrap();
+    assert!(
+        request.starts_with("POST /hook HTTP/1.1"),
+        "got: {}",
+        request
+    );
+    assert!(
+        request
+            .to_lowercase()
+            .contains("content-type: application/json")
+    );
+    assert!(
+        request.ends_with(r#"{"text": "hello"}"#),
+        "got: {}",
+        request
+    );
+
+    let response = fs::read_to_string(pd.join("workspace/response.json")).unwrap();
+    assert_eq!(response, "{\"ok\":true}");
+    let s = state::load(&pd.join("state.json")).unwrap().unwrap();
+    assert_eq!(s.steps["notify"].status, StepStatus::Completed);
+}
+
+#[test]
+fn run_http_step_fails_on_error_status() {
+    let dir = TempDir::new().unwrap();
+    let (url, server) = serve_once("503 Service Unavailable", "down");
+    setup_pipeline(
+        dir.path(),
+        &format!(
+            r#"
+version: 1
+workspace: workspace
+steps:
+  - id: fetch
+    type: http
+    url: {}/status
+    output: null
+"#,
+            url
+        ),
+    );
+    let pd = pipeline_dir(dir.path());
+
+    let err = runner::run_pipeline(&pd, &Config::default(), &RunOptions::default()).unwrap_err();
+    server.join().unwrap();
+    assert!(err.to_string().contains("503"), "got: {}", err);
+
+    let s = state::load(&pd.join("state.json")).unwrap().unwrap();
+    assert_eq!(s.steps["fetch"].status, StepStatus::Failed);
+    assert_eq!(s.steps["fetch"].exit_code, None);
+}
+
+#[test]
+fn run_http_step_accepts_expected_status() {
+    let dir = TempDir::new().unwrap();
+    let (url, server) = serve_once("404 Not Found", "");
+    setup_pipeline(
+        dir.path(),
+        &format!(
+            r#"
+version: 1
+workspace: workspace
+steps:
+  - id: probe
+    type: http
+    method: DELETE
+    url: {}/thing
+    expect_status: [204, 404]
+"#,
+            url
+        ),
+    );
+    let pd = pipeline_dir(dir.path());
+
+    runner::run_pipeline(&pd, &Config::default(), &RunOptions::default()).unwrap();
+    assert!(server.join().unwrap().starts_with("DELETE /thing "));
+
+    let s = state::load(&pd.join("state.json")).unwrap().unwrap();
+    assert_eq!(s.steps["probe"].status, StepStatus::Completed);
+}
+
+// ─── Inputs ───
+
+const INPUTS_PIPELINE: &str = r#"
+version: 1
+workspace: workspace
+steps:
+  - id: first
+    type: bash
+    bash: echo first
+  - id: consume
+    type: bash
+    bash: cat data.json
+    inputs: [data.json]
+"#;
+
+#[test]
+fn run_missing_input_waits_by_default() {
+    let dir = TempDir::new().unwrap();
+    setup_pipeline(dir.path(), INPUTS_PIPELINE);
+    let cfg = Config::default();
+    let pd = pipeline_dir(dir.path());
+
+    runner::run_pipeline(&pd, &cfg, &RunOptions::default()).unwrap();
+    runner::run_pipeline(&pd, &cfg, &RunOptions::default()).unwrap();
+    let s = state::load(&pd.join("state.json")).unwrap().unwrap();
+    assert_eq!(s.steps["consume"].status, StepStatus::Pending);
+    assert_eq!(s.steps["consume"].attempts, 0);
+
+    // Runs once the input appears
+    fs::write(pd.join("workspace/data.json"), "{}").unwrap();
+    runner::run_pipeline(&pd, &cfg, &RunOptions::default()).unwrap();
+    let s = state::load(&pd.join("state.json")).unwrap().unwrap();
+    assert_eq!(s.steps["consume"].status, StepStatus::Completed);
+}
+
+#[test]
+fn run_missing_input_fails_step_when_configured() {
+    let dir = TempDir::new().unwrap();
+    setup_pipeline(dir.path(), INPUTS_PIPELINE);
+    let cfg = Config {
+        missing_inputs: cronclaw::config::MissingInputs::Fail,
+        ..Config::default()
+    };
+    let pd = pipeline_dir(dir.path());
+
+    runner::run_pipeline(&pd, &cfg, &RunOptions::default()).unwrap();
+    let err = runner::run_pipeline(&pd, &cfg, &RunOptions::default()).unwrap_err();
+    assert!(
+        err.to_string()
+            .contains("step 'consume' failed: requires input 'data.json' which is missing"),
+        "got: {}",
+        err
+    );
+
+    let s = state::load(&pd.join("state.json")).unwrap().unwrap();
+    assert_eq!(s.steps["consume"].status, StepStatus::Failed);
+    assert!(
+        s.steps["consume"]
+            .error_message
+            .as_deref()
+            .unwrap()
+            .contains("data.json")
+    );
+}
+
+#[test]
+fn run_checks_workspace_free_space_when_configured() {
+    let dir = TempDir::new().unwrap();
+    setup_pipeline(
+        dir.path(),
+        r#"
+version: 1
+workspace: workspace
+steps:
+  - id: small
+    type: bash
+    bash: echo small
+  - id: huge
+    type: bash
+    bash: echo huge
+"#,
+    );
+    let pd = pipeline_dir(dir.path());
+
+    let cfg = Config {
+        min_free_bytes: Some(1),
+        ..Config::default()
+    };
+    runner::run_pipeline(&pd, &cfg, &RunOptions::default()).unwrap();
+    assert!(!pd.join("workspace/.cronclaw-write-check").exists());
+
+    let cfg = Config {
+        min_free_bytes: Some(u64::MAX),
+        ..Config::default()
+    };
+    let err = runner::run_pipeline(&pd, &cfg, &RunOptions::default()).unwrap_err();
+    assert!(matches!(err, CronclawError::StepFailed { code: None, .. }));
+    assert!(
+        err.to_string().contains("under min_free_bytes of"),
+        "got: {}",
+        err
+    );
+
+    let s = state::load(&pd.join("state.json")).unwrap().unwrap();
+    assert_eq!(s.steps["small"].status, StepStatus::Completed);
+    assert_eq!(s.steps["huge"].status, StepStatus::Failed);
+    assert_eq!(s.steps["huge"].attempts, 1);
+    let log = fs::read_to_string(pd.join("logs/huge.log")).unwrap();
+    assert!(log.contains("under min_free_bytes of"), "{}", log);
+}
+
+#[test]
+fn run_workspace_check_failure_honours_allow_failure_and_hooks() {
+    let dir = TempDir::new().unwrap();
+    setup_pipeline(
+        dir.path(),
+        r#"
+version: 1
+workspace: workspace
+on_failure: echo failed > hook.txt
+steps:
+  - id: optional
+    type: bash
+    bash: echo optional
+    allow_failure: true
+  - id: required
+    type: bash
+    bash: echo required
+"#,
+    );
+    let cfg = Config {
+        min_free_bytes: Some(u64::MAX),
+        ..Config::default()
+    };
+    let pd = pipeline_dir(dir.path());
+
+    // Allowed to fail, so the pipeline carries on
+    runner::run_pipeline(&pd, &cfg, &RunOptions::default()).unwrap();
+    let s = state::load(&pd.join("state.json")).unwrap().unwrap();
+    assert_eq!(s.steps["optional"].status, StepStatus::Completed);
+    assert!(
+        s.steps["optional"]
+            .error_message
+            .as_deref()
+            .unwrap()
+            .contains("under min_free_bytes of")
+    );
+
+    // Failing for good runs the pipeline's on_failure hook
+    assert!(runner::run_pipeline(&pd, &cfg, &RunOptions::default()).is_err());
+    let s = state::load(&pd.join("state.json")).unwrap().unwrap();
+    assert_eq!(s.steps["required"].status, StepStatus::Failed);
+    assert!(pd.join("hook.txt").exists());
+}
+
+#[test]
+fn run_missing_input_failure_honours_retries_and_allow_failure() {
+    let dir = TempDir::new().unwrap();
+    setup_pipeline(
+        dir.path(),
+        &INPUTS_PIPELINE.replace(
+            "    inputs: [data.json]\n",
+            "    inputs: [data.json]\n    retries: 1\n    allow_failure: true\n  - id: after\n    type: bash\n    bash: echo after\n",
+        ),
+    );
+    let cfg = Config {
+        missing_inputs: cronclaw::config::MissingInputs::Fail,
+        ..Config::default()
+    };
+    let pd = pipeline_dir(dir.path());
+
+    runner::run_pipeline(&pd, &cfg, &RunOptions::default()).unwrap();
+    // The first attempt fails and leaves a retry
+    runner::run_pipeline(&pd, &cfg, &RunOptions::default()).unwrap();
+    let s = state::load(&pd.join("state.json")).unwrap().unwrap();
+    assert_eq!(s.steps["consume"].status, StepStatus::Pending);
+    assert_eq!(s.steps["consume"].attempts, 1);
+
+    // The last one is allowed to fail, so the pipeline carries on
+    runner::run_pipeline(&pd, &cfg, &RunOptions::default()).unwrap();
+    runner::run_pipeline(&pd, &cfg, &RunOptions::default()).unwrap();
+    let s = state::load(&pd.join("state.json")).unwrap().unwrap();
+    assert_eq!(s.steps["consume"].status, StepStatus::Completed);
+    assert_eq!(s.steps["after"].status, StepStatus::Completed);
+    let log = fs::read_to_string(pd.join("logs/consume.log")).unwrap();
+    assert_eq!(
+        log.matches("failed: requires input 'data.json' which is missing")
+            .count(),
+        2
+    );
+}
+
+#[test]
+fn run_parallel_missing_input_claims_nothing() {
+    let dir = TempDir::new().unwrap();
+    setup_pipeline(
+        dir.path(),
+        r#"
+version: 1
+workspace: workspace
+steps:
+  - id: ready
+    type: bash
+    bash: echo ready
+    needs: []
+  - id: consume
+    type: bash
+    bash: cat data.json
+    needs: []
+    inputs: [data.json]
+"#,
+    );
+    let cfg = Config {
+        max_parallel: 2,
+        missing_inputs: cronclaw::config::MissingInputs::Fail,
+        ..Config::default()
+    };
+    let pd = pipeline_dir(dir.path());
+
+    assert!(runner::run_pipeline(&pd, &cfg, &RunOptions::default()).is_err());
+    let s = state::load(&pd.join("state.json")).unwrap().unwrap();
+    assert_eq!(s.steps["ready"].status, StepStatus::Pending);
+    assert_eq!(s.steps["consume"].status, StepStatus::Failed);
+}
+
+// ─── Hooks ───
+
+#[test]
+fn run_on_failure_hook_describes_failed_step() {
+    let dir = TempDir::new().unwrap();
+    setup_pipeline(
+        dir.path(),
+        r#"
+version: 1
+workspace: workspace
+steps:
+  - id: broken
+    type: bash
+    bash: exit 3
+"#,
+    );
+
+    let cfg = Config {
+        on_failure: Some(
+            "echo \"$CRONCLAW_PIPELINE $CRONCLAW_STATUS $CRONCLAW_STEP $CRONCLAW_EXIT_CODE\" > hook.txt"
+                .to_string(),
+        ),
+        on_success: Some("touch success.txt".to_string()),
+        ..Config::default()
+    };
+    let pd = pipeline_dir(dir.path());
+    assert!(runner::run_pipeline(&pd, &cfg, &RunOptions::default()).is_err());
+
+    let hook = fs::read_to_string(pd.join("hook.txt")).unwrap();
+    assert_eq!(hook.trim(), "test failed broken 3");
+    assert!(!pd.join("success.txt").exists());
+}
+
+#[test]
+fn run_on_failure_hook_waits_for_retries() {
+    let dir = TempDir::new().unwrap();
+    setup_pipeline(
+        dir.path(),
+        r#"
+version: 1
+workspace: workspace
+steps:
+  - id: flaky
+    type: bash
+    bash: exit 1
+    retries: 1
+"#,
+    );
+
+    let cfg = Config {
+        on_failure: Some("echo \"$CRONCLAW_ERROR\" >> hook.txt".to_string()),
+        ..Config::default()
+    };
+    let pd = pipeline_dir(dir.path());
+    runner::run_pipeline(&pd, &cfg, &RunOptions::default()).unwrap();
+    assert!(!pd.join("hook.txt").exists());
+
+    assert!(runner::run_pipeline(&pd, &cfg, &RunOptions::default()).is_err());
+    let hook = fs::read_to_string(pd.join("hook.txt")).unwrap();
+    assert_eq!(hook.lines().count(), 1);
+    assert!(hook.contains("code 1"), "{}", hook);
+}
+
+#[test]
+fn run_on_failure_cleanup_steps_run_after_failure() {
+    let dir = TempDir::new().unwrap();
+    setup_pipeline(
+        dir.path(),
+        r#"
+version: 1
+workspace: workspace
+on_failure:
+  - id: tidy
+    type: bash
+    bash: rm -f partial.txt
+  - id: broken-cleanup
+    type: bash
+    bash: exit 7
+  - id: note
+    type: bash
+    bash: echo cleaned > cleaned.txt
+steps:
+  - id: build
+    type: bash
+    bash: echo half > partial.txt && exit 1
+"#,
+    );
+
+    let cfg = Config {
+        on_failure: Some("touch config-hook.txt".to_string()),
+        ..Config::default()
+    };
+    let pd = pipeline_dir(dir.path());
+    let err = runner::run_pipeline(&pd, &cfg, &RunOptions::default()).unwrap_err();
+    // A failing cleanup step doesn't hide the original failure
+    assert_eq!(err.kind(), ErrorKind::Step);
+    assert!(
+        matches!(&err, CronclawError::StepFailed { id, code: Some(1), .. } if id == "build"),
+        "{:?}",
+        err
+    );
+    assert!(err.to_string().contains("step 'build' failed"), "{}", err);
+
+    let workspace = pd.join("workspace");
+    assert!(!workspace.join("partial.txt").exists());
+    assert!(workspace.join("cleaned.txt").exists());
+    // The pipeline's on_failure takes the config hook's place
+    assert!(!pd.join("config-hook.txt").exists());
+
+    let s = state::load(&pd.join("state.json")).unwrap().unwrap();
+    assert_eq!(s.steps.len(), 1);
+    assert_eq!(s.steps["build"].status, StepStatus::Failed);
+    assert_eq!(s.cleanup["tidy"].status, StepStatus::Completed);
+    assert_eq!(s.cleanup["broken-cleanup"].status, StepStatus::Failed);
+    assert_eq!(s.cleanup["broken-cleanup"].exit_code, Some(7));
+    assert_eq!(s.cleanup["note"].status, StepStatus::Completed);
+}
+
+#[test]
+fn run_pipeline_on_success_hook_overrides_config() {
+    let dir = TempDir::new().unwrap();
+    setup_pipeline(
+        dir.path(),
+        r#"
+version: 1
+workspace: workspace
+on_success: echo "$CRONCLAW_STATUS" > pipeline-hook.txt
+steps:
+  - id: first
+    type: bash
+    bash: echo one
+  - id: second
+    type: bash
+    bash: echo two
+"#,
+    );
+
+    let cfg = Config {
+        on_success: Some("touch config-hook.txt".to_string()),
+        ..Config::default()
+    };
+    let pd = pipeline_dir(dir.path());
+
+    // Only once the last step is done
+    runner::run_pipeline(&pd, &cfg, &RunOptions::default()).unwrap();
+    assert!(!pd.join("pipeline-hook.txt").exists());
+
+    runner::run_pipeline(&pd, &cfg, &RunOptions::default()).unwrap();
+    let hook = fs::read_to_string(pd.join("pipeline-hook.txt")).unwrap();
+    assert_eq!(hook.trim(), "completed");
+    assert!(!pd.join("config-hook.txt").exists());
+}
+
+#[test]
+fn run_failing_hook_is_not_fatal() {
+    let dir = TempDir::new().unwrap();
+    setup_pipeline(
+        dir.path(),
+        r#"
+version: 1
+workspace: workspace
+on_success: exit 1
+steps:
+  - id: only
+    type: bash
+    bash: echo hi
 "#,
     );
 
-    let cfg = Config::default();
     let pd = pipeline_dir(dir.path());
-
-    // Tick 1
-    runner::run_pipeline(&pd, &cfg, false).unwrap();
+    runner::run_pipeline(&pd, &Config::default(), &RunOptions::default()).unwrap();
     let s = state::load(&pd.join("state.json")).unwrap().unwrap();
-    assert_eq!(s.steps["first"].status, StepStatus::Completed);
-    assert_eq!(s.steps["second"].status, StepStatus::Pending);
-    assert_eq!(s.steps["third"].status, StepStatus::Pending);
+    assert_eq!(s.steps["only"].status, StepStatus::Completed);
+}
 
-    // Tick 2
-    runner::run_pipeline(&pd, &cfg, false).unwrap();
-    let s = state::load(&pd.join("state.json")).unwrap().unwrap();
-    assert_eq!(s.steps["first"].status, StepStatus::Completed);
-    assert_eq!(s.steps["second"].status, StepStatus::Completed);
-    assert_eq!(s.steps["third"].status, StepStatus::Pending);
+// ─── Dry run ───
 
-    // Tick 3
-    runner::run_pipeline(&pd, &cfg, false).unwrap();
-    let s = state::load(&pd.join("state.json")).unwrap().unwrap();
-    assert_eq!(s.steps["first"].status, StepStatus::Completed);
-    assert_eq!(s.steps["second"].status, StepStatus::Completed);
-    assert_eq!(s.steps["third"].status, StepStatus::Completed);
+fn dry_run() -> RunOptions {
+    RunOptions {
+        dry_run: true,
+        ..RunOptions::default()
+    }
 }
 
 #[test]
-fn run_failed_step_blocks_pipeline() {
+fn run_dry_run_writes_nothing() {
     let dir = TempDir::new().unwrap();
     setup_pipeline(
         dir.path(),
@@ -232,35 +3453,22 @@ fn run_failed_step_blocks_pipeline() {
 version: 1
 workspace: workspace
 steps:
-  - id: fail
-    type: bash
-    bash: exit 1
-  - id: after
+  - id: touch
     type: bash
-    bash: echo "should not run"
+    bash: touch made.txt
 "#,
     );
 
-    let cfg = Config::default();
     let pd = pipeline_dir(dir.path());
+    runner::run_pipeline(&pd, &Config::default(), &dry_run()).unwrap();
 
-    // Tick 1 — step fails
-    let result = runner::run_pipeline(&pd, &cfg, false);
-    assert!(result.is_err());
-
-    let s = state::load(&pd.join("state.json")).unwrap().unwrap();
-    assert_eq!(s.steps["fail"].status, StepStatus::Failed);
-    assert_eq!(s.steps["after"].status, StepStatus::Pending);
-
-    // Tick 2 — pipeline is blocked, no progress
-    runner::run_pipeline(&pd, &cfg, false).unwrap();
-    let s = state::load(&pd.join("state.json")).unwrap().unwrap();
-    assert_eq!(s.steps["fail"].status, StepStatus::Failed);
-    assert_eq!(s.steps["after"].status, StepStatus::Pending);
+    assert!(!pd.join("state.json").exists());
+    assert!(!pd.join("state.lock").exists());
+    assert!(!pd.join("workspace").exists());
 }
 
 #[test]
-fn run_failed_step_does_not_promote_outputs() {
+fn run_dry_run_leaves_state_unchanged() {
     let dir = TempDir::new().unwrap();
     setup_pipeline(
         dir.path(),
@@ -268,30 +3476,28 @@ fn run_failed_step_does_not_promote_outputs() {
 version: 1
 workspace: workspace
 steps:
-  - id: fail
+  - id: first
     type: bash
-    bash: echo "data" > out.txt.tmp && exit 1
-    outputs:
-      - name: out
-        path: out.txt
-        tmp: out.txt.tmp
+    bash: echo one
+  - id: second
+    type: bash
+    bash: touch made.txt
 "#,
     );
 
     let cfg = Config::default();
     let pd = pipeline_dir(dir.path());
-    let workspace = pd.join("workspace");
+    runner::run_pipeline(&pd, &cfg, &RunOptions::default()).unwrap();
+    let before = fs::read_to_string(pd.join("state.json")).unwrap();
 
-    let _ = runner::run_pipeline(&pd, &cfg, false);
+    runner::run_pipeline(&pd, &cfg, &dry_run()).unwrap();
 
-    // tmp should still exist (not promoted)
-    assert!(workspace.join("out.txt.tmp").exists());
-    // final should NOT exist
-    assert!(!workspace.join("out.txt").exists());
+    assert_eq!(fs::read_to_string(pd.join("state.json")).unwrap(), before);
+    assert!(!pd.join("workspace").join("made.txt").exists());
 }
 
 #[test]
-fn run_state_mismatch_errors() {
+fn run_dry_run_reports_template_errors() {
     let dir = TempDir::new().unwrap();
     setup_pipeline(
         dir.path(),
@@ -299,73 +3505,113 @@ fn run_state_mismatch_errors() {
 version: 1
 workspace: workspace
 steps:
-  - id: step-a
-    type: bash
-    bash: echo a
+  - id: ask
+    type: agent
+    agent: writer
+    prompt: "Summarise {{ file:missing.md }}"
 "#,
     );
 
-    let cfg = Config::default();
     let pd = pipeline_dir(dir.path());
+    let err = runner::run_pipeline(&pd, &Config::default(), &dry_run()).unwrap_err();
+    assert!(err.to_string().contains("missing.md"), "got: {}", err);
+    assert!(!pd.join("state.json").exists());
+}
 
-    // Run once to create state
-    runner::run_pipeline(&pd, &cfg, false).unwrap();
+// ─── Live output ───
 
-    // Change pipeline to have different steps
-    fs::write(
-        pd.join("pipeline.yaml"),
+#[test]
+fn run_shows_terminal_output_while_step_runs() {
+    use std::io::{BufRead, BufReader};
+
+    let dir = TempDir::new().unwrap();
+    let home = dir.path().join(".cronclaw");
+    setup_pipeline(
+        &home,
         r#"
 version: 1
 workspace: workspace
 steps:
-  - id: step-b
+  - id: slow
     type: bash
-    bash: echo b
+    bash: echo started; sleep 2; echo finished
 "#,
-    )
-    .unwrap();
+    );
 
-    let result = runner::run_pipeline(&pd, &cfg, false);
-    assert!(result.is_err());
-    let err = result.unwrap_err();
-    assert!(err.contains("mismatch"));
-    assert!(err.contains("reset"));
+    let mut child = std::process::Command::new(env!("CARGO_BIN_EXE_cronclaw"))
+        .arg("run")
+        .env("HOME", dir.path())
+        .stdout(std::process::Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut lines = BufReader::new(child.stdout.take().unwrap()).lines();
+
+    // The step's first line shows up before it finishes
+    let started = lines
+        .by_ref()
+        .map(|line| line.unwrap())
+        .find(|line| line == "started");
+    assert!(started.is_some());
+    assert!(child.try_wait().unwrap().is_none());
+
+    let rest: Vec<String> = lines.map(|line| line.unwrap()).collect();
+    assert!(rest.contains(&"finished".to_string()), "got: {:?}", rest);
+    assert!(child.wait().unwrap().success());
 }
 
+// ─── JSON events ───
+
 #[test]
-fn run_running_step_causes_early_exit() {
+fn run_json_emits_one_event_per_line() {
     let dir = TempDir::new().unwrap();
+    let home = dir.path().join(".cronclaw");
     setup_pipeline(
-        dir.path(),
+        &home,
         r#"
 version: 1
 workspace: workspace
 steps:
-  - id: stuck
+  - id: hello
     type: bash
-    bash: echo hi
-  - id: next
+    bash: echo "not an event"
+  - id: boom
     type: bash
-    bash: echo next
+    bash: exit 3
 "#,
     );
 
-    let cfg = Config::default();
-    let pd = pipeline_dir(dir.path());
-
-    // Create state with 'stuck' as running (simulating a crashed previous run)
-    let p = pipeline::parse(&fs::read_to_string(pd.join("pipeline.yaml")).unwrap()).unwrap();
-    let mut s = State::from_pipeline(&p);
-    s.steps.get_mut("stuck").unwrap().status = StepStatus::Running;
-    fs::create_dir_all(pd.join("workspace")).unwrap();
-    state::save(&pd.join("state.json"), &s).unwrap();
-
-    // Tick should see 'running' and exit without error, without touching 'next'
-    runner::run_pipeline(&pd, &cfg, false).unwrap();
-
-    let s = state::load(&pd.join("state.json")).unwrap().unwrap();
-    assert_eq!(s.steps["stuck"].status, StepStatus::Running);
-    assert_eq!(s.steps["next"].status, StepStatus::Pending);
+    let tick = || {
+        std::process::Command::new(env!("CARGO_BIN_EXE_cronclaw"))
+            .args(["--json", "run"])
+            .env("HOME", dir.path())
+            .output()
+            .unwrap()
+    };
+
+    let output = tick();
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let events: Vec<serde_json::Value> = stdout
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(events.len(), 2);
+    assert_eq!(events[0]["event"], "step_started");
+    assert_eq!(events[0]["pipeline"], "test");
+    assert_eq!(events[0]["step"], "hello");
+    assert_eq!(events[1]["event"], "step_completed");
+    assert_eq!(events[1]["status"], "completed");
+    assert!(events[1]["duration_secs"].is_u64());
+    assert!(events[1]["timestamp"].is_string());
+
+    let output = tick();
+    assert!(!output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let failed: serde_json::Value = serde_json::from_str(stdout.lines().last().unwrap()).unwrap();
+    assert_eq!(failed["event"], "step_failed");
+    assert_eq!(failed["step"], "boom");
+    assert_eq!(failed["status"], "failed");
+    assert_eq!(failed["exit_code"], 3);
 }
 
 // ─── Agent step integration ───
@@ -389,10 +3635,10 @@ fn run_with_fake_openclaw(
 
     // SAFETY: serialized by mutex — no concurrent env mutation.
     unsafe { std::env::set_var("OPENCLAW_BIN", fake_bin) };
-    let result = runner::run_pipeline(pipeline_dir, cfg, false);
+    let result = runner::run_pipeline(pipeline_dir, cfg, &RunOptions::default());
     unsafe { std::env::remove_var("OPENCLAW_BIN") };
 
-    result
+    result.map(|_| ()).map_err(|e| e.to_string())
 }
 
 #[test]
@@ -450,6 +3696,52 @@ steps:
 
     let s = state::load(&pd.join("state.json")).unwrap().unwrap();
     assert_eq!(s.steps["analyse"].status, StepStatus::Failed);
+    assert_eq!(s.steps["analyse"].exit_code, Some(1));
+    assert_eq!(
+        s.steps["analyse"].error_message.as_deref(),
+        Some("agent exited with code 1: agent error")
+    );
+}
+
+#[test]
+fn run_agent_step_failure_keeps_only_stderr_tail() {
+    let dir = TempDir::new().unwrap();
+
+    let fake_bin = install_fake_openclaw(
+        dir.path(),
+        "for i in 1 2 3 4 5 6 7 8; do echo \"line $i\" >&2; done\n\
+         printf 'x%.0s' $(seq 1 600) >&2\n\
+         echo >&2\n\
+         echo 'rate limited' >&2\n\
+         exit 2",
+    );
+
+    let pd = pipeline_dir(dir.path());
+    setup_pipeline(
+        dir.path(),
+        r#"
+version: 1
+workspace: workspace
+steps:
+  - id: analyse
+    type: agent
+    agent: pro-worker
+    prompt: "Analyse this data"
+"#,
+    );
+
+    assert!(run_with_fake_openclaw(&pd, &fake_bin, &Config::default()).is_err());
+    let s = state::load(&pd.join("state.json")).unwrap().unwrap();
+    let message = s.steps["analyse"].error_message.clone().unwrap();
+    assert!(
+        message.starts_with("agent exited with code 2: ..."),
+        "{}",
+        message
+    );
+    assert!(message.ends_with("xxx | rate limited"), "{}", message);
+    assert!(!message.contains("line 1"), "{}", message);
+    assert!(message.len() < 600, "{}", message.len());
+    assert_eq!(s.steps["analyse"].exit_code, Some(2));
 }
 
 #[test]
@@ -738,13 +4030,85 @@ steps:
     );
 
     let cfg = Config::default();
-    runner::run_pipeline(&pd, &cfg, false).unwrap();
+    runner::run_pipeline(&pd, &cfg, &RunOptions::default()).unwrap();
 
     let workspace = pd.join("workspace");
     let content = fs::read_to_string(workspace.join("greeting.txt")).unwrap();
     assert!(content.contains("hello from bash"));
 }
 
+#[test]
+fn run_append_output_accumulates_across_ticks() {
+    let dir = TempDir::new().unwrap();
+
+    let pd = pipeline_dir(dir.path());
+    setup_pipeline(
+        dir.path(),
+        r#"
+version: 1
+workspace: workspace
+steps:
+  - id: first
+    type: bash
+    bash: echo one
+    output: { file: runs.log, append: true }
+  - id: second
+    type: bash
+    bash: echo two
+    output: { file: runs.log, append: true }
+  - id: overwrite
+    type: bash
+    bash: echo three
+    output: latest.log
+"#,
+    );
+    let workspace = pd.join("workspace");
+    fs::create_dir_all(&workspace).unwrap();
+    fs::write(workspace.join("runs.log"), "zero\n").unwrap();
+    fs::write(workspace.join("latest.log"), "stale\n").unwrap();
+
+    let cfg = Config::default();
+    for _ in 0..3 {
+        runner::run_pipeline(&pd, &cfg, &RunOptions::default()).unwrap();
+    }
+
+    assert_eq!(
+        fs::read_to_string(workspace.join("runs.log")).unwrap(),
+        "zero\none\ntwo\n"
+    );
+    // Plain file targets are still replaced
+    assert_eq!(
+        fs::read_to_string(workspace.join("latest.log")).unwrap(),
+        "three\n"
+    );
+}
+
+#[test]
+fn run_binary_stdout_written_to_output_file_unchanged() {
+    let dir = TempDir::new().unwrap();
+
+    let pd = pipeline_dir(dir.path());
+    setup_pipeline(
+        dir.path(),
+        r#"
+version: 1
+workspace: workspace
+steps:
+  - id: binary
+    type: bash
+    bash: printf 'RIFF\377\376\000\200\nWAVE\351'
+    output: sound.wav
+"#,
+    );
+
+    let cfg = Config::default();
+    runner::run_pipeline(&pd, &cfg, &RunOptions::default()).unwrap();
+
+    // Not valid UTF-8, so any lossy conversion would show
+    let bytes = fs::read(pd.join("workspace").join("sound.wav")).unwrap();
+    assert_eq!(bytes, b"RIFF\xff\xfe\x00\x80\nWAVE\xe9");
+}
+
 #[test]
 fn run_bash_stderr_captured_to_error_file() {
     let dir = TempDir::new().unwrap();
@@ -764,13 +4128,42 @@ steps:
     );
 
     let cfg = Config::default();
-    runner::run_pipeline(&pd, &cfg, false).unwrap();
+    runner::run_pipeline(&pd, &cfg, &RunOptions::default()).unwrap();
 
     let workspace = pd.join("workspace");
     let content = fs::read_to_string(workspace.join("warnings.log")).unwrap();
     assert!(content.contains("warning msg"));
 }
 
+#[test]
+fn run_failed_step_still_writes_stream_files() {
+    let dir = TempDir::new().unwrap();
+
+    let pd = pipeline_dir(dir.path());
+    setup_pipeline(
+        dir.path(),
+        r#"
+version: 1
+workspace: workspace
+steps:
+  - id: broken
+    type: bash
+    bash: echo "partial" && echo "it broke" >&2 && exit 1
+    output: broken.out
+    error: broken.err
+"#,
+    );
+
+    let cfg = Config::default();
+    runner::run_pipeline(&pd, &cfg, &RunOptions::default()).unwrap_err();
+
+    let workspace = pd.join("workspace");
+    let out = fs::read_to_string(workspace.join("broken.out")).unwrap();
+    assert!(out.contains("partial"));
+    let err = fs::read_to_string(workspace.join("broken.err")).unwrap();
+    assert!(err.contains("it broke"));
+}
+
 #[test]
 fn run_void_output_discards_stdout() {
     let dir = TempDir::new().unwrap();
@@ -790,7 +4183,7 @@ steps:
     );
 
     let cfg = Config::default();
-    runner::run_pipeline(&pd, &cfg, false).unwrap();
+    runner::run_pipeline(&pd, &cfg, &RunOptions::default()).unwrap();
 
     // Step should complete successfully, no output file created
     let s = state::load(&pd.join("state.json")).unwrap().unwrap();
@@ -815,7 +4208,7 @@ steps:
     );
 
     let cfg = Config::default();
-    runner::run_pipeline(&pd, &cfg, false).unwrap();
+    runner::run_pipeline(&pd, &cfg, &RunOptions::default()).unwrap();
 
     // No output/error files should be created in workspace
     let workspace = pd.join("workspace");
@@ -863,3 +4256,150 @@ steps:
         err
     );
 }
+
+const FLAKY_AGENT_PIPELINE: &str = r#"
+version: 1
+workspace: workspace
+steps:
+  - id: analyse
+    type: agent
+    agent: pro-worker
+    prompt: "Analyse this data"
+    timeout: 5
+"#;
+
+/// A fake openclaw that fails until it has been called `succeed_on` times,
+/// counting its calls in `calls`.
+fn install_flaky_openclaw(dir: &std::path::Path, succeed_on: u32) -> std::path::PathBuf {
+    let calls = dir.join("calls");
+    install_fake_openclaw(
+        dir,
+        &format!(
+            "echo x >> {calls}\n[ $(wc -l < {calls}) -ge {succeed_on} ] || {{ echo busy >&2; exit 75; }}",
+            calls = calls.display(),
+            succeed_on = succeed_on
+        ),
+    )
+}
+
+#[test]
+fn run_agent_retries_with_backoff_within_step() {
+    let dir = TempDir::new().unwrap();
+    let fake_bin = install_flaky_openclaw(dir.path(), 3);
+    let pd = pipeline_dir(dir.path());
+    setup_pipeline(dir.path(), FLAKY_AGENT_PIPELINE);
+
+    let cfg = Config {
+        agent_retries: 2,
+        agent_backoff_base_secs: 0,
+        ..Config::default()
+    };
+    run_with_fake_openclaw(&pd, &fake_bin, &cfg).unwrap();
+
+    let s = state::load(&pd.join("state.json")).unwrap().unwrap();
+    assert_eq!(s.steps["analyse"].status, StepStatus::Completed);
+    assert_eq!(
+        fs::read_to_string(dir.path().join("calls"))
+            .unwrap()
+            .lines()
+            .count(),
+        3
+    );
+    let log = fs::read_to_string(pd.join("logs/analyse.log")).unwrap();
+    assert!(log.contains("retrying in 0s (1 of 2)"), "{}", log);
+    assert!(log.contains("retrying in 0s (2 of 2)"), "{}", log);
+}
+
+#[test]
+fn run_agent_fails_once_agent_retries_are_spent() {
+    let dir = TempDir::new().unwrap();
+    let fake_bin = install_flaky_openclaw(dir.path(), 10);
+    let pd = pipeline_dir(dir.path());
+    setup_pipeline(dir.path(), FLAKY_AGENT_PIPELINE);
+
+    let cfg = Config {
+        agent_retries: 1,
+        agent_backoff_base_secs: 0,
+        ..Config::default()
+    };
+    assert!(run_with_fake_openclaw(&pd, &fake_bin, &cfg).is_err());
+
+    let s = state::load(&pd.join("state.json")).unwrap().unwrap();
+    assert_eq!(s.steps["analyse"].status, StepStatus::Failed);
+    assert_eq!(s.steps["analyse"].exit_code, Some(75));
+    assert_eq!(
+        fs::read_to_string(dir.path().join("calls"))
+            .unwrap()
+            .lines()
+            .count(),
+        2
+    );
+}
+
+#[test]
+fn run_interrupt_cuts_agent_backoff_short() {
+    static STOP: AtomicBool = AtomicBool::new(false);
+    let dir = TempDir::new().unwrap();
+    let fake_bin = install_flaky_openclaw(dir.path(), 2);
+    let pd = pipeline_dir(dir.path());
+    setup_pipeline(
+        dir.path(),
+        &FLAKY_AGENT_PIPELINE.replace("timeout: 5", "timeout: 60"),
+    );
+
+    let cfg = Config {
+        agent_retries: 1,
+        agent_backoff_base_secs: 30,
+        ..Config::default()
+    };
+    let opts = RunOptions {
+        stop: Some(&STOP),
+        ..RunOptions::default()
+    };
+    let _guard = OPENCLAW_BIN_LOCK.lock().unwrap();
+    // SAFETY: serialized by mutex — no concurrent env mutation.
+    unsafe { std::env::set_var("OPENCLAW_BIN", &fake_bin) };
+    let started = std::time::Instant::now();
+    let err = std::thread::scope(|scope| {
+        scope.spawn(|| {
+            std::thread::sleep(std::time::Duration::from_millis(500));
+            STOP.store(true, Ordering::SeqCst);
+        });
+        runner::run_pipeline(&pd, &cfg, &opts).unwrap_err()
+    });
+    unsafe { std::env::remove_var("OPENCLAW_BIN") };
+
+    assert!(started.elapsed() < std::time::Duration::from_secs(10));
+    assert!(
+        matches!(&err, CronclawError::Interrupted { id, .. } if id == "analyse"),
+        "{:?}",
+        err
+    );
+    let s = state::load(&pd.join("state.json")).unwrap().unwrap();
+    assert_eq!(s.steps["analyse"].status, StepStatus::Pending);
+}
+
+#[test]
+fn run_agent_backoff_stops_at_step_timeout() {
+    let dir = TempDir::new().unwrap();
+    let fake_bin = install_flaky_openclaw(dir.path(), 2);
+    let pd = pipeline_dir(dir.path());
+    setup_pipeline(dir.path(), FLAKY_AGENT_PIPELINE);
+
+    // The first backoff alone would outlast the 5s timeout
+    let cfg = Config {
+        agent_retries: 3,
+        agent_backoff_base_secs: 10,
+        ..Config::default()
+    };
+    let started = std::time::Instant::now();
+    assert!(run_with_fake_openclaw(&pd, &fake_bin, &cfg).is_err());
+    assert!(started.elapsed() < std::time::Duration::from_secs(5));
+    assert_eq!(
+        fs::read_to_string(dir.path().join("calls"))
+            .unwrap()
+            .lines()
+            .count(),
+        1
+    );
+}
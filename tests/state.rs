@@ -23,7 +23,7 @@ steps:
     let s = State::from_pipeline(&p);
 
     assert_eq!(s.steps.len(), 3);
-    for (_, step_state) in &s.steps {
+    for step_state in s.steps.values() {
         assert_eq!(step_state.status, StepStatus::Pending);
     }
     assert!(s.steps.contains_key("step-a"));
@@ -110,6 +110,9 @@ steps:
   - id: d
     type: bash
     bash: echo d
+  - id: e
+    type: bash
+    bash: echo e
 "#;
     let p = pipeline::parse(yaml).unwrap();
     let mut s = State::from_pipeline(&p);
@@ -117,6 +120,7 @@ steps:
     s.steps.get_mut("b").unwrap().status = StepStatus::Running;
     s.steps.get_mut("c").unwrap().status = StepStatus::Completed;
     s.steps.get_mut("d").unwrap().status = StepStatus::Failed;
+    s.steps.get_mut("e").unwrap().status = StepStatus::Skipped;
 
     let dir = TempDir::new().unwrap();
     let path = dir.path().join("state.json");
@@ -127,4 +131,150 @@ steps:
     assert_eq!(loaded.steps["b"].status, StepStatus::Running);
     assert_eq!(loaded.steps["c"].status, StepStatus::Completed);
     assert_eq!(loaded.steps["d"].status, StepStatus::Failed);
+    assert_eq!(loaded.steps["e"].status, StepStatus::Skipped);
+}
+
+#[test]
+fn state_loads_file_without_timing_fields() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("state.json");
+    fs::write(&path, r#"{"steps": {"a": {"status": "completed"}}}"#).unwrap();
+
+    let loaded = state::load(&path).unwrap().unwrap();
+    assert_eq!(loaded.steps["a"].status, StepStatus::Completed);
+    assert!(loaded.steps["a"].started_at.is_none());
+    assert!(loaded.steps["a"].finished_at.is_none());
+    assert!(loaded.steps["a"].duration_secs.is_none());
+    assert!(loaded.steps["a"].output_hashes.is_empty());
+}
+
+#[test]
+fn step_state_start_and_finish_record_timing() {
+    let mut step = state::StepState::new();
+    assert!(step.started_at.is_none());
+
+    step.start();
+    assert_eq!(step.status, StepStatus::Running);
+    assert!(step.started_at.is_some());
+    assert!(step.finished_at.is_none());
+
+    step.finish(StepStatus::Completed);
+    assert_eq!(step.status, StepStatus::Completed);
+    assert!(step.finished_at.is_some());
+    assert_eq!(step.duration_secs, Some(0));
+}
+
+#[test]
+fn state_keeps_pipeline_order() {
+    let yaml = r#"
+version: 1
+workspace: workspace
+steps:
+  - id: zeta
+    type: bash
+    bash: echo z
+  - id: alpha
+    type: bash
+    bash: echo a
+  - id: mid
+    type: bash
+    bash: echo m
+"#;
+    let p = pipeline::parse(yaml).unwrap();
+    let s = State::from_pipeline(&p);
+    assert_eq!(s.order, ["zeta", "alpha", "mid"]);
+
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("state.json");
+    state::save(&path, &s).unwrap();
+    let loaded = state::load(&path).unwrap().unwrap();
+    let ids: Vec<&str> = loaded.ordered_steps().iter().map(|(id, _)| *id).collect();
+    assert_eq!(ids, ["zeta", "alpha", "mid"]);
+}
+
+#[test]
+fn state_without_order_falls_back_to_id_order() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("state.json");
+    fs::write(
+        &path,
+        r#"{"steps": {"b": {"status": "pending"}, "a": {"status": "completed"}}}"#,
+    )
+    .unwrap();
+
+    let s = state::load(&path).unwrap().unwrap();
+    assert!(s.order.is_empty());
+    let ids: Vec<&str> = s.ordered_steps().iter().map(|(id, _)| *id).collect();
+    assert_eq!(ids, ["a", "b"]);
+}
+
+#[test]
+fn state_without_tick_count_loads() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("state.json");
+    fs::write(&path, r#"{"steps": {"a": {"status": "pending"}}}"#).unwrap();
+
+    let s = state::load(&path).unwrap().unwrap();
+    assert_eq!(s.tick_count, 0);
+    assert_eq!(s.last_run_at, None);
+    assert_eq!(s.secs_since_last_run(), None);
+}
+
+#[test]
+fn save_replaces_state_without_leaving_tmp_file() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("state.json");
+    let p = pipeline::parse(
+        "version: 1\nworkspace: workspace\nsteps:\n  - id: a\n    type: bash\n    bash: echo a\n",
+    )
+    .unwrap();
+
+    // A crash mid-write leaves a truncated tmp file behind, never a truncated state.json
+    let mut s = State::from_pipeline(&p);
+    state::save(&path, &s).unwrap();
+    fs::write(state::tmp_path(&path), "{\"steps\": {\"a\": {\"sta").unwrap();
+    assert!(state::load(&path).unwrap().is_some());
+
+    s.steps.get_mut("a").unwrap().status = StepStatus::Completed;
+    state::save(&path, &s).unwrap();
+    let loaded = state::load(&path).unwrap().unwrap();
+    assert_eq!(loaded.steps["a"].status, StepStatus::Completed);
+    assert!(!state::tmp_path(&path).exists());
+}
+
+#[test]
+fn failed_save_keeps_previous_state() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("state.json");
+    let p = pipeline::parse(
+        "version: 1\nworkspace: workspace\nsteps:\n  - id: a\n    type: bash\n    bash: echo a\n",
+    )
+    .unwrap();
+    let mut s = State::from_pipeline(&p);
+    state::save(&path, &s).unwrap();
+
+    // The tmp file can't be written, so the new state never reaches state.json
+    fs::create_dir(state::tmp_path(&path)).unwrap();
+    s.steps.get_mut("a").unwrap().status = StepStatus::Failed;
+    assert!(state::save(&path, &s).is_err());
+
+    let loaded = state::load(&path).unwrap().unwrap();
+    assert_eq!(loaded.steps["a"].status, StepStatus::Pending);
+}
+
+#[test]
+fn state_drift_lists_added_and_removed_steps() {
+    let yaml = "version: 1\nworkspace: workspace\nsteps:\n  - id: a\n    type: bash\n    bash: echo a\n  - id: b\n    type: bash\n    bash: echo b\n";
+    let p = pipeline::parse(yaml).unwrap();
+    let mut state = State::from_pipeline(&p);
+    assert!(state.drift(&p).is_empty());
+
+    let edited = pipeline::parse(&yaml.replace("id: b", "id: c")).unwrap();
+    let drift = state.drift(&edited);
+    assert_eq!(drift.added, ["c"]);
+    assert_eq!(drift.removed, ["b"]);
+    assert_eq!(drift.to_string(), "added c; removed b");
+
+    state.steps.remove("b");
+    assert_eq!(state.drift(&p).to_string(), "added b");
 }